@@ -11,6 +11,7 @@ fn main() {
 
     // TODO use structopt or something like that for this
     let mut args = ::std::env::args();
+    let mut mode = None;
     if let Some(next) = args.nth(1) {
         match next.as_str() {
             "-c" | "--config" => {
@@ -19,15 +20,14 @@ fn main() {
                 riirc::Config::default().dump(&mut stdout);
                 return;
             }
-            "-a" | "--attach" => {
-                // this'll attach to the daemon
-                return;
-            }
+            "-a" | "--attach" => mode = Some(Mode::Attach),
+            "-d" | "--daemon" => mode = Some(Mode::Daemon),
 
             "-h" | "--help" | _ => {
                 let help = &[
                     "-c, --config: writes a default config to stdout",
-                    "-a, --attach: TODO",
+                    "-a, --attach: attaches to a running daemon",
+                    "-d, --daemon: runs without a ui, keeping the connections open",
                 ];
 
                 let help =
@@ -50,21 +50,65 @@ fn main() {
         }).unwrap();
 
     macro_rules! check {
-        ($e:expr) => {
-            if $e.is_empty() {
+        ($network:expr, $field:ident) => {
+            if $network.$field.is_empty() {
                 error!(
-                    "'{}' field is missing from the config",
-                    stringify!($e).split('.').last().unwrap()
+                    "'{}' field is missing from the config for '{}'",
+                    stringify!($field),
+                    $network.name
                 );
                 ::std::process::exit(1);
             }
         };
     }
 
-    check!(config.server);
-    check!(config.nick);
-    check!(config.user);
-    check!(config.real);
+    for network in &config.networks {
+        check!(network, server);
+        check!(network, nick);
+        check!(network, user);
+        check!(network, real);
+    }
+
+    match mode {
+        Some(Mode::Attach) => attach(config),
+        Some(Mode::Daemon) => daemon(config),
+        None => riirc::Gui::new(config).run(),
+    }
+}
+
+enum Mode {
+    Attach,
+    Daemon,
+}
+
+#[cfg(unix)]
+fn attach(config: riirc::Config) {
+    let path = riirc::socket_path();
+    match riirc::Gui::attach(config, &path) {
+        Ok(mut gui) => gui.run(),
+        Err(err) => {
+            error!("cannot attach to {}: {}", path.display(), err);
+            ::std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn daemon(config: riirc::Config) {
+    if let Err(err) = riirc::Daemon::new(config).run(riirc::socket_path()) {
+        error!("daemon stopped: {}", err);
+        ::std::process::exit(1);
+    }
+}
+
+#[cfg(not(unix))]
+fn attach(_config: riirc::Config) {
+    error!("attaching to a daemon is only supported on unix");
+    ::std::process::exit(1);
+}
 
-    riirc::Gui::new(config).run();
+#[cfg(not(unix))]
+fn daemon(_config: riirc::Config) {
+    error!("the daemon is only supported on unix");
+    ::std::process::exit(1);
 }
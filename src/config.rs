@@ -1,13 +1,15 @@
+use super::irc;
 use super::ui::*;
 
-use std::collections::HashMap;
 use std::io::Error as IoError;
-use std::path::Path;
-use std::{fmt, fs};
+use std::path::{Path, PathBuf};
+use std::{env, fmt, fs};
 use toml_document::ParserError as TomlError;
 
-#[derive(Debug, Default)]
-pub struct Config {
+/// Everything needed to connect to (and register with) a network
+#[derive(Debug, Clone, PartialEq)]
+pub struct Network {
+    pub name: String,
     pub server: String,
 
     pub nick: String,
@@ -15,7 +17,460 @@ pub struct Config {
     pub real: String,
     pub pass: String,
 
+    /// capabilities to request when connecting
+    pub caps: Vec<String>,
+
+    /// PLAIN or EXTERNAL, empty to skip SASL
+    pub sasl_mechanism: String,
+    pub sasl_user: String,
+    pub sasl_pass: String,
+
+    pub tls: bool,
+    /// verify the certificate chain and hostname
+    pub tls_verify: bool,
+    /// a sha-256 fingerprint to pin the server certificate to
+    pub tls_fingerprint: String,
+    /// path to a PEM encoded CA to trust
+    pub tls_ca: String,
+
+    /// how many lines can be sent back to back, 0 turns flood control off
+    pub flood_burst: u32,
+    /// milliseconds it takes to be able to send another line
+    pub flood_interval: u64,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Self {
+            name: "default".into(),
+            server: String::new(),
+            nick: String::new(),
+            user: String::new(),
+            real: String::new(),
+            pass: String::new(),
+            caps: vec![],
+            sasl_mechanism: String::new(),
+            sasl_user: String::new(),
+            sasl_pass: String::new(),
+            tls: false,
+            tls_verify: true,
+            tls_fingerprint: String::new(),
+            tls_ca: String::new(),
+            flood_burst: 5,
+            flood_interval: 2000,
+        }
+    }
+}
+
+impl Network {
+    /// the tls settings, used whenever the connection is tls even if `tls` isn't set
+    pub fn tls_config(&self) -> irc::TlsConfig {
+        irc::TlsConfig {
+            verify: self.tls_verify,
+            fingerprint: if self.tls_fingerprint.is_empty() {
+                None
+            } else {
+                Some(self.tls_fingerprint.clone())
+            },
+            ca_file: if self.tls_ca.is_empty() {
+                None
+            } else {
+                Some(self.tls_ca.clone().into())
+            },
+        }
+    }
+
+    /// the address to connect to, and the tls settings if tls is enabled
+    pub fn address(&self) -> Option<(String, Option<irc::TlsConfig>)> {
+        // the server field can still be a url, an ircs:// one still gets the tls settings
+        let (server, tls) = parse_address(&self.server, self.tls)?;
+        let tls = if tls { Some(self.tls_config()) } else { None };
+        Some((server, tls))
+    }
+
+    pub fn flood_control(&self) -> irc::FloodControl {
+        irc::FloodControl {
+            burst: self.flood_burst,
+            interval: ::std::time::Duration::from_millis(self.flood_interval),
+        }
+    }
+
+    /// what to send the server once connected
+    pub fn registration(&self) -> Result<irc::Registration, String> {
+        let sasl = if self.sasl_mechanism.is_empty() {
+            None
+        } else {
+            let mechanism = irc::Mechanism::parse(&self.sasl_mechanism)
+                .ok_or_else(|| format!("unknown sasl mechanism: {}", self.sasl_mechanism))?;
+
+            Some(irc::Sasl {
+                mechanism,
+                user: if self.sasl_user.is_empty() {
+                    self.nick.clone()
+                } else {
+                    self.sasl_user.clone()
+                },
+                pass: self.sasl_pass.clone(),
+            })
+        };
+
+        Ok(irc::Registration {
+            nick: self.nick.clone(),
+            user: self.user.clone(),
+            real: self.real.clone(),
+            pass: if self.pass.is_empty() {
+                None
+            } else {
+                Some(self.pass.clone())
+            },
+            caps: self.caps.clone(),
+            sasl,
+        })
+    }
+
+    fn apply(&mut self, key: &str, value: &toml_document::ValueRef) {
+        use toml_document::ValueRef;
+
+        match value {
+            ValueRef::String(data) => {
+                let data = data.get().to_string();
+                match key {
+                    "name" => self.name = data,
+                    "server" => self.server = data,
+                    "nick" => self.nick = data,
+                    "user" => self.user = data,
+                    "real" => self.real = data,
+                    "pass" => self.pass = data,
+                    "caps" => self.caps = data.split_whitespace().map(|s| s.to_owned()).collect(),
+                    "sasl_mechanism" => self.sasl_mechanism = data,
+                    "sasl_user" => self.sasl_user = data,
+                    "sasl_pass" => self.sasl_pass = data,
+                    "tls_fingerprint" => self.tls_fingerprint = data,
+                    "tls_ca" => self.tls_ca = data,
+                    _ => warn!("unknown config key: {}", key),
+                }
+            }
+            ValueRef::Boolean(data) => match key {
+                "tls" => self.tls = data.get(),
+                "tls_verify" => self.tls_verify = data.get(),
+                _ => warn!("unknown config key: {}", key),
+            },
+            ValueRef::Integer(data) if data.get() < 0 => warn!("negative value for: {}", key),
+            ValueRef::Integer(data) => match key {
+                "flood_burst" => self.flood_burst = data.get() as u32,
+                "flood_interval" => self.flood_interval = data.get() as u64,
+                _ => warn!("unknown config key: {}", key),
+            },
+            _ => warn!("unsupported value for: {}", key),
+        }
+    }
+
+    fn dump(&self, container: &mut toml_document::Container) {
+        let caps = self.caps.join(" ");
+        let strings = [
+            ("name", &self.name),
+            ("server", &self.server),
+            ("nick", &self.nick),
+            ("user", &self.user),
+            ("real", &self.real),
+            ("pass", &self.pass),
+            ("caps", &caps),
+            ("sasl_mechanism", &self.sasl_mechanism),
+            ("sasl_user", &self.sasl_user),
+            ("sasl_pass", &self.sasl_pass),
+            ("tls_fingerprint", &self.tls_fingerprint),
+            ("tls_ca", &self.tls_ca),
+        ];
+        for (i, (k, v)) in strings.iter().enumerate() {
+            container.insert_string(i, k.to_string(), v.to_string());
+        }
+
+        let len = strings.len();
+        for (i, (k, v)) in [("tls", self.tls), ("tls_verify", self.tls_verify)]
+            .iter()
+            .enumerate()
+        {
+            container.insert_boolean(len + i, k.to_string(), *v);
+        }
+
+        let len = len + 2;
+        let integers = [
+            ("flood_burst", i64::from(self.flood_burst)),
+            ("flood_interval", self.flood_interval as i64),
+        ];
+        for (i, (k, v)) in integers.iter().enumerate() {
+            container.insert_integer(len + i, k.to_string(), *v);
+        }
+    }
+}
+
+/// Words and patterns, besides our nick, that make a message a highlight
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Highlight {
+    /// matched as whole words, ignoring case
+    pub words: Vec<String>,
+    /// separated by whitespace, so use `\s` for a space
+    pub regexes: Vec<String>,
+}
+
+impl Highlight {
+    fn apply(&mut self, key: &str, value: &toml_document::ValueRef) {
+        use toml_document::ValueRef;
+
+        let split = |s: &str| s.split_whitespace().map(|s| s.to_owned()).collect();
+        match (key, value) {
+            ("words", ValueRef::String(data)) => self.words = split(data.get()),
+            ("regexes", ValueRef::String(data)) => self.regexes = split(data.get()),
+            _ => warn!("unknown config key: {}", key),
+        }
+    }
+
+    fn dump(&self, container: &mut toml_document::Container) {
+        container.insert_string(0, "words", self.words.join(" "));
+        container.insert_string(1, "regexes", self.regexes.join(" "));
+    }
+}
+
+/// How lines look. formats are templates with `{placeholders}` in them, and colors are
+/// names like `bright_red`, one of the 256 colors by number or `#rrggbb`, with `bold`,
+/// `italic` or `underline` in front. `event_placeholder` colors a placeholder in just one event
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    /// strftime style
+    pub timestamp: String,
+    /// a nick gets one of these by its hash, none leaves nicks to the colors
+    pub nick_colors: Vec<String>,
+    pub formats: Vec<(String, String)>,
+    pub colors: Vec<(String, String)>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let pairs = |list: &[(&str, &str)]| {
+            list.iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        Self {
+            timestamp: "%H%M%S ".into(),
+            nick_colors: [
+                "cyan",
+                "magenta",
+                "green",
+                "yellow",
+                "blue",
+                "bright_cyan",
+                "bright_magenta",
+                "bright_green",
+                "bright_yellow",
+                "bright_blue",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+            formats: pairs(&[
+                ("privmsg", "{nick} {text}"),
+                ("own_privmsg", "{nick} {text}"),
+                ("highlight", "{nick} {text}"),
+                ("notice", "{nick} {text}"),
+                ("action", "* {nick} {text}"),
+                ("own_action", "* {nick} {text}"),
+                ("join", "{nick} join {channel}"),
+                ("part", "{nick} left {channel}{reason}"),
+                ("quit", "{nick} quit{reason}"),
+                ("nick", "{nick} is now known as {new}"),
+                ("topic", "{nick} changed the topic to: {topic}"),
+                ("topic_clear", "{nick} cleared the topic"),
+                ("mode", "{nick} sets mode {modes}"),
+                ("error", "error {numeric}: {subject}{text}"),
+                ("ctcp", "{nick} sent ctcp {command}{params}"),
+                ("ctcp_reply", "ctcp {command} reply from {nick}{params}"),
+                ("own_ctcp", "sent ctcp {command} to {nick}{params}"),
+                ("channel_modes", "modes: {modes}"),
+                ("created", "created on {time}"),
+                ("no_topic", "no topic is set"),
+                ("topic_is", "topic: {topic}"),
+                ("topic_set_by", "set by {nick} on {time}"),
+                ("names", "users: {users}"),
+                ("isupport", "server supports: {tokens}"),
+                ("motd", "{line}"),
+                ("sasl_success", "sasl: {message}"),
+                ("sasl_failure", "sasl: {message}"),
+                ("sasl_info", "sasl: {message}"),
+                ("connecting", "connecting to {addr} (attempt {attempt})"),
+                ("connected", "connected to {addr}"),
+                ("disconnected", "disconnected: {reason}"),
+                ("reconnecting", "reconnecting in {delay}s"),
+                ("disconnect", "disconnected from {network}"),
+                ("not_on", "not on {channel}"),
+            ]),
+            colors: pairs(&[
+                ("timestamp", "white"),
+                ("text", "white"),
+                ("own_privmsg_nick", "green"),
+                ("own_action_nick", "green"),
+                ("highlight_nick", "bold bright_yellow"),
+                ("modes", "yellow"),
+                ("error", "red"),
+                ("subject", "cyan"),
+                ("error_text", "white"),
+                ("command", "yellow"),
+                ("channel_modes", "yellow"),
+                ("channel_modes_modes", "white"),
+                ("created", "yellow"),
+                ("no_topic", "yellow"),
+                ("topic_is", "yellow"),
+                ("topic_set_by", "yellow"),
+                ("names", "yellow"),
+                ("isupport", "yellow"),
+                ("motd", "bright_white"),
+                ("sasl_success", "green"),
+                ("sasl_failure", "red"),
+                ("sasl_info", "yellow"),
+                ("connecting", "green"),
+                ("connecting_addr", "cyan"),
+                ("connecting_attempt", "white"),
+                ("connected", "green"),
+                ("connected_addr", "cyan"),
+                ("disconnected", "red"),
+                ("disconnected_reason", "cyan"),
+                ("reconnecting", "yellow"),
+                ("disconnect", "yellow"),
+                ("disconnect_network", "cyan"),
+                ("not_on_channel", "cyan"),
+            ]),
+        }
+    }
+}
+
+impl Theme {
+    fn apply(&mut self, key: &str, value: &toml_document::ValueRef) {
+        use toml_document::ValueRef;
+
+        let split = |s: &str| s.split_whitespace().map(|s| s.to_owned()).collect();
+        match (key, value) {
+            ("timestamp", ValueRef::String(data)) => self.timestamp = data.get().to_string(),
+            ("nick_colors", ValueRef::String(data)) => self.nick_colors = split(data.get()),
+            (key, ValueRef::String(data)) => set(&mut self.formats, key, data.get()),
+            _ => warn!("unknown config key: {}", key),
+        }
+    }
+
+    fn apply_color(&mut self, key: &str, value: &toml_document::ValueRef) {
+        use toml_document::ValueRef;
+
+        match value {
+            ValueRef::String(data) => set(&mut self.colors, key, data.get()),
+            _ => warn!("unknown config key: {}", key),
+        }
+    }
+
+    fn dump(&self, container: &mut toml_document::Container) {
+        container.insert_string(0, "timestamp", self.timestamp.clone());
+        container.insert_string(1, "nick_colors", self.nick_colors.join(" "));
+        for (i, (k, v)) in self.formats.iter().enumerate() {
+            container.insert_string(i + 2, k.clone(), v.clone());
+        }
+    }
+
+    fn dump_colors(&self, container: &mut toml_document::Container) {
+        for (i, (k, v)) in self.colors.iter().enumerate() {
+            container.insert_string(i, k.clone(), v.clone());
+        }
+    }
+}
+
+fn set(list: &mut Vec<(String, String)>, key: &str, value: &str) {
+    match list.iter_mut().find(|(k, _)| k == key) {
+        Some((_, v)) => *v = value.to_string(),
+        None => list.push((key.to_string(), value.to_string())),
+    }
+}
+
+/// where riirc keeps its own files, `$XDG_DATA_HOME/riirc` or `~/.local/share/riirc`
+pub fn data_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(base.join("riirc"))
+}
+
+/// where the daemon listens, `$XDG_RUNTIME_DIR/riirc.sock` or in the data directory
+pub fn socket_path() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir).join("riirc.sock"),
+        _ => data_dir().unwrap_or_default().join("riirc.sock"),
+    }
+}
+
+/// Lines typed into the input, kept between sessions
+#[derive(Debug, Clone, PartialEq)]
+pub struct History {
+    /// where it's saved, empty to not save it. only we can read it
+    pub file: String,
+    /// a history for each buffer, rather than one for all of them
+    pub per_buffer: bool,
+    /// how many lines each history keeps
+    pub size: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        let file = data_dir().map(|dir| dir.join("history"));
+        Self {
+            file: file
+                .map(|file| file.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            per_buffer: false,
+            size: 100,
+        }
+    }
+}
+
+impl History {
+    fn apply(&mut self, key: &str, value: &toml_document::ValueRef) {
+        use toml_document::ValueRef;
+
+        match (key, value) {
+            ("file", ValueRef::String(data)) => self.file = data.get().to_string(),
+            ("per_buffer", ValueRef::Boolean(data)) => self.per_buffer = data.get(),
+            (key, ValueRef::Integer(data)) if data.get() < 0 => {
+                warn!("negative value for: {}", key)
+            }
+            ("size", ValueRef::Integer(data)) => self.size = data.get() as usize,
+            _ => warn!("unknown config key: {}", key),
+        }
+    }
+
+    fn dump(&self, container: &mut toml_document::Container) {
+        container.insert_string(0, "file", self.file.clone());
+        container.insert_boolean(1, "per_buffer", self.per_buffer);
+        container.insert_integer(2, "size", self.size as i64);
+    }
+}
+
+#[derive(Debug)]
+pub struct Config {
+    /// `[[networks]]` entries, which inherit anything they don't set from `[irc]`
+    pub networks: Vec<Network>,
     pub keybinds: Keybinds,
+    pub highlight: Highlight,
+    pub theme: Theme,
+    pub history: History,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            networks: vec![Network::default()],
+            keybinds: Keybinds::default(),
+            highlight: Highlight::default(),
+            theme: Theme::default(),
+            history: History::default(),
+        }
+    }
 }
 
 pub enum Error {
@@ -45,43 +500,84 @@ impl Config {
         use toml_document::*;
         let doc = Document::parse(&data).map_err(Error::CannotParse)?;
 
-        let list = vec![
-            "server".to_string(),
-            "nick".to_string(),
-            "user".to_string(),
-            "real".to_string(),
-            "pass".to_string(),
-        ];
-
-        let mut map: HashMap<String, Option<String>> = HashMap::new();
-        for el in list {
-            map.insert(el, None);
-        }
+        let name = |container: &Container| {
+            container
+                .keys()
+                .markup()
+                .iter()
+                .map(|k| k.get())
+                .collect::<Vec<_>>()
+                .join(".")
+        };
 
-        for child in doc.get_container(0).iter_children() {
-            if let ValueRef::String(data) = child.value() {
-                if let Some(val) = map.get_mut(child.key().get()) {
-                    *val = Some(data.get().to_string());
+        // [irc] is read first so the networks can inherit from it
+        let mut base = Network::default();
+        for container in doc.iter_containers() {
+            if let (ContainerKind::Table, "irc") = (container.kind(), name(container).as_str()) {
+                for child in container.iter_children() {
+                    base.apply(child.key().get(), &child.value());
                 }
             }
         }
 
+        let mut networks = vec![];
         let mut keybinds = Keybinds::default();
-        for child in doc.get_container(1).iter_children() {
-            if let ValueRef::String(data) = child.value() {
-                if let Some(req) = KeyRequest::parse(child.key().get().to_string()) {
-                    keybinds.insert(KeyType::from(data.get().to_string()), req)
+        let mut highlight = Highlight::default();
+        let mut theme = Theme::default();
+        let mut history = History::default();
+        for container in doc.iter_containers() {
+            match (container.kind(), name(container).as_str()) {
+                (ContainerKind::ArrayMember, "networks") => {
+                    let mut network = base.clone();
+                    for child in container.iter_children() {
+                        network.apply(child.key().get(), &child.value());
+                    }
+                    networks.push(network);
+                }
+                (ContainerKind::Table, "keybinds") => {
+                    for child in container.iter_children() {
+                        if let ValueRef::String(data) = child.value() {
+                            if let Some(req) = KeyRequest::parse(child.key().get()) {
+                                keybinds.insert(KeyType::from(data.get().to_string()), req)
+                            }
+                        }
+                    }
+                }
+                (ContainerKind::Table, "highlight") => {
+                    for child in container.iter_children() {
+                        highlight.apply(child.key().get(), &child.value());
+                    }
+                }
+                (ContainerKind::Table, "theme") => {
+                    for child in container.iter_children() {
+                        theme.apply(child.key().get(), &child.value());
+                    }
                 }
+                (ContainerKind::Table, "theme.colors") => {
+                    for child in container.iter_children() {
+                        theme.apply_color(child.key().get(), &child.value());
+                    }
+                }
+                (ContainerKind::Table, "history") => {
+                    for child in container.iter_children() {
+                        history.apply(child.key().get(), &child.value());
+                    }
+                }
+                _ => {}
             }
         }
 
+        // a lone [irc] table is a single network
+        if networks.is_empty() {
+            networks.push(base)
+        }
+
         Ok(Config {
-            server: map.remove("server").unwrap().unwrap(),
-            nick: map.remove("nick").unwrap().unwrap(),
-            user: map.remove("user").unwrap().unwrap(),
-            real: map.remove("real").unwrap().unwrap(),
-            pass: map.remove("pass").unwrap().unwrap(),
+            networks,
             keybinds,
+            highlight,
+            theme,
+            history,
         })
     }
 
@@ -89,30 +585,115 @@ impl Config {
         use toml_document::*;
 
         let mut doc = Document::new();
-        let container = doc.insert_container(0, vec!["irc"].into_iter(), ContainerKind::Table);
-        for (i, (k, v)) in [
-            ("server", &self.server),
-            ("nick", &self.nick),
-            ("user", &self.user),
-            ("real", &self.real),
-            ("pass", &self.pass),
-        ]
-            .into_iter()
-            .enumerate()
-        {
-            container.insert_string(i, k.to_string(), v.to_string());
+        for (i, network) in self.networks.iter().enumerate() {
+            let container =
+                doc.insert_container(i, vec!["networks"].into_iter(), ContainerKind::ArrayMember);
+            network.dump(container);
         }
 
-        let container = doc.insert_container(1, vec!["keybinds"].into_iter(), ContainerKind::Table);
+        let container = doc.insert_container(
+            self.networks.len(),
+            vec!["keybinds"].into_iter(),
+            ContainerKind::Table,
+        );
         for (i, (v, k)) in self.keybinds.iter().enumerate() {
             let _s = container.insert_string(i, format!("{}", v), format!("{}", k));
         }
 
+        let container = doc.insert_container(
+            self.networks.len() + 1,
+            vec!["highlight"].into_iter(),
+            ContainerKind::Table,
+        );
+        self.highlight.dump(container);
+
+        let container = doc.insert_container(
+            self.networks.len() + 2,
+            vec!["theme"].into_iter(),
+            ContainerKind::Table,
+        );
+        self.theme.dump(container);
+
+        let container = doc.insert_container(
+            self.networks.len() + 3,
+            vec!["theme", "colors"].into_iter(),
+            ContainerKind::Table,
+        );
+        self.theme.dump_colors(container);
+
+        let container = doc.insert_container(
+            self.networks.len() + 4,
+            vec!["history"].into_iter(),
+            ContainerKind::Table,
+        );
+        self.history.dump(container);
+
         writeln!(w, "{}", doc.to_string()).expect("to write config");
     }
 
+    pub fn network(&self, name: impl AsRef<str>) -> Option<&Network> {
+        let name = name.as_ref();
+        self.networks.iter().find(|n| n.name == name)
+    }
+
     pub fn save(&self) {
         let mut file = fs::File::create("riirc.toml").expect("to create file");
         self.dump(&mut file);
     }
 }
+
+/// `ircs://host[:port]`, `irc://host[:port]` or `host:port`
+pub fn parse_address(addr: &str, tls: bool) -> Option<(String, bool)> {
+    let (addr, tls, port) = if let Some(addr) = addr.strip_prefix("ircs://") {
+        (addr, true, 6697)
+    } else if let Some(addr) = addr.strip_prefix("irc://") {
+        (addr, false, 6667)
+    } else {
+        (addr, tls, if tls { 6697 } else { 6667 })
+    };
+
+    let addr = addr.trim_end_matches('/');
+    if addr.is_empty() {
+        return None;
+    }
+
+    // an ipv6 address will have a ']' before the port
+    match addr.rfind(':') {
+        Some(pos) if !addr[pos..].contains(']') => Some((addr.to_string(), tls)),
+        _ => Some((format!("{}:{}", addr, port), tls)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ircs_keeps_tls_settings() {
+        let network = Network {
+            server: "ircs://irc.example.com".into(),
+            tls: false,
+            tls_verify: false,
+            tls_fingerprint: "AB:CD".into(),
+            tls_ca: "ca.pem".into(),
+            ..Network::default()
+        };
+
+        let (server, tls) = network.address().unwrap();
+        assert_eq!(server, "irc.example.com:6697");
+        assert_eq!(
+            tls,
+            Some(irc::TlsConfig {
+                verify: false,
+                fingerprint: Some("AB:CD".into()),
+                ca_file: Some("ca.pem".into()),
+            })
+        );
+
+        let network = Network {
+            server: "irc.example.com:6667".into(),
+            ..network
+        };
+        assert_eq!(network.address().unwrap().1, None);
+    }
+}
@@ -0,0 +1,91 @@
+use super::*;
+
+const MAX_BACKLOG: usize = 4096;
+
+/// Every line the daemon has produced, numbered so a ui can ask for what it missed
+pub struct Backlog {
+    seq: u64,
+    lines: irc::Queue<Packet>,
+}
+
+impl Default for Backlog {
+    fn default() -> Self {
+        Self::new(MAX_BACKLOG)
+    }
+}
+
+impl Backlog {
+    pub fn new(max: usize) -> Self {
+        Self {
+            seq: 0,
+            lines: irc::Queue::new(max),
+        }
+    }
+
+    /// returns the packet to send to an attached ui
+    pub fn push(
+        &mut self,
+        network: impl Into<String>,
+        buffer: impl Into<String>,
+        data: impl Into<String>,
+    ) -> Packet {
+        self.seq += 1;
+        let packet = Packet::Line {
+            seq: self.seq,
+            network: network.into(),
+            buffer: buffer.into(),
+            data: data.into(),
+        };
+        self.lines.push(packet.clone());
+        packet
+    }
+
+    /// the lines after `last_seen`, oldest first
+    pub fn since(&self, last_seen: u64) -> Vec<Packet> {
+        // a ui from before we were restarted has seen none of these
+        let last_seen = if last_seen > self.seq { 0 } else { last_seen };
+        self.lines
+            .iter()
+            .filter(|p| match p {
+                Packet::Line { seq, .. } => *seq > last_seen,
+                _ => false,
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn last_seq(&self) -> u64 {
+        self.seq
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seqs(packets: &[Packet]) -> Vec<u64> {
+        packets
+            .iter()
+            .map(|p| match p {
+                Packet::Line { seq, .. } => *seq,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn since() {
+        let mut backlog = Backlog::new(8);
+        for n in 0..5 {
+            backlog.push("net", "#test", format!("line {}", n));
+        }
+
+        assert_eq!(seqs(&backlog.since(0)), vec![1, 2, 3, 4, 5]);
+        assert_eq!(seqs(&backlog.since(3)), vec![4, 5]);
+        assert!(backlog.since(5).is_empty());
+        assert_eq!(backlog.last_seq(), 5);
+
+        // the ui was attached to the daemon that was running before this one
+        assert_eq!(seqs(&backlog.since(100)), vec![1, 2, 3, 4, 5]);
+    }
+}
@@ -0,0 +1,576 @@
+use super::irc::ctcp::Ctcp;
+use super::irc::{self, IrcClient};
+use super::ui::{self, Request};
+use super::Config;
+
+pub mod protocol;
+pub use self::protocol::*;
+
+import!(backlog, session);
+
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::time::Duration;
+use std::{fmt, io};
+
+// how long a ui has to say hello before it's dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    VersionMismatch(u32),
+    UnexpectedPacket(Packet),
+    Rejected(String),
+    Disconnected,
+    AlreadyRunning,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "io error: {}", err),
+            Error::VersionMismatch(v) => write!(f, "unsupported protocol version: {}", v),
+            Error::UnexpectedPacket(p) => write!(f, "unexpected packet: {:?}", p),
+            Error::Rejected(msg) => write!(f, "the daemon said: {}", msg),
+            Error::Disconnected => write!(f, "disconnected during the handshake"),
+            Error::AlreadyRunning => write!(f, "another daemon is already listening"),
+        }
+    }
+}
+
+/// Owns the connections and keeps the backlog while no ui is attached
+pub struct Daemon {
+    config: Config,
+    clients: HashMap<String, irc::Client>,
+    backlog: Backlog,
+    session: Option<Session>,
+}
+
+impl Daemon {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            clients: HashMap::new(),
+            backlog: Backlog::default(),
+            session: None,
+        }
+    }
+
+    /// listens on the socket until killed
+    #[cfg(unix)]
+    pub fn run(mut self, path: impl AsRef<::std::path::Path>) -> Result<(), Error> {
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+        use std::os::unix::net::UnixListener;
+        use std::thread;
+
+        let path = path.as_ref();
+        claim(path)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(Error::Io)?;
+        }
+
+        let listener = UnixListener::bind(path).map_err(Error::Io)?;
+        // whoever can connect can talk as us
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600)).map_err(Error::Io)?;
+        listener.set_nonblocking(true).map_err(Error::Io)?;
+        info!("listening on {}", path.display());
+
+        let networks = self
+            .config
+            .networks
+            .iter()
+            .map(|n| n.name.clone())
+            .collect::<Vec<_>>();
+        for network in networks {
+            self.connect(&network);
+        }
+
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(err) = stream.set_nonblocking(false) {
+                        warn!("cannot attach: {}", err);
+                        continue;
+                    }
+                    match Session::accept(stream, &self.backlog, HANDSHAKE_TIMEOUT) {
+                        Ok(session) => self.attach(session),
+                        Err(err) => warn!("cannot attach: {}", err),
+                    }
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                Err(err) => warn!("cannot accept: {}", err),
+            }
+
+            self.step();
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    /// only one ui can be attached at a time, the newest one wins
+    pub fn attach(&mut self, session: Session) {
+        if let Some(mut old) = self.session.take() {
+            old.send(&Packet::Error {
+                message: "attached from somewhere else".into(),
+            });
+        }
+        info!("ui attached");
+        self.session = Some(session);
+    }
+
+    pub fn step(&mut self) {
+        self.read_clients();
+        self.read_session();
+    }
+
+    fn connect(&mut self, name: &str) {
+        if self.clients.contains_key(name) {
+            return self.push(name, "*status", "already connected");
+        }
+
+        let network = match self.config.network(name) {
+            Some(network) => network.clone(),
+            None => return self.push("", "*status", format!("unknown network: {}", name)),
+        };
+
+        let (addr, tls) = match network.address() {
+            Some(addr) => addr,
+            None => return self.push(name, "*status", "invalid server address"),
+        };
+        let reg = match network.registration() {
+            Ok(reg) => reg,
+            Err(err) => return self.push(name, "*status", err),
+        };
+
+        self.push(name, "*status", format!("connecting to {}", addr));
+        match irc::Client::connect(addr, tls, reg, network.flood_control()) {
+            Ok(client) => {
+                self.clients.insert(name.to_string(), client);
+            }
+            Err(err) => self.push(name, "*status", format!("error: {:?}", err)),
+        }
+    }
+
+    fn read_clients(&mut self) {
+        let mut lines = vec![];
+        let mut failed = vec![];
+
+        for (network, client) in &self.clients {
+            let status = client.status();
+            while let Some(status) = status.try_recv() {
+                lines.push((
+                    network.clone(),
+                    "*status".to_string(),
+                    describe_status(&status),
+                ));
+            }
+
+            if let Some(err) = client.errors().try_recv() {
+                lines.push((
+                    network.clone(),
+                    "*status".into(),
+                    format!("error: {:?}", err),
+                ));
+                failed.push(network.clone());
+            }
+
+            let state = client.state();
+            let isupport = state.isupport();
+            while let Some((_, msg, _)) = state.next_message() {
+                if let Some((buffer, text)) = describe(&msg, &isupport) {
+                    let ts = match msg.tags.server_time() {
+                        Some(ts) => ts.with_timezone(&Local),
+                        None => Local::now(),
+                    };
+                    lines.push((network.clone(), buffer, format!("{} {}", stamp(ts), text)));
+                }
+            }
+        }
+
+        for network in failed {
+            self.clients.remove(&network);
+        }
+        for (network, buffer, data) in lines {
+            self.push(network, buffer, data);
+        }
+    }
+
+    fn read_session(&mut self) {
+        let mut packets = vec![];
+        if let Some(session) = &self.session {
+            while let Some(packet) = session.try_recv() {
+                packets.push(packet);
+            }
+        }
+
+        for packet in packets {
+            match packet {
+                Packet::Input {
+                    network,
+                    buffer,
+                    data,
+                } => self.input(&network, &buffer, &data),
+                Packet::Detach => {
+                    info!("ui detached");
+                    self.session.take();
+                }
+                packet => warn!("unexpected packet from the ui: {:?}", packet),
+            }
+        }
+    }
+
+    /// handles something typed into a buffer of the ui
+    fn input(&mut self, network: &str, buffer: &str, data: &str) {
+        // the main status buffer has no network, so use the only one there is
+        let network = if network.is_empty() && self.clients.len() == 1 {
+            self.clients.keys().next().cloned().unwrap()
+        } else {
+            network.to_string()
+        };
+
+        if data.split(' ').next() == Some("/connect") {
+            let name = data.split(' ').nth(1).unwrap_or(&network).to_string();
+            return self.connect(&name);
+        }
+
+        let client = match self.clients.get(&network) {
+            Some(client) => client,
+            None => return self.push(network.clone(), "*status", "not connected"),
+        };
+
+        let request = match ui::client_request(&network, buffer, data) {
+            Some(Ok(request)) => request,
+            Some(Err(err)) => return self.push(network.clone(), buffer, describe_error(err)),
+            None if data.starts_with('/') => {
+                let command = data.split(' ').next().unwrap_or_default();
+                return self.push(
+                    network.clone(),
+                    buffer,
+                    format!("unknown command: {}", command),
+                );
+            }
+            None if buffer.starts_with('*') => {
+                return self.push(network.clone(), buffer, "cannot send to a status buffer")
+            }
+            None => Request::Privmsg(network.clone(), buffer.to_string(), data.to_string()),
+        };
+
+        let echo = match request {
+            Request::Join(_, channel, key) => {
+                client.join(&channel, key.as_deref());
+                None
+            }
+            Request::Part(_, channel) => {
+                client.part(&channel, "leaving");
+                None
+            }
+            Request::Quit(_, reason) => {
+                client.quit(reason);
+                None
+            }
+            Request::Privmsg(_, target, text) => {
+                client.privmsg(&target, &text);
+                Some((target, text))
+            }
+            request => {
+                warn!("unexpected request: {:?}", request);
+                None
+            }
+        };
+
+        if let Some((target, text)) = echo {
+            let me = client.state().nickname().unwrap_or_default();
+            let text = describe_text(&me, &text, false);
+            let line = format!("{} {}", stamp(Local::now()), text);
+            self.push(network, target, line);
+        }
+    }
+
+    fn push(
+        &mut self,
+        network: impl Into<String>,
+        buffer: impl Into<String>,
+        data: impl Into<String>,
+    ) {
+        let packet = self.backlog.push(network, buffer, data);
+        let gone = match &mut self.session {
+            Some(session) => !session.send(&packet),
+            None => false,
+        };
+        if gone {
+            info!("ui went away");
+            self.session.take();
+        }
+    }
+}
+
+/// makes way for our socket, unless another daemon is still using it
+#[cfg(unix)]
+fn claim(path: &::std::path::Path) -> Result<(), Error> {
+    use std::os::unix::net::UnixStream;
+
+    match UnixStream::connect(path) {
+        Ok(_) => Err(Error::AlreadyRunning),
+        // a stale socket from a previous run would stop us from binding
+        Err(ref err) if err.kind() == io::ErrorKind::ConnectionRefused => {
+            ::std::fs::remove_file(path).map_err(Error::Io)
+        }
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(Error::Io(err)),
+    }
+}
+
+fn stamp<Tz: TimeZone>(ts: DateTime<Tz>) -> String
+where
+    Tz::Offset: fmt::Display,
+{
+    ts.format("%H%M%S").to_string()
+}
+
+fn describe_status(status: &irc::Status) -> String {
+    match status {
+        irc::Status::Connecting { addr, attempt } => {
+            format!("connecting to {} (attempt {})", addr, attempt)
+        }
+        irc::Status::Connected { addr } => format!("connected to {}", addr),
+        irc::Status::Disconnected(err) => format!("disconnected: {:?}", err),
+        irc::Status::Reconnecting { delay } => format!("reconnecting in {}s", delay.as_secs()),
+    }
+}
+
+fn describe_error(err: ui::CommandError) -> String {
+    match err {
+        ui::CommandError::InvalidArgument(msg) => msg,
+        ui::CommandError::InvalidBuffer(name) => format!("cannot do that in {}", name),
+        err => format!("error: {:?}", err),
+    }
+}
+
+/// which buffer a message belongs in, and how it reads
+fn describe(msg: &irc::Message, isupport: &irc::ISupport) -> Option<(String, String)> {
+    use super::irc::{Command, Prefix};
+
+    let nick = match &msg.prefix {
+        Some(Prefix::User { nick, .. }) => nick.as_str(),
+        Some(Prefix::Server { host }) => host.as_str(),
+        None => "",
+    };
+
+    let line = match &msg.command {
+        Command::Privmsg {
+            target,
+            data,
+            is_notice,
+        } => {
+            // a message sent directly to us goes in a buffer named after the sender
+            let buffer = match &msg.prefix {
+                _ if Ctcp::parse(data).is_some_and(|ctcp| !ctcp.is_action()) => "*status",
+                _ if isupport.is_channel(target) => target,
+                Some(Prefix::User { .. }) => nick,
+                _ => "*status",
+            };
+            (buffer.to_string(), describe_text(nick, data, *is_notice))
+        }
+        Command::Join { channel, .. } => (channel.clone(), format!("{} joined {}", nick, channel)),
+        Command::Part { channel, reason } => {
+            let text = match reason {
+                Some(reason) => format!("{} left {}: {}", nick, channel, reason),
+                None => format!("{} left {}", nick, channel),
+            };
+            (channel.clone(), text)
+        }
+        Command::Quit { reason } => ("*status".into(), format!("{} quit: {}", nick, reason)),
+        Command::Nick { nickname } => (
+            "*status".into(),
+            format!("{} is now known as {}", nick, nickname),
+        ),
+        // user modes go in the status buffer
+        Command::Mode {
+            target,
+            modes,
+            args,
+        } => {
+            let buffer = if isupport.is_channel(target) {
+                target.clone()
+            } else {
+                "*status".into()
+            };
+            let mut text = format!("{} sets mode {}", nick, modes);
+            for arg in args {
+                text.push(' ');
+                text.push_str(arg);
+            }
+            (buffer, text)
+        }
+        Command::Topic { channel, topic } if topic.is_empty() => {
+            (channel.clone(), format!("{} cleared the topic", nick))
+        }
+        Command::Topic { channel, topic } => (
+            channel.clone(),
+            format!("{} changed the topic to: {}", nick, topic),
+        ),
+        Command::Error { message } => ("*status".into(), format!("error: {}", message)),
+        Command::Reply { numeric: 366, .. } | Command::Reply { numeric: 329, .. } => return None,
+        Command::Reply {
+            numeric: 324,
+            params,
+        } if params.len() > 2 => (
+            params[1].clone(),
+            format!("modes: {}", params[2..].join(" ")),
+        ),
+        Command::Reply {
+            numeric: 332,
+            params,
+        } if params.len() > 2 => (params[1].clone(), format!("topic: {}", params[2])),
+        Command::Reply {
+            numeric: 333,
+            params,
+        } if params.len() > 2 => (params[1].clone(), format!("set by {}", params[2])),
+        Command::Reply {
+            numeric: 353,
+            params,
+        } if params.len() > 3 => (params[2].clone(), format!("users: {}", params[3])),
+        // the ui creates a buffer for a channel it hasn't seen, so only channel errors go there
+        Command::Reply { numeric, params }
+            if *numeric >= 400 && *numeric < 600 && params.len() > 2 =>
+        {
+            let buffer = if isupport.is_channel(&params[1]) {
+                params[1].clone()
+            } else {
+                "*status".into()
+            };
+            let subject = params[1..params.len() - 1].join(" ");
+            let reason = &params[params.len() - 1];
+            (
+                buffer,
+                format!("error {}: {}: {}", numeric, subject, reason),
+            )
+        }
+        // the first param is always our nick
+        Command::Reply { params, .. } if params.len() > 1 => {
+            ("*status".into(), params[1..].join(" "))
+        }
+        _ => return None,
+    };
+    Some(line)
+}
+
+/// how a message reads, the client has already answered any CTCP queries
+fn describe_text(nick: &str, data: &str, is_notice: bool) -> String {
+    match Ctcp::parse(data) {
+        Some(ref ctcp) if ctcp.is_action() => format!("* {} {}", nick, ctcp.params),
+        Some(ref ctcp) if is_notice => {
+            format!("ctcp {} reply from {}: {}", ctcp.command, nick, ctcp.params)
+        }
+        Some(ctcp) => format!("{} sent ctcp {}", nick, ctcp.command),
+        None if is_notice => format!("-{}- {}", nick, data),
+        None => format!("<{}> {}", nick, data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn describe_line(input: &str) -> Option<(String, String)> {
+        describe(
+            &irc::Message::parse(input).unwrap(),
+            &irc::ISupport::default(),
+        )
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn claim_socket() {
+        use std::os::unix::net::UnixListener;
+
+        let path =
+            ::std::env::temp_dir().join(format!("riirc-claim-{}.sock", ::std::process::id()));
+        let _ = ::std::fs::remove_file(&path);
+        assert!(claim(&path).is_ok());
+
+        let listener = UnixListener::bind(&path).unwrap();
+        match claim(&path) {
+            Err(Error::AlreadyRunning) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert!(path.exists());
+
+        // the socket outlives the listener, but nobody answers it anymore
+        drop(listener);
+        assert!(claim(&path).is_ok());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn describe_messages() {
+        let expected = vec![
+            (
+                ":bob!b@host PRIVMSG #test :hello",
+                Some(("#test", "<bob> hello")),
+            ),
+            (":bob!b@host PRIVMSG me :psst", Some(("bob", "<bob> psst"))),
+            (
+                ":bob!b@host PRIVMSG #test :\x01ACTION waves\x01",
+                Some(("#test", "* bob waves")),
+            ),
+            (
+                ":bob!b@host PRIVMSG me :\x01VERSION\x01",
+                Some(("*status", "bob sent ctcp VERSION")),
+            ),
+            (
+                ":bob!b@host NOTICE me :\x01PING 123\x01",
+                Some(("*status", "ctcp PING reply from bob: 123")),
+            ),
+            (
+                ":irc.host NOTICE * :looking up your host",
+                Some(("*status", "-irc.host- looking up your host")),
+            ),
+            (
+                ":bob!b@host JOIN #test",
+                Some(("#test", "bob joined #test")),
+            ),
+            (
+                ":irc.host 001 me :Welcome to the network",
+                Some(("*status", "Welcome to the network")),
+            ),
+            (
+                ":irc.host 332 me #test :the topic",
+                Some(("#test", "topic: the topic")),
+            ),
+            (
+                ":irc.host 353 me = #test :me @bob",
+                Some(("#test", "users: me @bob")),
+            ),
+            (":irc.host 366 me #test :End of /NAMES list.", None),
+            (
+                ":irc.host 474 me #test :Cannot join channel (+b)",
+                Some(("#test", "error 474: #test: Cannot join channel (+b)")),
+            ),
+            (
+                ":irc.host 433 me me :Nickname is already in use",
+                Some(("*status", "error 433: me: Nickname is already in use")),
+            ),
+            (
+                ":bob!b@host TOPIC #test :new topic",
+                Some(("#test", "bob changed the topic to: new topic")),
+            ),
+            (
+                ":bob!b@host MODE #test +o-v alice carol",
+                Some(("#test", "bob sets mode +o-v alice carol")),
+            ),
+            (":me MODE me :+i", Some(("*status", "me sets mode +i"))),
+            ("PING :12345", None),
+        ];
+
+        for (input, expected) in expected {
+            assert_eq!(
+                describe_line(input),
+                expected.map(|(b, t)| (b.to_string(), t.to_string())),
+                "{}",
+                input
+            );
+        }
+    }
+}
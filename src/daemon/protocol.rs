@@ -0,0 +1,231 @@
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+/// bumped whenever a packet changes shape
+pub const VERSION: u32 = 1;
+
+/// One line on the wire: tab separated fields, the first being the kind
+#[derive(Debug, PartialEq, Clone)]
+pub enum Packet {
+    /// ui -> daemon, first thing sent. `last_seen` is 0 for a fresh ui
+    Hello { version: u32, last_seen: u64 },
+    /// daemon -> ui, the handshake was accepted
+    Welcome { version: u32 },
+    /// daemon -> ui, a line for a buffer. the network is empty for the main status buffer
+    Line {
+        seq: u64,
+        network: String,
+        buffer: String,
+        data: String,
+    },
+    /// ui -> daemon, something typed into a buffer
+    Input {
+        network: String,
+        buffer: String,
+        data: String,
+    },
+    /// ui -> daemon, the ui is going away but the daemon should stay
+    Detach,
+    /// daemon -> ui, the connection will be closed after this
+    Error { message: String },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    EmptyPacket,
+    UnknownPacket(String),
+    MissingField(&'static str),
+    InvalidNumber(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::EmptyPacket => write!(f, "empty packet"),
+            Error::UnknownPacket(kind) => write!(f, "unknown packet: {}", kind),
+            Error::MissingField(field) => write!(f, "missing field: {}", field),
+            Error::InvalidNumber(s) => write!(f, "invalid number: {}", s),
+        }
+    }
+}
+
+impl Packet {
+    pub fn parse(input: &str) -> Result<Self, Error> {
+        let input = input.trim_end_matches(&['\r', '\n'][..]);
+        if input.is_empty() {
+            return Err(Error::EmptyPacket);
+        }
+
+        let mut fields = input.split('\t').map(unescape);
+        let kind = fields.next().ok_or(Error::EmptyPacket)?;
+
+        let mut next = |name: &'static str| fields.next().ok_or(Error::MissingField(name));
+        let number = |s: String| s.parse::<u64>().map_err(|_e| Error::InvalidNumber(s));
+
+        let packet = match kind.as_str() {
+            "HELLO" => Packet::Hello {
+                version: number(next("version")?)? as u32,
+                last_seen: number(next("last_seen")?)?,
+            },
+            "WELCOME" => Packet::Welcome {
+                version: number(next("version")?)? as u32,
+            },
+            "LINE" => Packet::Line {
+                seq: number(next("seq")?)?,
+                network: next("network")?,
+                buffer: next("buffer")?,
+                data: next("data")?,
+            },
+            "INPUT" => Packet::Input {
+                network: next("network")?,
+                buffer: next("buffer")?,
+                data: next("data")?,
+            },
+            "DETACH" => Packet::Detach,
+            "ERROR" => Packet::Error {
+                message: next("message")?,
+            },
+            _ => return Err(Error::UnknownPacket(kind)),
+        };
+        Ok(packet)
+    }
+
+    /// without the trailing newline
+    pub fn encode(&self) -> String {
+        let fields = match self {
+            Packet::Hello { version, last_seen } => {
+                vec!["HELLO".into(), version.to_string(), last_seen.to_string()]
+            }
+            Packet::Welcome { version } => vec!["WELCOME".into(), version.to_string()],
+            Packet::Line {
+                seq,
+                network,
+                buffer,
+                data,
+            } => vec![
+                "LINE".into(),
+                seq.to_string(),
+                escape(network),
+                escape(buffer),
+                escape(data),
+            ],
+            Packet::Input {
+                network,
+                buffer,
+                data,
+            } => vec![
+                "INPUT".into(),
+                escape(network),
+                escape(buffer),
+                escape(data),
+            ],
+            Packet::Detach => vec!["DETACH".into()],
+            Packet::Error { message } => vec!["ERROR".into(), escape(message)],
+        };
+        fields.join("\t")
+    }
+}
+
+pub fn write_packet(w: &mut impl Write, packet: &Packet) -> io::Result<()> {
+    writeln!(w, "{}", packet.encode())?;
+    w.flush()
+}
+
+/// None on a clean end of stream
+pub fn read_packet(r: &mut impl BufRead) -> io::Result<Option<Packet>> {
+    let mut line = String::new();
+    if r.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    Packet::parse(&line)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            ch => out.push(ch),
+        }
+    }
+    out
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut iter = s.chars();
+    while let Some(ch) = iter.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+        match iter.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(ch) => out.push(ch),
+            None => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let packets = vec![
+            Packet::Hello {
+                version: VERSION,
+                last_seen: 42,
+            },
+            Packet::Welcome { version: VERSION },
+            Packet::Line {
+                seq: 7,
+                network: "freenode".into(),
+                buffer: "#rust".into(),
+                data: "tabs\tand\\slashes\r\n".into(),
+            },
+            Packet::Input {
+                network: String::new(),
+                buffer: "*status".into(),
+                data: "/join #rust".into(),
+            },
+            Packet::Detach,
+            Packet::Error {
+                message: "version mismatch".into(),
+            },
+        ];
+
+        for packet in packets {
+            let line = packet.encode();
+            assert!(!line.contains('\n'), "{:?}", line);
+            assert_eq!(Packet::parse(&line), Ok(packet));
+        }
+    }
+
+    #[test]
+    fn parse_error() {
+        assert_eq!(Packet::parse(""), Err(Error::EmptyPacket));
+        assert_eq!(
+            Packet::parse("PING"),
+            Err(Error::UnknownPacket("PING".into()))
+        );
+        assert_eq!(
+            Packet::parse("HELLO\t1"),
+            Err(Error::MissingField("last_seen"))
+        );
+        assert_eq!(
+            Packet::parse("WELCOME\tone"),
+            Err(Error::InvalidNumber("one".into()))
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use super::*;
+
+use crossbeam_channel as channel;
+use std::io::{BufReader, Read, Write};
+use std::thread;
+use std::time::Duration;
+
+/// The daemon's end of an attached ui
+pub struct Session {
+    writer: Box<dyn Write + Send>,
+    packets: channel::Receiver<Packet>,
+}
+
+impl Session {
+    /// does the handshake, then replays everything the ui hasn't seen
+    pub fn start<R, W>(reader: R, writer: W, backlog: &Backlog) -> Result<Self, Error>
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let mut reader = BufReader::new(reader);
+        let mut writer: Box<dyn Write + Send> = Box::new(writer);
+        handshake(&mut reader, &mut writer, backlog)?;
+        Ok(Self::spawn(reader, writer))
+    }
+
+    /// like `start`, but gives up on a ui that doesn't say hello within `timeout`
+    #[cfg(unix)]
+    pub fn accept(
+        stream: ::std::os::unix::net::UnixStream,
+        backlog: &Backlog,
+        timeout: Duration,
+    ) -> Result<Self, Error> {
+        stream.set_read_timeout(Some(timeout)).map_err(Error::Io)?;
+        let mut reader = BufReader::new(stream.try_clone().map_err(Error::Io)?);
+        let mut writer: Box<dyn Write + Send> = Box::new(stream.try_clone().map_err(Error::Io)?);
+        handshake(&mut reader, &mut writer, backlog)?;
+
+        // once attached, the ui can stay quiet for as long as it likes
+        stream.set_read_timeout(None).map_err(Error::Io)?;
+        Ok(Self::spawn(reader, writer))
+    }
+
+    fn spawn<R>(mut reader: BufReader<R>, writer: Box<dyn Write + Send>) -> Self
+    where
+        R: Read + Send + 'static,
+    {
+        let (tx, rx) = channel::unbounded();
+        thread::spawn(move || {
+            loop {
+                match read_packet(&mut reader) {
+                    Ok(Some(packet)) => tx.send(packet),
+                    Ok(None) => break,
+                    Err(err) => {
+                        debug!("bad packet from the ui: {}", err);
+                        break;
+                    }
+                }
+            }
+            // going away without saying anything is still a detach
+            tx.send(Packet::Detach);
+        });
+
+        Self {
+            writer,
+            packets: rx,
+        }
+    }
+
+    /// returns false if the ui has gone away
+    pub fn send(&mut self, packet: &Packet) -> bool {
+        match write_packet(&mut self.writer, packet) {
+            Ok(_) => true,
+            Err(err) => {
+                debug!("cannot write to the ui: {}", err);
+                false
+            }
+        }
+    }
+
+    pub fn try_recv(&self) -> Option<Packet> {
+        self.packets.try_recv()
+    }
+}
+
+fn handshake<R: Read>(
+    reader: &mut BufReader<R>,
+    writer: &mut Box<dyn Write + Send>,
+    backlog: &Backlog,
+) -> Result<(), Error> {
+    let last_seen = match read_packet(reader).map_err(Error::Io)? {
+        Some(Packet::Hello { version, last_seen }) if version == VERSION => last_seen,
+        Some(Packet::Hello { version, .. }) => {
+            let message = format!("unsupported version: {} (expected {})", version, VERSION);
+            // they'll find out one way or another
+            let _ = write_packet(writer, &Packet::Error { message });
+            return Err(Error::VersionMismatch(version));
+        }
+        Some(packet) => return Err(Error::UnexpectedPacket(packet)),
+        None => return Err(Error::Disconnected),
+    };
+
+    write_packet(writer, &Packet::Welcome { version: VERSION }).map_err(Error::Io)?;
+    for packet in backlog.since(last_seen) {
+        write_packet(writer, &packet).map_err(Error::Io)?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::ui::Remote;
+    use std::os::unix::net::UnixStream;
+
+    fn backlog() -> Backlog {
+        let mut backlog = Backlog::default();
+        backlog.push("", "*status", "hello");
+        backlog.push("net", "#test", "first");
+        backlog.push("net", "#test", "second");
+        backlog
+    }
+
+    fn start(stream: UnixStream, backlog: Backlog) -> thread::JoinHandle<Result<Session, Error>> {
+        thread::spawn(move || Session::start(stream.try_clone().unwrap(), stream, &backlog))
+    }
+
+    fn recv(remote: &Remote) -> Packet {
+        for _ in 0..100 {
+            if let Some(packet) = remote.try_recv() {
+                return packet;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("nothing received")
+    }
+
+    fn recv_session(session: &Session) -> Packet {
+        for _ in 0..100 {
+            if let Some(packet) = session.try_recv() {
+                return packet;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("nothing received")
+    }
+
+    fn data(packet: Packet) -> String {
+        match packet {
+            Packet::Line { data, .. } => data,
+            packet => panic!("unexpected packet: {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn replay_missed() {
+        let (ui, daemon) = UnixStream::pair().unwrap();
+        let handle = start(daemon, backlog());
+
+        let remote = Remote::attach(ui.try_clone().unwrap(), ui, 1).unwrap();
+        let mut session = handle.join().unwrap().unwrap();
+
+        assert_eq!(data(recv(&remote)), "first");
+        assert_eq!(data(recv(&remote)), "second");
+        assert_eq!(remote.last_seen(), 3);
+
+        let live = Backlog::default().push("net", "#test", "live");
+        assert!(session.send(&live));
+        assert_eq!(data(recv(&remote)), "live");
+    }
+
+    #[test]
+    fn input_and_detach() {
+        let (ui, daemon) = UnixStream::pair().unwrap();
+        let handle = start(daemon, Backlog::default());
+
+        let remote = Remote::attach(ui.try_clone().unwrap(), ui, 0).unwrap();
+        let session = handle.join().unwrap().unwrap();
+
+        remote.send_input(Some("net"), "#test", "hi there");
+        remote.detach();
+
+        let mut packets = vec![];
+        for _ in 0..100 {
+            match session.try_recv() {
+                Some(Packet::Detach) => break,
+                Some(packet) => packets.push(packet),
+                None => thread::sleep(Duration::from_millis(10)),
+            }
+        }
+
+        assert_eq!(
+            packets,
+            vec![Packet::Input {
+                network: "net".into(),
+                buffer: "#test".into(),
+                data: "hi there".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn version_mismatch() {
+        let (ui, daemon) = UnixStream::pair().unwrap();
+        let handle = start(daemon, Backlog::default());
+
+        let mut writer = ui.try_clone().unwrap();
+        let hello = Packet::Hello {
+            version: VERSION + 1,
+            last_seen: 0,
+        };
+        write_packet(&mut writer, &hello).unwrap();
+
+        match handle.join().unwrap() {
+            Err(Error::VersionMismatch(v)) => assert_eq!(v, VERSION + 1),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("handshake should've failed"),
+        }
+
+        match read_packet(&mut BufReader::new(ui)).unwrap() {
+            Some(Packet::Error { .. }) => {}
+            packet => panic!("unexpected packet: {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn silent_ui() {
+        let (_ui, daemon) = UnixStream::pair().unwrap();
+        let timeout = Duration::from_millis(50);
+
+        match Session::accept(daemon, &Backlog::default(), timeout) {
+            Err(Error::Io(_)) => {}
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("handshake should've timed out"),
+        }
+    }
+
+    #[test]
+    fn accept_then_wait() {
+        let (ui, daemon) = UnixStream::pair().unwrap();
+        let timeout = Duration::from_millis(50);
+        let handle = thread::spawn(move || Session::accept(daemon, &backlog(), timeout));
+
+        let remote = Remote::attach(ui.try_clone().unwrap(), ui, 3).unwrap();
+        let session = handle.join().unwrap().unwrap();
+
+        // well past the handshake timeout, the ui is still attached
+        thread::sleep(timeout * 3);
+        assert_eq!(session.try_recv(), None);
+
+        remote.send_input(None, "*status", "hi");
+        match recv_session(&session) {
+            Packet::Input { data, .. } => assert_eq!(data, "hi"),
+            packet => panic!("unexpected packet: {:?}", packet),
+        }
+    }
+}
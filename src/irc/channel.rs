@@ -1,9 +1,15 @@
-use std::collections::{HashMap, HashSet};
+use super::{CaseMapping, ISupport};
+
+use chrono::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::sync::{Arc, RwLock};
 
+/// Channels are keyed by their case folded name
 #[derive(Default)]
 pub struct Channels {
     data: RwLock<HashMap<String, Arc<Channel>>>,
+    isupport: RwLock<ISupport>,
 }
 
 impl Channels {
@@ -11,20 +17,28 @@ impl Channels {
         Channels::default()
     }
 
+    /// used for the channels added after this
+    pub(crate) fn set_isupport(&self, isupport: &ISupport) {
+        *self.isupport.write().unwrap() = isupport.clone();
+    }
+
+    fn fold(&self, channel: &str) -> String {
+        self.isupport.read().unwrap().fold(channel)
+    }
+
     pub fn add(&self, channel: impl Into<String>) -> Arc<Channel> {
         let channel = channel.into();
 
         debug_assert!(
-            self.contains(&channel),
+            !self.contains(&channel),
             "channel '{}' shouldn't exist",
             &channel
         );
 
-        let chan = Arc::new(Channel::new());
-        self.data
-            .write()
-            .unwrap()
-            .insert(channel, Arc::clone(&chan));
+        let isupport = self.isupport.read().unwrap();
+        let key = isupport.fold(&channel);
+        let chan = Arc::new(Channel::new(channel, &isupport));
+        self.data.write().unwrap().insert(key, Arc::clone(&chan));
         chan
     }
 
@@ -32,24 +46,46 @@ impl Channels {
         let channel = channel.as_ref();
 
         debug_assert!(
-            !self.contains(&channel),
+            self.contains(channel),
             "channel '{}' should exist",
             &channel
         );
 
-        self.data.write().unwrap().remove(channel);
+        let key = self.fold(channel);
+        self.data.write().unwrap().remove(&key);
     }
 
     pub fn contains(&self, channel: impl AsRef<str>) -> bool {
-        self.data.read().unwrap().contains_key(channel.as_ref())
+        let key = self.fold(channel.as_ref());
+        self.data.read().unwrap().contains_key(&key)
+    }
+
+    /// the names of the channels, as they were joined
+    pub fn names(&self) -> Vec<String> {
+        let data = self.data.read().unwrap();
+        let mut keys = data.keys().collect::<Vec<_>>();
+        keys.sort();
+        keys.into_iter()
+            .map(|key| data[key].name().to_owned())
+            .collect()
+    }
+
+    /// the names of the channels `nick` is in
+    pub fn with_nick(&self, nick: impl AsRef<str>) -> Vec<String> {
+        let nick = nick.as_ref();
+        self.names()
+            .into_iter()
+            .filter(|name| self.get(name).is_some_and(|ch| ch.contains(nick)))
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.data.write().unwrap().clear();
     }
 
     pub fn get(&self, channel: impl AsRef<str>) -> Option<Arc<Channel>> {
-        self.data
-            .read()
-            .unwrap()
-            .get(channel.as_ref())
-            .map(Arc::clone)
+        let key = self.fold(channel.as_ref());
+        self.data.read().unwrap().get(&key).map(Arc::clone)
     }
 
     pub fn update_nick(&self, old: impl AsRef<str>, nick: impl Into<String>) {
@@ -68,21 +104,63 @@ impl Channels {
     }
 }
 
+/// Someone in a channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub nick: String,
+    /// symbols for the prefix modes they have, highest rank first
+    pub prefix: String,
+}
+
+impl Member {
+    /// the symbol that's shown in front of their nick
+    pub fn highest(&self) -> Option<char> {
+        self.prefix.chars().next()
+    }
+}
+
+impl fmt::Display for Member {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(symbol) = self.highest() {
+            write!(f, "{}", symbol)?;
+        }
+        write!(f, "{}", self.nick)
+    }
+}
+
 // FIXME: this does all sorts of dumb allocations
-#[derive(Default)]
 pub struct Channel {
+    name: String,
+    casemapping: CaseMapping,
+    // the prefix modes and their symbols, highest rank first
+    prefix: Vec<(char, char)>,
     inner: RwLock<Inner>,
 }
 
 #[derive(Default)]
 struct Inner {
     topic: Option<String>,
-    users: HashSet<Arc<String>>,
+    // who set the topic, and when
+    topic_info: Option<(String, DateTime<Utc>)>,
+    key: Option<String>,
+    // everything but the lists and the prefix modes
+    modes: BTreeMap<char, Option<String>>,
+    // keyed by the case folded nick
+    users: HashMap<String, Member>,
 }
 
 impl Channel {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(name: impl Into<String>, isupport: &ISupport) -> Self {
+        Self {
+            name: name.into(),
+            casemapping: isupport.casemapping,
+            prefix: isupport.prefix.clone(),
+            inner: RwLock::new(Inner::default()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
     pub fn set_topic(&self, topic: impl Into<String>) {
@@ -95,36 +173,238 @@ impl Channel {
         inner.topic.clone()
     }
 
+    pub fn clear_topic(&self) {
+        let inner = &mut self.inner.write().unwrap();
+        inner.topic = None;
+        inner.topic_info = None;
+    }
+
+    pub fn set_topic_info(&self, setter: impl Into<String>, time: DateTime<Utc>) {
+        let inner = &mut self.inner.write().unwrap();
+        inner.topic_info = Some((setter.into(), time))
+    }
+
+    /// who set the topic, and when
+    pub fn topic_info(&self) -> Option<(String, DateTime<Utc>)> {
+        let inner = &self.inner.read().unwrap();
+        inner.topic_info.clone()
+    }
+
+    pub fn set_key(&self, key: Option<String>) {
+        let inner = &mut self.inner.write().unwrap();
+        inner.key = key
+    }
+
+    /// the key used to join the channel, if any
+    pub fn key(&self) -> Option<String> {
+        let inner = &self.inner.read().unwrap();
+        inner.key.clone()
+    }
+
+    /// sets (or unsets) a channel setting, like `n` or `l 10`
+    pub fn set_mode(&self, mode: char, set: bool, arg: Option<String>) {
+        let inner = &mut self.inner.write().unwrap();
+        if mode == 'k' {
+            inner.key = if set { arg.clone() } else { None };
+        }
+
+        if set {
+            inner.modes.insert(mode, arg);
+        } else {
+            inner.modes.remove(&mode);
+        }
+    }
+
+    /// forgets the settings, before a fresh RPL_CHANNELMODEIS
+    pub fn clear_modes(&self) {
+        let inner = &mut self.inner.write().unwrap();
+        inner.modes.clear();
+    }
+
+    /// the settings, and their arguments
+    pub fn modes(&self) -> Vec<(char, Option<String>)> {
+        let inner = &self.inner.read().unwrap();
+        inner
+            .modes
+            .iter()
+            .map(|(&mode, arg)| (mode, arg.clone()))
+            .collect()
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        let inner = &self.inner.read().unwrap();
+        match inner.modes.get(&'l') {
+            Some(Some(limit)) => limit.parse().ok(),
+            _ => None,
+        }
+    }
+
     pub fn contains(&self, nick: impl AsRef<str>) -> bool {
+        let key = self.casemapping.fold(nick.as_ref());
         let inner = &self.inner.read().unwrap();
-        inner.users.contains(&nick.as_ref().to_owned())
+        inner.users.contains_key(&key)
     }
 
     pub fn add(&self, nick: impl Into<String>) {
+        self.add_with_prefix(nick, "")
+    }
+
+    /// `prefix` is the symbols from a NAMES reply, like `@+`
+    pub fn add_with_prefix(&self, nick: impl Into<String>, prefix: &str) {
+        let nick = nick.into();
+        let key = self.casemapping.fold(&nick);
+        let prefix = self.ranked(|symbol| prefix.contains(symbol));
         let inner = &mut self.inner.write().unwrap();
-        inner.users.insert(Arc::new(nick.into()));
+        inner.users.insert(key, Member { nick, prefix });
     }
 
     pub fn remove(&self, user: impl AsRef<str>) {
+        let key = self.casemapping.fold(user.as_ref());
+        let inner = &mut self.inner.write().unwrap();
+        inner.users.remove(&key);
+    }
+
+    /// forgets every user, before a fresh NAMES reply
+    pub fn clear_users(&self) {
         let inner = &mut self.inner.write().unwrap();
-        inner.users.remove(&user.as_ref().to_owned());
+        inner.users.clear();
     }
 
     pub fn update(&self, old: impl AsRef<str>, new: impl Into<String>) {
-        let old = old.as_ref().to_owned();
+        let old = self.casemapping.fold(old.as_ref());
+        let inner = &mut self.inner.write().unwrap();
+        if let Some(mut member) = inner.users.remove(&old) {
+            member.nick = new.into();
+            inner
+                .users
+                .insert(self.casemapping.fold(&member.nick), member);
+        }
+    }
+
+    /// gives (or takes) a prefix mode, like `o`
+    pub fn set_member_mode(&self, nick: impl AsRef<str>, mode: char, set: bool) {
+        let symbol = match self.prefix.iter().find(|&&(m, _)| m == mode) {
+            Some(&(_, symbol)) => symbol,
+            None => return,
+        };
+
+        let key = self.casemapping.fold(nick.as_ref());
         let inner = &mut self.inner.write().unwrap();
-        if inner.users.contains(&old) {
-            inner.users.remove(&old);
-            inner.users.insert(Arc::new(new.into()));
+        if let Some(member) = inner.users.get_mut(&key) {
+            let prefix = self.ranked(|s| {
+                if s == symbol {
+                    set
+                } else {
+                    member.prefix.contains(s)
+                }
+            });
+            member.prefix = prefix
         }
     }
 
-    pub fn users(&self) -> Vec<Arc<String>> {
+    pub fn member(&self, nick: impl AsRef<str>) -> Option<Member> {
+        let key = self.casemapping.fold(nick.as_ref());
+        let inner = &self.inner.read().unwrap();
+        inner.users.get(&key).cloned()
+    }
+
+    /// highest rank first, then sorted the way the server compares nicks
+    pub fn users(&self) -> Vec<Member> {
         let inner = &*self.inner.read().unwrap();
-        let mut v = inner.users.iter().map(Arc::clone).collect::<Vec<_>>();
-        // IRC uses a non ASCII lexigraphical comparison for nicknames
-        // TODO implement it
-        v.sort();
-        v
+        let mut v = inner.users.iter().collect::<Vec<_>>();
+        v.sort_by_key(|&(key, member)| (self.rank(member), key));
+        v.into_iter().map(|(_, member)| member.clone()).collect()
+    }
+
+    // the symbols that pass `keep`, in rank order
+    fn ranked(&self, keep: impl Fn(char) -> bool) -> String {
+        self.prefix
+            .iter()
+            .map(|&(_, symbol)| symbol)
+            .filter(|&symbol| keep(symbol))
+            .collect()
+    }
+
+    fn rank(&self, member: &Member) -> usize {
+        member
+            .highest()
+            .and_then(|symbol| self.prefix.iter().position(|&(_, s)| s == symbol))
+            .unwrap_or(self.prefix.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn casemapping() {
+        let channels = Channels::new();
+        channels.add("#Test[1]");
+        assert!(channels.contains("#test{1}"));
+        assert_eq!(channels.names(), vec!["#Test[1]"]);
+
+        let channel = channels.get("#TEST[1]").unwrap();
+        channel.add("Bob");
+        channel.add("alice");
+        channel.add("[away]");
+        assert!(channel.contains("BOB"));
+
+        assert_eq!(channels.with_nick("BOB"), vec!["#Test[1]"]);
+        assert!(channels.with_nick("carol").is_empty());
+
+        channels.update_nick("bob", "Robert");
+        let users = channel
+            .users()
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(users, vec!["alice", "Robert", "[away]"]);
+
+        channels.remove("#test{1}");
+        assert!(channels.names().is_empty());
+    }
+
+    fn users(channel: &Channel) -> Vec<String> {
+        channel.users().iter().map(|m| m.to_string()).collect()
+    }
+
+    #[test]
+    fn prefixes() {
+        let mut isupport = ISupport::new();
+        isupport.apply("PREFIX=(qaohv)~&@%+");
+        let channel = Channel::new("#test", &isupport);
+
+        channel.add_with_prefix("carol", "");
+        channel.add_with_prefix("bob", "+@");
+        channel.add_with_prefix("alice", "+");
+        channel.add("dave");
+        assert_eq!(users(&channel), vec!["@bob", "+alice", "carol", "dave"]);
+        assert_eq!(channel.member("bob").unwrap().prefix, "@+");
+
+        channel.set_member_mode("dave", 'q', true);
+        channel.set_member_mode("bob", 'o', false);
+        channel.set_member_mode("carol", 'h', true);
+        assert_eq!(users(&channel), vec!["~dave", "%carol", "+alice", "+bob"]);
+
+        // the prefix survives a nick change
+        channel.update("dave", "david");
+        assert_eq!(channel.member("DAVID").unwrap().prefix, "~");
+    }
+
+    #[test]
+    fn modes() {
+        let channel = Channel::new("#test", &ISupport::default());
+        channel.set_mode('n', true, None);
+        channel.set_mode('l', true, Some("10".into()));
+        channel.set_mode('k', true, Some("hunter2".into()));
+        assert_eq!(channel.limit(), Some(10));
+        assert_eq!(channel.key(), Some("hunter2".into()));
+
+        channel.set_mode('l', false, None);
+        channel.set_mode('k', false, Some("hunter2".into()));
+        assert_eq!(channel.limit(), None);
+        assert_eq!(channel.key(), None);
+        assert_eq!(channel.modes(), vec![('n', None)]);
     }
 }
@@ -0,0 +1,98 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Exponential backoff, with half of each delay randomized
+#[derive(Debug)]
+pub struct Backoff {
+    base: u64,
+    max: u64,
+    attempt: u32,
+    seed: u64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(2), Duration::from_secs(5 * 60))
+    }
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos() as u64 ^ d.as_secs())
+            .unwrap_or(0x2545_F491_4F6C_DD1D);
+
+        Self {
+            base: millis(base),
+            max: millis(max),
+            attempt: 0,
+            // xorshift gets stuck on zero
+            seed: seed | 1,
+        }
+    }
+
+    /// how many times we've backed off since the last reset
+    pub fn attempts(&self) -> u32 {
+        self.attempt
+    }
+
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        let shift = ::std::cmp::min(self.attempt, 16);
+        let delay = ::std::cmp::min(self.base << shift, self.max);
+        self.attempt += 1;
+
+        let half = delay / 2;
+        let jitter = if half == 0 { 0 } else { self.random() % half };
+        Duration::from_millis(half + jitter)
+    }
+
+    fn random(&mut self) -> u64 {
+        let mut x = self.seed;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.seed = x;
+        x
+    }
+}
+
+pub(super) fn millis(d: Duration) -> u64 {
+    d.as_secs() * 1000 + u64::from(d.subsec_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grows_and_caps() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(30));
+        let expected = &[1000, 2000, 4000, 8000, 16000, 30000, 30000, 30000];
+        for (n, max) in expected.iter().enumerate() {
+            let delay = millis(backoff.next_delay());
+            assert!(
+                delay >= max / 2 && delay < *max,
+                "{}: {} ({})",
+                n,
+                delay,
+                max
+            );
+        }
+        assert_eq!(backoff.attempts(), expected.len() as u32);
+
+        backoff.reset();
+        assert_eq!(backoff.attempts(), 0);
+        assert!(millis(backoff.next_delay()) < 1000);
+    }
+
+    #[test]
+    fn jitters() {
+        let mut backoff = Backoff::new(Duration::from_secs(60), Duration::from_secs(60));
+        let delays = (0..8).map(|_| backoff.next_delay()).collect::<Vec<_>>();
+        assert!(delays.iter().any(|d| *d != delays[0]));
+    }
+}
@@ -0,0 +1,344 @@
+use super::*;
+
+use std::collections::{HashMap, HashSet};
+
+// keep the REQ lines well under the 512 byte limit
+const MAX_REQ_LEN: usize = 400;
+
+/// Drives the `CAP LS 302` / `REQ` / `ACK` / `NAK` / `END` exchange
+#[derive(Debug, Default)]
+pub struct Negotiator {
+    sasl: Option<Authenticator>,
+    wanted: Vec<String>,
+    available: HashMap<String, Option<String>>,
+    pending: HashSet<String>,
+    listing: bool,
+    finished: bool,
+}
+
+impl Negotiator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// authenticate with these credentials once the server acks `sasl`
+    pub fn set_sasl(&mut self, sasl: Sasl) {
+        self.sasl = Some(Authenticator::new(sasl));
+    }
+
+    /// starts negotiation, this should be sent before NICK/USER
+    pub fn start(&mut self, client: &impl IrcClient, wanted: impl Into<Vec<String>>) {
+        *self = Self {
+            sasl: self.sasl.take().map(|auth| auth.reset()),
+            wanted: wanted.into(),
+            ..Self::default()
+        };
+
+        if self.sasl.is_some() && !self.wanted.iter().any(|cap| cap == "sasl") {
+            self.wanted.push("sasl".into());
+        }
+
+        if self.wanted.is_empty() {
+            self.finished = true;
+            return;
+        }
+
+        self.listing = true;
+        client.cap_ls();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// the value the server advertised for the capability, e.g. `sasl=PLAIN,EXTERNAL`
+    pub fn value(&self, cap: impl AsRef<str>) -> Option<&str> {
+        self.available
+            .get(cap.as_ref())
+            .and_then(|v| v.as_ref().map(|s| s.as_str()))
+    }
+
+    /// registration finished, the server isn't waiting on us anymore
+    pub fn finish(&mut self) {
+        self.listing = false;
+        self.finished = true;
+        self.pending.clear();
+    }
+
+    pub fn handle(
+        &mut self,
+        client: &impl IrcClient,
+        state: &State,
+        subcommand: &str,
+        more: bool,
+        caps: &[String],
+    ) {
+        match subcommand {
+            "LS" => {
+                self.advertise(caps);
+                if more || !self.listing {
+                    return;
+                }
+                self.listing = false;
+
+                let wanted = self.wanted_available(state);
+                if wanted.is_empty() {
+                    self.end(client);
+                } else {
+                    self.request(client, wanted);
+                }
+            }
+
+            "ACK" => {
+                for cap in caps {
+                    // a leading '-' means the capability was disabled
+                    let name = cap.trim_start_matches('-');
+                    if name.len() == cap.len() {
+                        debug!("enabled capability: {}", cap);
+                        state.enable_cap(name);
+                    } else {
+                        state.disable_cap(name);
+                    }
+                    self.pending.remove(name);
+                }
+
+                // hold off on ending until the server tells us how it went
+                if caps.iter().any(|cap| cap == "sasl") {
+                    if let Some(sasl) = &mut self.sasl {
+                        sasl.begin(client)
+                    }
+                }
+                self.try_end(client);
+            }
+
+            "NAK" => {
+                for cap in caps {
+                    warn!("capability was rejected: {}", cap);
+                    self.pending.remove(cap);
+                }
+                self.try_end(client);
+            }
+
+            "NEW" => {
+                self.advertise(caps);
+                let wanted = self.wanted_available(state);
+                if !wanted.is_empty() {
+                    self.request(client, wanted);
+                }
+            }
+
+            "DEL" => {
+                for cap in caps {
+                    debug!("capability was removed: {}", cap);
+                    self.available.remove(cap);
+                    state.disable_cap(cap);
+                }
+            }
+
+            sub => trace!("ignoring CAP {}: {:?}", sub, caps),
+        }
+    }
+
+    /// the server sent an `AUTHENTICATE` line
+    pub fn authenticate(&mut self, client: &impl IrcClient, data: &str) {
+        if let Some(sasl) = &mut self.sasl {
+            sasl.challenge(client, data)
+        }
+    }
+
+    /// one of the SASL numerics (900-908)
+    pub fn sasl_reply(&mut self, client: &impl IrcClient, numeric: u16) {
+        let done = match &mut self.sasl {
+            Some(sasl) => sasl.reply(numeric),
+            None => false,
+        };
+
+        if done {
+            self.try_end(client)
+        }
+    }
+
+    fn advertise(&mut self, caps: &[String]) {
+        for cap in caps {
+            let mut parts = cap.splitn(2, '=');
+            let name = parts.next().unwrap().to_owned();
+            let value = parts.next().map(|s| s.to_owned());
+            self.available.insert(name, value);
+        }
+    }
+
+    fn wanted_available(&self, state: &State) -> Vec<String> {
+        self.wanted
+            .iter()
+            .filter(|cap| self.available.contains_key(cap.as_str()))
+            .filter(|cap| !state.has_cap(cap) && !self.pending.contains(cap.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    fn request(&mut self, client: &impl IrcClient, caps: Vec<String>) {
+        let mut line: Vec<String> = vec![];
+        let mut len = 0;
+        for cap in caps {
+            if len + cap.len() + 1 > MAX_REQ_LEN {
+                client.cap_req(&line);
+                line.clear();
+                len = 0;
+            }
+            len += cap.len() + 1;
+            self.pending.insert(cap.clone());
+            line.push(cap);
+        }
+
+        if !line.is_empty() {
+            client.cap_req(&line);
+        }
+    }
+
+    fn try_end(&mut self, client: &impl IrcClient) {
+        let authenticating = self.sasl.as_ref().is_some_and(|s| s.in_progress());
+        if self.pending.is_empty() && !authenticating {
+            self.end(client)
+        }
+    }
+
+    fn end(&mut self, client: &impl IrcClient) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        client.cap_end();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irc::client::testing::Recorder;
+
+    fn caps(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn multiline_ls() {
+        let (client, state) = (Recorder::default(), State::new());
+        let mut neg = Negotiator::new();
+
+        neg.start(&client, caps(&["multi-prefix", "sasl", "batch"]));
+        assert_eq!(client.take(), vec!["CAP LS 302"]);
+
+        neg.handle(
+            &client,
+            &state,
+            "LS",
+            true,
+            &caps(&["multi-prefix", "away-notify"]),
+        );
+        assert!(client.take().is_empty());
+
+        neg.handle(
+            &client,
+            &state,
+            "LS",
+            false,
+            &caps(&["sasl=PLAIN,EXTERNAL"]),
+        );
+        assert_eq!(client.take(), vec!["CAP REQ :multi-prefix sasl"]);
+        assert_eq!(neg.value("sasl"), Some("PLAIN,EXTERNAL"));
+
+        neg.handle(&client, &state, "ACK", false, &caps(&["multi-prefix"]));
+        assert!(client.take().is_empty());
+        assert!(!neg.is_finished());
+
+        neg.handle(&client, &state, "NAK", false, &caps(&["sasl"]));
+        assert_eq!(client.take(), vec!["CAP END"]);
+        assert!(neg.is_finished());
+
+        assert_eq!(state.caps(), vec!["multi-prefix"]);
+    }
+
+    #[test]
+    fn nothing_wanted() {
+        let (client, state) = (Recorder::default(), State::new());
+        let mut neg = Negotiator::new();
+
+        neg.start(&client, vec![]);
+        assert!(client.take().is_empty());
+        assert!(neg.is_finished());
+
+        neg.start(&client, caps(&["batch"]));
+        neg.handle(&client, &state, "LS", false, &caps(&["multi-prefix"]));
+        assert_eq!(client.take(), vec!["CAP LS 302", "CAP END"]);
+        assert!(state.caps().is_empty());
+    }
+
+    #[test]
+    fn new_and_del() {
+        let (client, state) = (Recorder::default(), State::new());
+        let mut neg = Negotiator::new();
+
+        neg.start(&client, caps(&["away-notify", "batch"]));
+        neg.handle(&client, &state, "LS", false, &caps(&["away-notify"]));
+        neg.handle(&client, &state, "ACK", false, &caps(&["away-notify"]));
+        assert_eq!(
+            client.take(),
+            vec!["CAP LS 302", "CAP REQ :away-notify", "CAP END"]
+        );
+
+        neg.handle(&client, &state, "NEW", false, &caps(&["batch", "chghost"]));
+        assert_eq!(client.take(), vec!["CAP REQ :batch"]);
+        neg.handle(&client, &state, "ACK", false, &caps(&["batch"]));
+        // END is only sent once
+        assert!(client.take().is_empty());
+        assert_eq!(state.caps(), vec!["away-notify", "batch"]);
+
+        neg.handle(&client, &state, "DEL", false, &caps(&["away-notify"]));
+        assert_eq!(state.caps(), vec!["batch"]);
+        assert!(!state.has_cap("away-notify"));
+    }
+
+    #[test]
+    fn sasl_holds_end() {
+        let (client, state) = (Recorder::default(), State::new());
+        let mut neg = Negotiator::new();
+        neg.set_sasl(Sasl {
+            mechanism: Mechanism::External,
+            user: String::new(),
+            pass: String::new(),
+        });
+
+        neg.start(&client, caps(&["multi-prefix"]));
+        neg.handle(
+            &client,
+            &state,
+            "LS",
+            false,
+            &caps(&["multi-prefix", "sasl"]),
+        );
+        neg.handle(
+            &client,
+            &state,
+            "ACK",
+            false,
+            &caps(&["multi-prefix", "sasl"]),
+        );
+        assert_eq!(
+            client.take(),
+            vec![
+                "CAP LS 302",
+                "CAP REQ :multi-prefix sasl",
+                "AUTHENTICATE EXTERNAL"
+            ]
+        );
+        assert!(!neg.is_finished());
+
+        neg.authenticate(&client, "+");
+        neg.sasl_reply(&client, 900);
+        assert_eq!(client.take(), vec!["AUTHENTICATE +"]);
+
+        neg.sasl_reply(&client, 903);
+        assert_eq!(client.take(), vec!["CAP END"]);
+        assert!(neg.is_finished());
+    }
+}
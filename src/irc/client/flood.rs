@@ -0,0 +1,232 @@
+use super::backoff::millis;
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How fast lines can be sent to the server
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FloodControl {
+    /// how many lines can be sent back to back, 0 turns flood control off
+    pub burst: u32,
+    /// how long it takes to earn another line
+    pub interval: Duration,
+}
+
+impl Default for FloodControl {
+    fn default() -> Self {
+        Self {
+            burst: 5,
+            interval: Duration::from_secs(2),
+        }
+    }
+}
+
+struct Bucket {
+    flood: FloodControl,
+    tokens: u32,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(flood: FloodControl, now: Instant) -> Self {
+        Self {
+            flood,
+            tokens: flood.burst,
+            last: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let interval = millis(self.flood.interval).max(1);
+        let earned = millis(now.duration_since(self.last)) / interval;
+        if earned == 0 {
+            return;
+        }
+
+        let earned = ::std::cmp::min(earned, u64::from(self.flood.burst)) as u32;
+        self.tokens = ::std::cmp::min(self.tokens + earned, self.flood.burst);
+        self.last = if self.tokens == self.flood.burst {
+            now
+        } else {
+            self.last + self.flood.interval * earned
+        };
+    }
+
+    /// how long until a line can be sent
+    fn wait(&mut self, now: Instant) -> Option<Duration> {
+        if self.flood.burst == 0 {
+            return None;
+        }
+
+        self.refill(now);
+        if self.tokens > 0 {
+            return None;
+        }
+        Some((self.last + self.flood.interval).duration_since(now))
+    }
+
+    fn take(&mut self) {
+        if self.tokens > 0 {
+            self.tokens -= 1;
+        }
+    }
+}
+
+pub enum Next {
+    Ready(Vec<u8>),
+    Wait(Duration),
+    Empty,
+}
+
+/// Lines waiting to be written, PONGs (and QUITs) skip the line and the bucket
+pub struct Outbox {
+    urgent: VecDeque<Vec<u8>>,
+    normal: VecDeque<Vec<u8>>,
+    bucket: Bucket,
+}
+
+impl Outbox {
+    pub fn new(flood: FloodControl, now: Instant) -> Self {
+        Self {
+            urgent: VecDeque::new(),
+            normal: VecDeque::new(),
+            bucket: Bucket::new(flood, now),
+        }
+    }
+
+    pub fn push(&mut self, line: Vec<u8>) {
+        if line.starts_with(b"PONG ") || line.starts_with(b"QUIT ") {
+            self.urgent.push_back(line)
+        } else {
+            self.normal.push_back(line)
+        }
+    }
+
+    pub fn next(&mut self, now: Instant) -> Next {
+        if let Some(line) = self.urgent.pop_front() {
+            return Next::Ready(line);
+        }
+        if self.normal.is_empty() {
+            return Next::Empty;
+        }
+
+        match self.bucket.wait(now) {
+            Some(wait) => Next::Wait(wait),
+            None => {
+                self.bucket.take();
+                Next::Ready(self.normal.pop_front().unwrap())
+            }
+        }
+    }
+
+    pub fn next_urgent(&mut self) -> Next {
+        match self.urgent.pop_front() {
+            Some(line) => Next::Ready(line),
+            None => Next::Empty,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.urgent.is_empty() && self.normal.is_empty()
+    }
+
+    /// takes the queued lines so something else can go in front of them
+    pub fn take_pending(&mut self) -> VecDeque<Vec<u8>> {
+        ::std::mem::take(&mut self.normal)
+    }
+
+    pub fn append(&mut self, mut lines: VecDeque<Vec<u8>>) {
+        self.normal.append(&mut lines)
+    }
+
+    /// pongs for a connection that's gone are useless
+    pub fn clear_urgent(&mut self) {
+        self.urgent.clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flood() -> FloodControl {
+        FloodControl {
+            burst: 2,
+            interval: Duration::from_secs(2),
+        }
+    }
+
+    fn next(outbox: &mut Outbox, now: Instant) -> Result<String, Option<Duration>> {
+        match outbox.next(now) {
+            Next::Ready(line) => Ok(String::from_utf8(line).unwrap()),
+            Next::Wait(wait) => Err(Some(wait)),
+            Next::Empty => Err(None),
+        }
+    }
+
+    #[test]
+    fn bucket() {
+        let start = Instant::now();
+        let mut outbox = Outbox::new(flood(), start);
+        for n in 0..4 {
+            outbox.push(format!("PRIVMSG #test :{}", n).into_bytes());
+        }
+
+        assert_eq!(next(&mut outbox, start), Ok("PRIVMSG #test :0".into()));
+        assert_eq!(next(&mut outbox, start), Ok("PRIVMSG #test :1".into()));
+        assert_eq!(next(&mut outbox, start), Err(Some(Duration::from_secs(2))));
+
+        let later = start + Duration::from_millis(1500);
+        assert_eq!(
+            next(&mut outbox, later),
+            Err(Some(Duration::from_millis(500)))
+        );
+
+        let later = start + Duration::from_secs(2);
+        assert_eq!(next(&mut outbox, later), Ok("PRIVMSG #test :2".into()));
+        assert!(next(&mut outbox, later).is_err());
+
+        // an idle bucket fills back up, but no further than the burst
+        let later = start + Duration::from_secs(60);
+        assert_eq!(next(&mut outbox, later), Ok("PRIVMSG #test :3".into()));
+        assert_eq!(next(&mut outbox, later), Err(None));
+        outbox.push(b"PRIVMSG #test :4".to_vec());
+        outbox.push(b"PRIVMSG #test :5".to_vec());
+        assert!(next(&mut outbox, later).is_ok());
+        assert!(next(&mut outbox, later).is_err());
+    }
+
+    #[test]
+    fn pong_jumps_ahead() {
+        let start = Instant::now();
+        let mut outbox = Outbox::new(flood(), start);
+        for n in 0..4 {
+            outbox.push(format!("PRIVMSG #test :{}", n).into_bytes());
+        }
+        next(&mut outbox, start).unwrap();
+        next(&mut outbox, start).unwrap();
+
+        outbox.push(b"PONG :12345".to_vec());
+        assert_eq!(next(&mut outbox, start), Ok("PONG :12345".into()));
+        assert!(next(&mut outbox, start).is_err());
+    }
+
+    #[test]
+    fn disabled() {
+        let start = Instant::now();
+        let mut outbox = Outbox::new(
+            FloodControl {
+                burst: 0,
+                interval: Duration::from_secs(2),
+            },
+            start,
+        );
+        for n in 0..10 {
+            outbox.push(format!("PRIVMSG #test :{}", n).into_bytes());
+        }
+        for _ in 0..10 {
+            assert!(next(&mut outbox, start).is_ok());
+        }
+        assert_eq!(next(&mut outbox, start), Err(None));
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::{Duration, Instant};
+
+const TOKEN: &str = "lag";
+
+/// Measures the round trip to the server with our own PINGs
+#[derive(Debug)]
+pub struct Lag {
+    interval: Duration,
+    // when the next PING should go out, `None` until we're registered
+    due: Option<Instant>,
+    sent: Option<Instant>,
+}
+
+impl Default for Lag {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(30))
+    }
+}
+
+impl Lag {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            due: None,
+            sent: None,
+        }
+    }
+
+    /// starts pinging, servers won't answer a PING before registration is done
+    pub fn start(&mut self, now: Instant) {
+        self.due = Some(now);
+        self.sent = None;
+    }
+
+    pub fn stop(&mut self) {
+        self.due = None;
+        self.sent = None;
+    }
+
+    /// the PING to send, if one is due and the last one was answered
+    pub fn poll(&mut self, now: Instant) -> Option<String> {
+        match self.due {
+            Some(due) if now >= due && self.sent.is_none() => {
+                self.sent = Some(now);
+                Some(format!("PING :{}\r\n", TOKEN))
+            }
+            _ => None,
+        }
+    }
+
+    /// the round trip, if `pong` answers our PING. `pong` is everything after the command
+    pub fn pong(&mut self, pong: &str, now: Instant) -> Option<Duration> {
+        if pong.rsplit(' ').next().map(|s| s.trim_start_matches(':')) != Some(TOKEN) {
+            return None;
+        }
+
+        let sent = self.sent.take()?;
+        self.due = Some(now + self.interval);
+        Some(now - sent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let now = Instant::now();
+        let mut lag = Lag::new(Duration::from_secs(30));
+        assert_eq!(lag.poll(now), None);
+
+        lag.start(now);
+        assert_eq!(lag.poll(now), Some("PING :lag\r\n".into()));
+        // only one at a time
+        assert_eq!(lag.poll(now + Duration::from_secs(60)), None);
+
+        let later = now + Duration::from_millis(250);
+        assert_eq!(lag.pong("irc.example.com :sync", later), None);
+        assert_eq!(
+            lag.pong("irc.example.com :lag", later),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(lag.pong("irc.example.com :lag", later), None);
+
+        assert_eq!(lag.poll(later + Duration::from_secs(29)), None);
+        assert!(lag.poll(later + Duration::from_secs(30)).is_some());
+
+        lag.stop();
+        assert_eq!(lag.poll(later + Duration::from_secs(90)), None);
+    }
+}
@@ -0,0 +1,958 @@
+use super::ctcp::{self, Ctcp};
+use super::message::Error as MessageError;
+use super::*;
+
+mod backoff;
+mod cap;
+mod flood;
+mod lag;
+mod sasl;
+mod split;
+mod stream;
+pub use self::backoff::Backoff;
+pub use self::cap::Negotiator;
+pub use self::flood::{FloodControl, Next, Outbox};
+pub use self::lag::Lag;
+pub use self::sasl::{Authenticator, Mechanism, Sasl};
+pub use self::stream::{Stream, TlsConfig};
+
+use chrono::{Local, TimeZone, Utc};
+use crossbeam_channel as channel;
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// how long the writer sleeps when it has nothing to do, it's woken up for new lines
+const WRITER_IDLE: Duration = Duration::from_secs(1);
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Error {
+    CannotConnect,
+    Tls(String),
+    FingerprintMismatch(String),
+    ParseError(MessageError),
+    CannotRead,
+    EndOfStream,
+}
+
+impl Error {
+    /// whether trying again could possibly help
+    fn is_recoverable(&self) -> bool {
+        !matches!(self, Error::Tls(_) | Error::FingerprintMismatch(_))
+    }
+}
+
+/// What gets sent to the server each time a connection is made
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Registration {
+    pub nick: String,
+    pub user: String,
+    pub real: String,
+    pub pass: Option<String>,
+    pub caps: Vec<String>,
+    pub sasl: Option<Sasl>,
+}
+
+/// Connection progress, as seen by the supervisor
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    Connecting { addr: String, attempt: u32 },
+    Connected { addr: String },
+    Disconnected(Error),
+    Reconnecting { delay: Duration },
+}
+
+pub struct Client {
+    state: Arc<State>,
+    inner: Arc<Inner>,
+    errors: channel::Receiver<Error>,
+    status: channel::Receiver<Status>,
+}
+
+impl Client {
+    /// connects in the background, reconnecting (and re-registering) until `close` is called
+    pub fn connect(
+        addr: impl Into<String>,
+        tls: Option<TlsConfig>,
+        reg: Registration,
+        flood: FloodControl,
+    ) -> Result<Self, Error> {
+        let (err_tx, err_rx) = channel::bounded(8);
+        let (status_tx, status_rx) = channel::unbounded();
+
+        let this = Self {
+            state: Arc::new(State::new()),
+            inner: Arc::new(Inner {
+                stream: Mutex::new(None),
+                outbox: Mutex::new(Outbox::new(flood, Instant::now())),
+                wake: Condvar::new(),
+                caps: Mutex::new(Negotiator::new()),
+                keys: Mutex::new(HashMap::new()),
+                rejoin: Mutex::new(vec![]),
+                names: Mutex::new(HashSet::new()),
+                lag: Mutex::new(Lag::default()),
+                ctcp: Mutex::new(ctcp::Limiter::default()),
+                closing: AtomicBool::new(false),
+            }),
+            errors: err_rx,
+            status: status_rx,
+        };
+
+        let supervisor = Supervisor {
+            state: Arc::clone(&this.state),
+            inner: Arc::clone(&this.inner),
+            addr: addr.into(),
+            tls,
+            reg,
+            status: status_tx,
+        };
+
+        let inner = Arc::clone(&this.inner);
+        thread::spawn(move || inner.write_loop());
+
+        thread::spawn(move || {
+            let err = supervisor.run();
+            trace!("supervisor stopped: {:?}", err);
+            // lets the writer go
+            supervisor.inner.close();
+            err_tx.send(err);
+        });
+
+        Ok(this)
+    }
+
+    /// this is only sent to once, when the client has given up
+    pub fn errors(&self) -> channel::Receiver<Error> {
+        self.errors.clone()
+    }
+
+    pub fn status(&self) -> channel::Receiver<Status> {
+        self.status.clone()
+    }
+
+    pub fn state(&self) -> Arc<State> {
+        Arc::clone(&self.state)
+    }
+
+    /// long messages are split so the server doesn't truncate them
+    fn send_split(&self, command: &str, target: &str, data: &str) {
+        let nick = self.state.nickname().map_or(split::MAX_NICK, |s| s.len());
+        let max = split::max_payload(nick, command, target);
+        for line in split::split_message(data, max) {
+            self.inner
+                .write(format!("{} {} :{}\r\n", command, target, line).as_bytes())
+        }
+    }
+}
+
+struct Supervisor {
+    state: Arc<State>,
+    inner: Arc<Inner>,
+    addr: String,
+    tls: Option<TlsConfig>,
+    reg: Registration,
+    status: channel::Sender<Status>,
+}
+
+impl Supervisor {
+    fn run(&self) -> Error {
+        let mut backoff = Backoff::default();
+        loop {
+            self.status.send(Status::Connecting {
+                addr: self.addr.clone(),
+                attempt: backoff.attempts() + 1,
+            });
+
+            let err = match Stream::connect(&self.addr, self.tls.as_ref()) {
+                Ok(conn) => {
+                    backoff.reset();
+                    self.status.send(Status::Connected {
+                        addr: self.addr.clone(),
+                    });
+                    self.read_loop(conn)
+                }
+                Err(err) => err,
+            };
+
+            self.inner.disconnected(&self.state);
+            if self.inner.is_closing() || !err.is_recoverable() {
+                return err;
+            }
+            self.status.send(Status::Disconnected(err));
+
+            let delay = backoff.next_delay();
+            self.status.send(Status::Reconnecting { delay });
+            thread::sleep(delay);
+
+            if self.inner.is_closing() {
+                return Error::EndOfStream;
+            }
+        }
+    }
+
+    fn read_loop(&self, conn: Stream) -> Error {
+        let read = conn.try_clone().expect("conn clone for read");
+        let write = conn.try_clone().expect("conn clone for write");
+
+        {
+            // registration has to go before anything queued while we were disconnected
+            let pending = self.inner.outbox.lock().unwrap().take_pending();
+            self.inner.register(&self.reg);
+            self.inner.outbox.lock().unwrap().append(pending);
+
+            *self.inner.stream.lock().unwrap() = Some(write);
+            self.inner.wake.notify_all();
+        }
+
+        for line in BufReader::new(read).lines() {
+            let line = match line.map_err(|_err| Error::CannotRead) {
+                Ok(line) => line,
+                Err(err) => {
+                    debug!("error reading: {:?}", err);
+                    return err;
+                }
+            };
+            trace!("<< {}", line.trim());
+
+            // a line we don't understand isn't worth dropping the connection over
+            let msg = match Message::parse(&line).map_err(Error::ParseError) {
+                Ok(msg) => msg,
+                Err(err) => {
+                    debug!("error parsing: {:?}", err);
+                    continue;
+                }
+            };
+
+            // the channels forget who left (or who was renamed) before anything else sees it
+            let channels = match &msg.command {
+                Command::Nick { .. } | Command::Quit { .. } => {
+                    self.state.channels().with_nick(msg.get_nick())
+                }
+                _ => vec![],
+            };
+
+            self.inner.update(&msg, &self.state);
+            self.state.push_message((Instant::now(), msg, channels));
+        }
+
+        trace!("end of read loop");
+        Error::EndOfStream
+    }
+}
+
+impl IrcClient for Client {
+    fn join(&self, channel: impl AsRef<str>, key: Option<&str>) {
+        let channel = channel.as_ref();
+        if let Some(key) = key {
+            // remembered so we can rejoin after a reconnect
+            let channel = self.state.isupport().fold(channel);
+            self.inner
+                .keys
+                .lock()
+                .unwrap()
+                .insert(channel, key.to_string());
+        }
+        self.inner.join(channel, key)
+    }
+
+    fn privmsg(&self, target: impl AsRef<str>, data: impl AsRef<str>) {
+        self.send_split("PRIVMSG", target.as_ref(), data.as_ref())
+    }
+
+    fn notice(&self, target: impl AsRef<str>, data: impl AsRef<str>) {
+        self.send_split("NOTICE", target.as_ref(), data.as_ref())
+    }
+
+    fn write(&self, data: &[u8]) {
+        self.inner.write(data);
+    }
+
+    fn close(&self) {
+        self.inner.close();
+    }
+}
+
+struct Inner {
+    stream: Mutex<Option<Stream>>,
+    outbox: Mutex<Outbox>,
+    // wakes up the writer
+    wake: Condvar,
+    caps: Mutex<Negotiator>,
+    keys: Mutex<HashMap<String, String>>,
+    rejoin: Mutex<Vec<(String, Option<String>)>>,
+    // channels in the middle of a NAMES reply
+    names: Mutex<HashSet<String>>,
+    lag: Mutex<Lag>,
+    // how fast CTCP queries get answered
+    ctcp: Mutex<ctcp::Limiter>,
+    closing: AtomicBool,
+}
+
+impl Inner {
+    fn register(&self, reg: &Registration) {
+        {
+            let caps = &mut self.caps.lock().unwrap();
+            if let Some(sasl) = &reg.sasl {
+                caps.set_sasl(sasl.clone());
+            }
+            caps.start(self, reg.caps.clone());
+        }
+
+        if let Some(pass) = &reg.pass {
+            self.pass(pass);
+        }
+        self.nick(&reg.nick);
+        self.user(&reg.user, &reg.real);
+    }
+
+    /// forgets the connection, remembering which channels to rejoin
+    fn disconnected(&self, state: &State) {
+        self.stream.lock().unwrap().take();
+        self.outbox.lock().unwrap().clear_urgent();
+        self.lag.lock().unwrap().stop();
+
+        let channels = state.channels();
+        let rejoin = channels
+            .names()
+            .into_iter()
+            .map(|name| {
+                let key = channels.get(&name).and_then(|ch| ch.key());
+                (name, key)
+            })
+            .collect::<Vec<_>>();
+
+        // a connection that dropped before rejoining shouldn't forget the channels
+        if !rejoin.is_empty() {
+            *self.rejoin.lock().unwrap() = rejoin;
+        }
+
+        channels.clear();
+        self.names.lock().unwrap().clear();
+        state.reset();
+    }
+
+    fn is_closing(&self) -> bool {
+        self.closing.load(Ordering::SeqCst)
+    }
+
+    fn rejoin(&self) {
+        let channels = self.rejoin.lock().unwrap().drain(..).collect::<Vec<_>>();
+        for (channel, key) in channels {
+            self.join(&channel, key.as_deref());
+        }
+    }
+
+    fn update(&self, msg: &Message, state: &Arc<State>) {
+        let from_self = match &msg.prefix {
+            Some(Prefix::User { nick, .. }) => state.is_me(nick),
+            _ => false,
+        };
+
+        match &msg.command {
+            Command::Ping { token } => self.pong(token),
+
+            Command::Pong { target } => {
+                if let Some(lag) = self.lag.lock().unwrap().pong(target, Instant::now()) {
+                    state.set_lag(lag)
+                }
+            }
+
+            Command::Privmsg {
+                data,
+                is_notice: false,
+                ..
+            } if !from_self => self.ctcp(msg, data),
+
+            Command::Join { channel, key: _key } => {
+                let channel = if from_self {
+                    let folded = state.isupport().fold(channel);
+                    let key = self.keys.lock().unwrap().remove(&folded);
+                    let channel = state.channels().add(channel.clone());
+                    if key.is_some() {
+                        channel.set_key(key);
+                    }
+                    // the reply fills in the channel's modes
+                    self.write(format!("MODE {}\r\n", channel.name()).as_bytes());
+                    channel
+                } else {
+                    match state.channels().get(channel) {
+                        Some(channel) => channel,
+                        // the server can be ahead of us after a reconnect
+                        None => {
+                            warn!("join for an unknown channel: {}", channel);
+                            return;
+                        }
+                    }
+                };
+                channel.add(msg.get_nick());
+            }
+
+            Command::Part {
+                channel,
+                reason: _reason,
+            } => {
+                if from_self {
+                    state.channels().remove(channel.clone());
+                    return;
+                }
+
+                match state.channels().get(channel) {
+                    Some(channel) => channel.remove(msg.get_nick()),
+                    None => warn!("part for an unknown channel: {}", channel),
+                }
+            }
+
+            Command::Quit { reason: _reason } => {
+                if from_self {
+                    // let the client clean up
+                    return;
+                }
+
+                state.channels().clear_nick(msg.get_nick());
+            }
+
+            Command::Nick { nickname } => {
+                if from_self {
+                    state.set_nickname(nickname.clone());
+                    return;
+                }
+
+                state
+                    .channels()
+                    .update_nick(msg.get_nick(), nickname.clone());
+            }
+
+            Command::Mode {
+                target,
+                modes,
+                args,
+            } => {
+                if let Some(channel) = state.channels().get(target) {
+                    apply_modes(&channel, modes, args, &state.isupport())
+                }
+            }
+
+            Command::Topic { channel, topic } => {
+                if let Some(channel) = state.channels().get(channel) {
+                    if topic.is_empty() {
+                        channel.clear_topic();
+                        return;
+                    }
+                    channel.set_topic(topic.clone());
+                    let time = msg.tags.server_time().unwrap_or_else(Utc::now);
+                    channel.set_topic_info(msg.get_nick(), time);
+                }
+            }
+
+            Command::Cap {
+                subcommand,
+                more,
+                caps,
+                ..
+            } => self
+                .caps
+                .lock()
+                .unwrap()
+                .handle(self, state, subcommand, *more, caps),
+
+            Command::Authenticate { data } => {
+                self.caps.lock().unwrap().authenticate(self, data)
+            }
+
+            Command::Other {
+                command: _command,
+                params: _params,
+            } => {
+                // need to periodically do a /who or /names #channel
+            }
+
+            Command::Reply { numeric, params } => self.reply(*numeric, params, state),
+
+            _ => {
+                // what should be done here?
+            }
+        };
+    }
+
+    /// answers a CTCP query, unless they're coming in too fast
+    fn ctcp(&self, msg: &Message, data: &str) {
+        let reply = match Ctcp::parse(data).and_then(|ctcp| ctcp.reply(Local::now())) {
+            Some(reply) => reply,
+            None => return,
+        };
+
+        let nick = match &msg.prefix {
+            Some(Prefix::User { nick, .. }) => nick,
+            _ => return,
+        };
+        if self.ctcp.lock().unwrap().allow(Instant::now()) {
+            self.notice(nick, reply.encode())
+        } else {
+            debug!("not answering ctcp {} from {}", reply.command, nick)
+        }
+    }
+
+    // the first param of a reply is always our nick
+    fn reply(&self, numeric: u16, params: &[String], state: &State) {
+        let param = |n: usize| params.get(n).map(|s| s.as_str()).unwrap_or_default();
+        let channel = |n: usize| state.channels().get(param(n));
+
+        match numeric {
+            1 if !params.is_empty() => {
+                state.set_nickname(params[0].clone());
+                self.caps.lock().unwrap().finish();
+                self.lag.lock().unwrap().start(Instant::now());
+                self.rejoin();
+            }
+
+            // RPL_ISUPPORT, the last param is "are supported by this server"
+            5 if params.len() > 2 => {
+                for token in &params[1..params.len() - 1] {
+                    state.apply_isupport(token)
+                }
+            }
+
+            // RPL_CHANNELMODEIS, everything that's set right now
+            324 if params.len() > 2 => {
+                if let Some(channel) = channel(1) {
+                    channel.clear_modes();
+                    apply_modes(&channel, &params[2], &params[3..], &state.isupport())
+                }
+            }
+
+            // RPL_NOTOPIC
+            331 => {
+                if let Some(channel) = channel(1) {
+                    channel.clear_topic()
+                }
+            }
+
+            // RPL_TOPIC
+            332 => {
+                if let Some(channel) = channel(1) {
+                    channel.set_topic(param(2))
+                }
+            }
+
+            // RPL_TOPICWHOTIME
+            333 => {
+                let time = param(3)
+                    .parse()
+                    .ok()
+                    .and_then(|time| Utc.timestamp_opt(time, 0).single());
+                if let (Some(channel), Some(time)) = (channel(1), time) {
+                    // the setter can be a full nick!user@host
+                    let setter = param(2).split('!').next().unwrap_or_default();
+                    channel.set_topic_info(setter, time)
+                }
+            }
+
+            // RPL_NAMREPLY, a fresh reply replaces whoever we thought was there
+            353 => {
+                let channel = match channel(2) {
+                    Some(channel) => channel,
+                    None => return,
+                };
+                if self.names.lock().unwrap().insert(param(2).to_string()) {
+                    channel.clear_users()
+                }
+
+                let isupport = state.isupport();
+                for name in param(3).split_whitespace() {
+                    let (prefix, name) = isupport.split_prefix(name);
+                    channel.add_with_prefix(name.split('!').next().unwrap_or_default(), prefix)
+                }
+            }
+
+            // RPL_ENDOFNAMES
+            366 => {
+                self.names.lock().unwrap().remove(param(1));
+            }
+
+            // RPL_UNAWAY and RPL_NOWAWAY
+            305 => state.set_away(false),
+            306 => state.set_away(true),
+
+            // ERR_NICKNAMEINUSE, try again with an underscore on the end
+            433 => match params.get(1).cloned().or_else(|| state.nickname()) {
+                Some(nick) => self.nick(format!("{}_", nick)),
+                None => debug!("nick in use, but no nick to change"),
+            },
+
+            900..=908 => self.caps.lock().unwrap().sasl_reply(self, numeric),
+
+            // the motd and the errors are only interesting to the ui
+            375 | 372 | 376 | 422 => {}
+            400..=599 => debug!("error reply: {} {:?}", numeric, params),
+
+            _ => {}
+        }
+    }
+
+    /// writes queued lines as fast as flood control allows
+    fn write_loop(&self) {
+        let mut outbox = self.outbox.lock().unwrap();
+        loop {
+            let closing = self.is_closing();
+            let connected = self.stream.lock().unwrap().is_some();
+
+            if connected && !closing {
+                // it waits its turn like everything else, so flood control counts it
+                if let Some(ping) = self.lag.lock().unwrap().poll(Instant::now()) {
+                    outbox.push(ping.into_bytes());
+                }
+            }
+
+            let wait = if connected {
+                // once closing, only the urgent lines (like a QUIT) still matter
+                let next = if closing {
+                    outbox.next_urgent()
+                } else {
+                    outbox.next(Instant::now())
+                };
+
+                match next {
+                    Next::Ready(line) => {
+                        drop(outbox);
+                        self.send(&line);
+                        outbox = self.outbox.lock().unwrap();
+                        continue;
+                    }
+                    Next::Wait(wait) => wait,
+                    Next::Empty => WRITER_IDLE,
+                }
+            } else {
+                WRITER_IDLE
+            };
+
+            if closing {
+                break;
+            }
+            outbox = self.wake.wait_timeout(outbox, wait).unwrap().0;
+        }
+
+        trace!("end of write loop");
+        if let Some(stream) = &*self.stream.lock().unwrap() {
+            if let Err(err) = stream.shutdown() {
+                debug!("error shutting down the stream: {}", err)
+            }
+        }
+    }
+
+    fn send(&self, data: &[u8]) {
+        use std::io::Write;
+
+        let mut stream = self.stream.lock().unwrap();
+        let stream = match stream.as_mut() {
+            Some(stream) => stream,
+            None => return,
+        };
+
+        // anything can be written, so this can't assume it's utf-8 or even a whole line
+        trace!(
+            ">> {}",
+            String::from_utf8_lossy(data).trim_end_matches("\r\n")
+        );
+        // the reader will notice if the connection is gone
+        if let Err(err) = stream.write_all(data) {
+            debug!("cannot write: {}", err)
+        }
+    }
+}
+
+fn apply_modes(channel: &Channel, modes: &str, args: &[String], isupport: &ISupport) {
+    for change in ModeChange::parse(modes, args, isupport) {
+        if isupport.prefix.iter().any(|&(mode, _)| mode == change.mode) {
+            if let Some(nick) = &change.arg {
+                channel.set_member_mode(nick, change.mode, change.set)
+            }
+        } else if !isupport.chanmodes.a.contains(change.mode) {
+            // lists (like bans) aren't kept
+            channel.set_mode(change.mode, change.set, change.arg)
+        }
+    }
+}
+
+impl IrcClient for Inner {
+    fn write(&self, data: &[u8]) {
+        if data.len() > 512 {
+            warn!(
+                "line is too long and will be truncated: {} bytes",
+                data.len()
+            )
+        }
+
+        self.outbox.lock().unwrap().push(split::truncate(data));
+        self.wake.notify_all();
+    }
+
+    /// the writer hangs up once the QUIT (if there was one) has been sent
+    fn close(&self) {
+        self.closing.store(true, Ordering::SeqCst);
+        self.wake.notify_all();
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// records everything written to it, line by line
+    #[derive(Default)]
+    pub struct Recorder(RefCell<Vec<String>>);
+
+    impl Recorder {
+        pub fn take(&self) -> Vec<String> {
+            self.0.borrow_mut().drain(..).collect()
+        }
+    }
+
+    impl IrcClient for Recorder {
+        fn write(&self, data: &[u8]) {
+            let line = ::std::str::from_utf8(data).unwrap();
+            self.0.borrow_mut().push(line.trim_end().to_owned());
+        }
+
+        fn close(&self) {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    fn expect(reader: &mut impl BufRead, prefix: &str) {
+        loop {
+            let mut line = String::new();
+            assert!(reader.read_line(&mut line).unwrap() > 0, "eof waiting for {}", prefix);
+            if line.starts_with(prefix) {
+                return;
+            }
+        }
+    }
+
+    fn accept(listener: &TcpListener) -> (BufReader<TcpStream>, TcpStream) {
+        let (conn, _) = listener.accept().unwrap();
+        (BufReader::new(conn.try_clone().unwrap()), conn)
+    }
+
+    #[test]
+    fn malformed() {
+        let listener = TcpListener::bind("localhost:0").unwrap();
+        let addr = format!("localhost:{}", listener.local_addr().unwrap().port());
+
+        let client = Client::connect(
+            addr,
+            None,
+            Registration {
+                nick: "test".into(),
+                user: "test".into(),
+                real: "test".into(),
+                ..Registration::default()
+            },
+            FloodControl::default(),
+        )
+        .unwrap();
+
+        let (mut reader, mut conn) = accept(&listener);
+        expect(&mut reader, "USER");
+        conn.write_all(b":irc 001\r\n").unwrap();
+        conn.write_all(b"PING :first\r\n").unwrap();
+        expect(&mut reader, "PONG");
+
+        // shorter than a line ending, but it still goes out
+        client.write(b"\n");
+        client.write(b"PRIVMSG bob :hi\r\n");
+        expect(&mut reader, "PRIVMSG bob :hi");
+
+        client.close();
+        drop(conn);
+        assert_eq!(client.errors().recv(), Some(Error::EndOfStream));
+    }
+
+    #[test]
+    fn unknown_channel() {
+        let listener = TcpListener::bind("localhost:0").unwrap();
+        let addr = format!("localhost:{}", listener.local_addr().unwrap().port());
+
+        let client = Client::connect(
+            addr,
+            None,
+            Registration {
+                nick: "test".into(),
+                user: "test".into(),
+                real: "test".into(),
+                ..Registration::default()
+            },
+            FloodControl::default(),
+        )
+        .unwrap();
+
+        let (mut reader, mut conn) = accept(&listener);
+        expect(&mut reader, "USER");
+        conn.write_all(b":irc 001 test :welcome\r\n").unwrap();
+
+        // someone else in a channel we aren't tracking, the client should just carry on
+        conn.write_all(b":bob!b@host JOIN #elsewhere\r\n").unwrap();
+        conn.write_all(b":bob!b@host PART #elsewhere\r\n").unwrap();
+        conn.write_all(b"PING :sync\r\n").unwrap();
+        expect(&mut reader, "PONG");
+        assert!(!client.state().channels().contains("#elsewhere"));
+
+        client.close();
+        drop(conn);
+        assert_eq!(client.errors().recv(), Some(Error::EndOfStream));
+    }
+
+    #[test]
+    fn reconnect_and_rejoin() {
+        let listener = TcpListener::bind("localhost:0").unwrap();
+        let addr = format!("localhost:{}", listener.local_addr().unwrap().port());
+
+        let client = Client::connect(
+            addr,
+            None,
+            Registration {
+                nick: "test".into(),
+                user: "test".into(),
+                real: "test".into(),
+                ..Registration::default()
+            },
+            FloodControl::default(),
+        )
+        .unwrap();
+
+        let (mut reader, mut conn) = accept(&listener);
+        expect(&mut reader, "USER");
+        conn.write_all(b":irc 001 test :welcome\r\n").unwrap();
+
+        client.join("#test", Some("hunter2"));
+        expect(&mut reader, "JOIN #test hunter2");
+        conn.write_all(b":test!user@host JOIN #test\r\n").unwrap();
+        conn.write_all(b"PING :sync\r\n").unwrap();
+        expect(&mut reader, "PONG");
+        assert!(client.state().channels().contains("#test"));
+
+        // drop the connection, the client should come back on its own
+        conn.shutdown(::std::net::Shutdown::Both).unwrap();
+        drop(reader);
+
+        let (mut reader, mut conn) = accept(&listener);
+        expect(&mut reader, "NICK test");
+        expect(&mut reader, "USER");
+        conn.write_all(b":irc 001 test :welcome\r\n").unwrap();
+        expect(&mut reader, "JOIN #test hunter2");
+
+        let status = client.status();
+        let mut events = vec![];
+        while let Some(ev) = status.try_recv() {
+            events.push(ev)
+        }
+        assert!(events.contains(&Status::Disconnected(Error::EndOfStream)));
+        assert!(events.iter().any(|s| match s {
+            Status::Reconnecting { .. } => true,
+            _ => false,
+        }));
+
+        client.close();
+        drop(conn);
+        assert_eq!(client.errors().recv(), Some(Error::EndOfStream));
+    }
+
+    #[test]
+    fn numerics() {
+        let listener = TcpListener::bind("localhost:0").unwrap();
+        let addr = format!("localhost:{}", listener.local_addr().unwrap().port());
+
+        let client = Client::connect(
+            addr,
+            None,
+            Registration {
+                nick: "test".into(),
+                user: "test".into(),
+                real: "test".into(),
+                ..Registration::default()
+            },
+            FloodControl::default(),
+        )
+        .unwrap();
+
+        let (mut reader, mut conn) = accept(&listener);
+        expect(&mut reader, "USER");
+        let lines = [
+            ":irc 001 test :welcome",
+            ":irc 005 test CHANTYPES=# NICKLEN=30 EXCEPTS -EXCEPTS :are supported by this server",
+            ":test!user@host JOIN #test",
+            ":irc 332 test #test :the topic",
+            ":irc 333 test #test bob!b@host 1500000000",
+            ":irc 353 test = #test :test @bob +alice",
+            ":irc 353 test = #test :carol",
+            ":irc 366 test #test :End of /NAMES list.",
+            "PING :sync",
+        ];
+        for line in &lines {
+            conn.write_all(format!("{}\r\n", line).as_bytes()).unwrap();
+        }
+        expect(&mut reader, "PONG");
+
+        let state = client.state();
+        let isupport = state.isupport();
+        assert_eq!(isupport.chantypes, "#");
+        assert_eq!(isupport.nicklen, 30);
+        assert_eq!(isupport.get("EXCEPTS"), None);
+
+        let channel = state.channels().get("#test").unwrap();
+        assert_eq!(channel.topic(), Some("the topic".into()));
+        assert_eq!(
+            channel.topic_info(),
+            Utc.timestamp_opt(1_500_000_000, 0)
+                .single()
+                .map(|time| ("bob".into(), time))
+        );
+        let users = channel
+            .users()
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(users, vec!["@bob", "+alice", "carol", "test"]);
+
+        conn.write_all(b":irc 324 test #test +ntl 20\r\n").unwrap();
+        conn.write_all(b":bob!b@host MODE #test +o-o+k carol bob hunter2\r\n")
+            .unwrap();
+        conn.write_all(b"PING :sync\r\n").unwrap();
+        expect(&mut reader, "PONG");
+        assert_eq!(channel.member("carol").unwrap().prefix, "@");
+        assert_eq!(channel.member("bob").unwrap().prefix, "");
+        assert_eq!(channel.limit(), Some(20));
+        assert_eq!(channel.key(), Some("hunter2".into()));
+
+        // another NAMES reply starts over
+        conn.write_all(b":irc 353 test = #test :test\r\n").unwrap();
+        conn.write_all(b":irc 366 test #test :End of /NAMES list.\r\n")
+            .unwrap();
+        conn.write_all(b":bob!b@host TOPIC #test :\r\n").unwrap();
+        conn.write_all(b"PING :sync\r\n").unwrap();
+        expect(&mut reader, "PONG");
+        assert_eq!(channel.users().len(), 1);
+        assert_eq!(channel.topic(), None);
+
+        conn.write_all(b":bob!b@host PRIVMSG test :\x01PING 12345\x01\r\n")
+            .unwrap();
+        expect(&mut reader, "NOTICE bob :\x01PING 12345\x01");
+
+        // a 433 without the nick in it falls back to the one we have
+        conn.write_all(b":irc 433 test\r\n").unwrap();
+        expect(&mut reader, "NICK test_");
+
+        client.close();
+        drop(conn);
+        assert_eq!(client.errors().recv(), Some(Error::EndOfStream));
+    }
+}
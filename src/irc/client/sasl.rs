@@ -0,0 +1,218 @@
+use super::*;
+
+// AUTHENTICATE payloads are sent in chunks of this size
+const CHUNK_SIZE: usize = 400;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Mechanism {
+    Plain,
+    External,
+}
+
+impl Mechanism {
+    pub fn parse(s: impl AsRef<str>) -> Option<Self> {
+        match s.as_ref().to_ascii_uppercase().as_str() {
+            "PLAIN" => Some(Mechanism::Plain),
+            "EXTERNAL" => Some(Mechanism::External),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mechanism::Plain => "PLAIN",
+            Mechanism::External => "EXTERNAL",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Sasl {
+    pub mechanism: Mechanism,
+    pub user: String,
+    pub pass: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Step {
+    Idle,
+    Started,
+    Done,
+}
+
+/// The AUTHENTICATE exchange, driven by the capability negotiator
+#[derive(Debug)]
+pub struct Authenticator {
+    sasl: Sasl,
+    step: Step,
+}
+
+impl Authenticator {
+    pub fn new(sasl: Sasl) -> Self {
+        Self {
+            sasl,
+            step: Step::Idle,
+        }
+    }
+
+    /// starts over, for a new connection
+    pub fn reset(self) -> Self {
+        Self::new(self.sasl)
+    }
+
+    pub fn in_progress(&self) -> bool {
+        self.step == Step::Started
+    }
+
+    pub fn begin(&mut self, client: &impl IrcClient) {
+        if self.step != Step::Idle {
+            return;
+        }
+
+        self.step = Step::Started;
+        client.authenticate(self.sasl.mechanism.name());
+    }
+
+    /// the server sent an `AUTHENTICATE` line
+    pub fn challenge(&mut self, client: &impl IrcClient, data: &str) {
+        if self.step != Step::Started || data != "+" {
+            return;
+        }
+
+        let payload = match self.sasl.mechanism {
+            Mechanism::Plain => encode(&format!(
+                "{user}\0{user}\0{pass}",
+                user = self.sasl.user,
+                pass = self.sasl.pass
+            )),
+            Mechanism::External => String::new(),
+        };
+
+        for chunk in payload.as_bytes().chunks(CHUNK_SIZE) {
+            // base64 is ascii so this is fine
+            client.authenticate(::std::str::from_utf8(chunk).unwrap());
+        }
+
+        // an empty line (or one that ended exactly on the boundary) is terminated with a '+'
+        if payload.len() % CHUNK_SIZE == 0 {
+            client.authenticate("+");
+        }
+    }
+
+    /// returns true if this numeric ended the exchange
+    pub fn reply(&mut self, numeric: u16) -> bool {
+        match numeric {
+            902..=907 if self.step == Step::Started => {
+                self.step = Step::Done;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn encode(data: &str) -> String {
+    const TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut buf = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.as_bytes().chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | u32::from(*b) << (16 - i * 8));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                buf.push(TABLE[(n >> (18 - i * 6) & 0x3F) as usize] as char)
+            } else {
+                buf.push('=')
+            }
+        }
+    }
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::irc::client::testing::Recorder;
+
+    #[test]
+    fn base64() {
+        let inputs = &[
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foobar", "Zm9vYmFy"),
+            ("jilles\0jilles\0sesame", "amlsbGVzAGppbGxlcwBzZXNhbWU="),
+        ];
+
+        for (input, expected) in inputs {
+            assert_eq!(encode(input), *expected);
+        }
+    }
+
+    #[test]
+    fn plain() {
+        let client = Recorder::default();
+        let mut auth = Authenticator::new(Sasl {
+            mechanism: Mechanism::Plain,
+            user: "jilles".into(),
+            pass: "sesame".into(),
+        });
+
+        auth.begin(&client);
+        assert!(auth.in_progress());
+        auth.challenge(&client, "+");
+        assert!(!auth.reply(900));
+        assert!(auth.reply(903));
+        assert!(!auth.in_progress());
+
+        assert_eq!(
+            client.take(),
+            vec![
+                "AUTHENTICATE PLAIN",
+                "AUTHENTICATE amlsbGVzAGppbGxlcwBzZXNhbWU=",
+            ]
+        );
+    }
+
+    #[test]
+    fn external() {
+        let client = Recorder::default();
+        let mut auth = Authenticator::new(Sasl {
+            mechanism: Mechanism::External,
+            user: String::new(),
+            pass: String::new(),
+        });
+
+        auth.begin(&client);
+        auth.challenge(&client, "+");
+        assert!(auth.reply(904));
+
+        assert_eq!(
+            client.take(),
+            vec!["AUTHENTICATE EXTERNAL", "AUTHENTICATE +"]
+        );
+    }
+
+    #[test]
+    fn chunked() {
+        let client = Recorder::default();
+        // 300 bytes of input is exactly 400 bytes of base64
+        let mut auth = Authenticator::new(Sasl {
+            mechanism: Mechanism::Plain,
+            user: "a".repeat(49),
+            pass: "b".repeat(200),
+        });
+
+        auth.begin(&client);
+        auth.challenge(&client, "+");
+
+        let lines = client.take();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1].len(), "AUTHENTICATE ".len() + CHUNK_SIZE);
+        assert_eq!(lines[2], "AUTHENTICATE +");
+    }
+}
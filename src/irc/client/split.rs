@@ -0,0 +1,178 @@
+use super::super::ctcp::Ctcp;
+
+// including the CRLF
+const MAX_LINE: usize = 512;
+// we can't know what the server thinks our user and host are, so assume the worst
+const MAX_USER: usize = 10;
+const MAX_HOST: usize = 63;
+/// used before the server has told us our nick
+pub const MAX_NICK: usize = 30;
+
+/// how many bytes of `data` fit in `COMMAND target :data` once the server adds our prefix
+pub fn max_payload(nick_len: usize, command: &str, target: &str) -> usize {
+    // :nick!user@host COMMAND target :data\r\n
+    let prefix = 1 + nick_len + 1 + MAX_USER + 1 + MAX_HOST + 1;
+    let overhead = prefix + command.len() + 1 + target.len() + 2 + 2;
+    MAX_LINE.saturating_sub(overhead)
+}
+
+/// splits on a space if there's one to split on, otherwise on a char boundary
+pub fn split(data: &str, max: usize) -> Vec<&str> {
+    // anything smaller couldn't hold a 4 byte char
+    let max = ::std::cmp::max(max, 4);
+
+    let mut lines = vec![];
+    let mut rest = data;
+    while rest.len() > max {
+        let mut end = max;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        let space = if rest.as_bytes()[end] == b' ' {
+            Some(end)
+        } else {
+            rest[..end].rfind(' ')
+        };
+
+        let (line, next) = match space {
+            Some(pos) if pos > 0 => (&rest[..pos], &rest[pos + 1..]),
+            _ => (&rest[..end], &rest[end..]),
+        };
+        lines.push(line);
+        rest = next;
+    }
+
+    if !rest.is_empty() || lines.is_empty() {
+        lines.push(rest);
+    }
+    lines
+}
+
+/// like `split`, but a CTCP is split inside its delimiters so each line is still one
+pub fn split_message(data: &str, max: usize) -> Vec<String> {
+    let ctcp = match Ctcp::parse(data) {
+        Some(ref ctcp) if !ctcp.params.is_empty() => ctcp.clone(),
+        _ => return split(data, max).into_iter().map(str::to_string).collect(),
+    };
+
+    // \x01COMMAND params\x01
+    let max = max.saturating_sub(ctcp.command.len() + 3);
+    split(&ctcp.params, max)
+        .into_iter()
+        .map(|params| Ctcp::new(&ctcp.command, params).encode())
+        .collect()
+}
+
+/// cuts a line (ending with the CRLF) down to what a server accepts, without splitting a char
+pub fn truncate(line: &[u8]) -> Vec<u8> {
+    if line.len() <= MAX_LINE {
+        return line.to_vec();
+    }
+
+    // the first byte of a char is never 0b10xxxxxx
+    let end = (0..=MAX_LINE - 2)
+        .rev()
+        .find(|&i| line[i] & 0xC0 != 0x80)
+        .unwrap_or(0);
+
+    let mut out = line[..end].to_vec();
+    out.extend_from_slice(b"\r\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short() {
+        assert_eq!(split("hello world", 100), vec!["hello world"]);
+        assert_eq!(split("", 100), vec![""]);
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(
+            split("the quick brown fox jumps", 10),
+            vec!["the quick", "brown fox", "jumps"]
+        );
+        // the space right at the limit is still a good place to split
+        assert_eq!(split("aaaaa bbbbb", 5), vec!["aaaaa", "bbbbb"]);
+    }
+
+    #[test]
+    fn long_words() {
+        assert_eq!(split("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+    }
+
+    #[test]
+    fn utf8() {
+        // each of these is 3 bytes
+        let data = "日本語のテキスト";
+        let lines = split(data, 10);
+        assert_eq!(lines, vec!["日本語", "のテキ", "スト"]);
+        assert_eq!(lines.concat(), data);
+    }
+
+    #[test]
+    fn ctcp() {
+        let data = Ctcp::new("ACTION", "waves at everyone here").encode();
+        let lines = split_message(&data, 20);
+        assert_eq!(
+            lines,
+            vec![
+                "\x01ACTION waves at\x01",
+                "\x01ACTION everyone\x01",
+                "\x01ACTION here\x01",
+            ]
+        );
+        assert!(lines.iter().all(|line| line.len() <= 20));
+
+        // anything else is split as it is
+        assert_eq!(split_message("hello world", 5), vec!["hello", "world"]);
+        assert_eq!(
+            split_message("\x01VERSION\x01", 20),
+            vec!["\x01VERSION\x01"]
+        );
+    }
+
+    #[test]
+    fn truncating() {
+        let line = b"PING short\r\n";
+        assert_eq!(truncate(line), line.to_vec());
+
+        let line = format!("PRIVMSG #c :{}\r\n", "a".repeat(600));
+        let out = truncate(line.as_bytes());
+        assert_eq!(out.len(), MAX_LINE);
+        assert!(out.ends_with(b"a\r\n"));
+
+        // the 3 byte char that would straddle the limit is dropped
+        let line = format!("PRIVMSG #c :{}\r\n", "日".repeat(200));
+        let out = truncate(line.as_bytes());
+        assert!(out.len() <= MAX_LINE);
+        assert!(out.ends_with(b"\r\n"));
+        let text = ::std::str::from_utf8(&out).unwrap();
+        assert!(text.trim_end().ends_with('日'));
+    }
+
+    #[test]
+    fn fits() {
+        let nick = "somebody";
+        let target = "#channel";
+        let max = max_payload(nick.len(), "PRIVMSG", target);
+
+        let data = "word ".repeat(300);
+        for line in split(&data, max) {
+            let full = format!(
+                ":{}!{}@{} PRIVMSG {} :{}\r\n",
+                nick,
+                "u".repeat(MAX_USER),
+                "h".repeat(MAX_HOST),
+                target,
+                line
+            );
+            assert!(full.len() <= MAX_LINE, "{}", full.len());
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use super::Error;
+
+use native_tls::{Certificate, TlsConnector, TlsStream};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// how long the reader holds the tls stream before letting a writer in
+const TLS_READ_TIMEOUT: u64 = 50;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsConfig {
+    /// verify the certificate chain and hostname
+    pub verify: bool,
+    /// a sha-256 fingerprint of the server certificate, this replaces chain verification
+    pub fingerprint: Option<String>,
+    /// a PEM encoded CA to trust, in addition to the system roots
+    pub ca_file: Option<PathBuf>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            verify: true,
+            fingerprint: None,
+            ca_file: None,
+        }
+    }
+}
+
+/// Either half of a connection, cloning it gives you the other half
+pub enum Stream {
+    Plain(TcpStream),
+    Tls(Arc<Mutex<TlsStream<TcpStream>>>),
+}
+
+impl Stream {
+    pub fn connect(addr: &str, tls: Option<&TlsConfig>) -> Result<Self, Error> {
+        let conn = TcpStream::connect(addr).map_err(|_err| Error::CannotConnect)?;
+        let tls = match tls {
+            Some(tls) => tls,
+            None => return Ok(Stream::Plain(conn)),
+        };
+
+        let mut builder = TlsConnector::builder();
+        if let Some(path) = &tls.ca_file {
+            let pem = ::std::fs::read(path)
+                .map_err(|err| Error::Tls(format!("cannot read {}: {}", path.display(), err)))?;
+            let cert = Certificate::from_pem(&pem).map_err(|err| Error::Tls(err.to_string()))?;
+            builder.add_root_certificate(cert);
+        }
+
+        // a pinned certificate is usually self-signed, so the pin is the verification
+        if !tls.verify || tls.fingerprint.is_some() {
+            builder.danger_accept_invalid_certs(true);
+            builder.danger_accept_invalid_hostnames(true);
+        }
+
+        let connector = builder.build().map_err(|err| Error::Tls(err.to_string()))?;
+        let stream = connector
+            .connect(host(addr), conn)
+            .map_err(|err| Error::Tls(err.to_string()))?;
+
+        if let Some(expected) = &tls.fingerprint {
+            let cert = stream
+                .peer_certificate()
+                .map_err(|err| Error::Tls(err.to_string()))?
+                .ok_or_else(|| Error::Tls("server sent no certificate".into()))?;
+            let der = cert.to_der().map_err(|err| Error::Tls(err.to_string()))?;
+
+            let actual = fingerprint(&der);
+            if normalize(expected) != actual {
+                return Err(Error::FingerprintMismatch(actual));
+            }
+        }
+
+        stream
+            .get_ref()
+            .set_read_timeout(Some(Duration::from_millis(TLS_READ_TIMEOUT)))
+            .map_err(|_err| Error::CannotConnect)?;
+
+        Ok(Stream::Tls(Arc::new(Mutex::new(stream))))
+    }
+
+    pub fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Stream::Plain(conn) => conn.try_clone().map(Stream::Plain),
+            Stream::Tls(conn) => Ok(Stream::Tls(Arc::clone(conn))),
+        }
+    }
+
+    pub fn shutdown(&self) -> io::Result<()> {
+        match self {
+            Stream::Plain(conn) => conn.shutdown(Shutdown::Both),
+            Stream::Tls(conn) => {
+                let conn = &mut *conn.lock().unwrap();
+                // the close_notify is a courtesy, the socket is going away regardless
+                let _ = conn.shutdown();
+                conn.get_ref().shutdown(Shutdown::Both)
+            }
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(conn) => conn.read(buf),
+            Stream::Tls(conn) => loop {
+                // the lock is released between attempts so writes can get through
+                match conn.lock().unwrap().read(buf) {
+                    Err(ref err)
+                        if err.kind() == io::ErrorKind::WouldBlock
+                            || err.kind() == io::ErrorKind::TimedOut => {}
+                    res => return res,
+                }
+            },
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(conn) => conn.write(buf),
+            Stream::Tls(conn) => conn.lock().unwrap().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Plain(conn) => conn.flush(),
+            Stream::Tls(conn) => conn.lock().unwrap().flush(),
+        }
+    }
+}
+
+fn host(addr: &str) -> &str {
+    let host = match addr.rfind(':') {
+        Some(pos) => &addr[..pos],
+        None => addr,
+    };
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+/// lowercase hex, without separators
+fn fingerprint(der: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn normalize(fp: &str) -> String {
+    fp.chars()
+        .filter(|c| c.is_ascii_hexdigit())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use native_tls::{Identity, TlsAcceptor};
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::thread;
+
+    const IDENTITY: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/data/localhost.p12"
+    ));
+
+    const FINGERPRINT: &str = "89:75:3F:56:8C:F3:DB:33:22:19:6A:C4:6D:C4:8C:39:\
+                               17:2F:95:A6:CF:8C:36:CE:2C:AF:63:DC:79:39:EB:CB";
+
+    fn ca_file() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/localhost.pem")
+    }
+
+    /// a self-signed listener that echoes one line back
+    fn listener(tls: bool) -> String {
+        let listener = TcpListener::bind("localhost:0").unwrap();
+        let addr = format!("localhost:{}", listener.local_addr().unwrap().port());
+
+        thread::spawn(move || {
+            let (conn, _) = listener.accept().unwrap();
+            if !tls {
+                return echo(conn);
+            }
+
+            let identity = Identity::from_pkcs12(IDENTITY, "riirc").unwrap();
+            let acceptor = TlsAcceptor::new(identity).unwrap();
+            if let Ok(conn) = acceptor.accept(conn) {
+                echo(conn)
+            }
+        });
+
+        addr
+    }
+
+    fn echo(mut conn: impl Read + Write) {
+        let mut line = String::new();
+        BufReader::new(&mut conn).read_line(&mut line).unwrap();
+        conn.write_all(line.as_bytes()).unwrap();
+        conn.flush().unwrap();
+    }
+
+    fn roundtrip(stream: Stream) {
+        let mut write = stream.try_clone().unwrap();
+        write.write_all(b"PING :test\r\n").unwrap();
+
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        assert_eq!(line, "PING :test\r\n");
+    }
+
+    #[test]
+    fn plain() {
+        let addr = listener(false);
+        roundtrip(Stream::connect(&addr, None).unwrap());
+    }
+
+    #[test]
+    fn tls_custom_ca() {
+        let addr = listener(true);
+        let config = TlsConfig {
+            ca_file: Some(ca_file()),
+            ..TlsConfig::default()
+        };
+        roundtrip(Stream::connect(&addr, Some(&config)).unwrap());
+    }
+
+    #[test]
+    fn tls_untrusted() {
+        let addr = listener(true);
+        match Stream::connect(&addr, Some(&TlsConfig::default())) {
+            Err(Error::Tls(_)) => {}
+            Err(err) => panic!("unexpected error: {:?}", err),
+            Ok(_) => panic!("self-signed certificate was trusted"),
+        }
+    }
+
+    #[test]
+    fn tls_no_verify() {
+        let addr = listener(true);
+        let config = TlsConfig {
+            verify: false,
+            ..TlsConfig::default()
+        };
+        roundtrip(Stream::connect(&addr, Some(&config)).unwrap());
+    }
+
+    #[test]
+    fn tls_pinned() {
+        let addr = listener(true);
+        let config = TlsConfig {
+            fingerprint: Some(FINGERPRINT.into()),
+            ..TlsConfig::default()
+        };
+        roundtrip(Stream::connect(&addr, Some(&config)).unwrap());
+
+        let addr = listener(true);
+        let config = TlsConfig {
+            fingerprint: Some("00".repeat(32)),
+            ..TlsConfig::default()
+        };
+        match Stream::connect(&addr, Some(&config)) {
+            Err(Error::FingerprintMismatch(fp)) => assert_eq!(fp, normalize(FINGERPRINT)),
+            Err(err) => panic!("unexpected error: {:?}", err),
+            Ok(_) => panic!("wrong fingerprint was accepted"),
+        }
+    }
+
+    #[test]
+    fn host_from_addr() {
+        assert_eq!(host("irc.example.com:6697"), "irc.example.com");
+        assert_eq!(host("irc.example.com"), "irc.example.com");
+        assert_eq!(host("[::1]:6697"), "::1");
+    }
+}
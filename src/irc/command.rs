@@ -1,6 +1,6 @@
 use super::is_valid_nick;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Error {
     MissingCommand,
     MissingData,
@@ -34,6 +34,17 @@ pub enum Command {
     Nick {
         nickname: String,
     },
+    // the arguments are only known once the server's CHANMODES is, see `ModeChange::parse`
+    Mode {
+        target: String,
+        modes: String,
+        args: Vec<String>,
+    },
+    // an empty topic means it was cleared
+    Topic {
+        channel: String,
+        topic: String,
+    },
     Ping {
         token: String,
     },
@@ -43,7 +54,16 @@ pub enum Command {
     Error {
         message: String,
     },
-    // TODO CAP and TAG
+    Cap {
+        target: String,
+        subcommand: String,
+        // a '*' before the list means there are more lines coming
+        more: bool,
+        caps: Vec<String>,
+    },
+    Authenticate {
+        data: String,
+    },
     Other {
         command: String,
         params: Vec<String>,
@@ -139,6 +159,33 @@ impl Command {
                 }
             }
 
+            "MODE" => {
+                let mut params = split_params(rest).into_iter();
+                let target = match params.next() {
+                    Some(target) if !target.is_empty() => target,
+                    _ => return Err(Error::MissingTarget),
+                };
+
+                Command::Mode {
+                    target,
+                    modes: params.next().ok_or(Error::MissingData)?,
+                    args: params.collect(),
+                }
+            }
+
+            "TOPIC" => {
+                let mut params = split_params(rest).into_iter();
+                let channel = match params.next() {
+                    Some(channel) if !channel.is_empty() => channel,
+                    _ => return Err(Error::MissingTarget),
+                };
+
+                Command::Topic {
+                    channel,
+                    topic: params.next().ok_or(Error::MissingData)?,
+                }
+            }
+
             "PING" => Command::Ping {
                 token: rest.to_owned(),
             },
@@ -156,23 +203,40 @@ impl Command {
                 }
             }
 
-            command => {
-                let params = if let Some(pos) = rest.find(':') {
-                    let (l, r) = rest.split_at(pos);
-                    let (l, r) = (l.trim(), r.trim());
-                    let r = if r.get(0..1) == Some(":") { &r[1..] } else { r }.to_owned();
-
-                    if l.is_empty() {
-                        vec![r]
-                    } else {
-                        let mut v = l.split(' ').map(|s| s.to_owned()).collect::<Vec<_>>();
-                        v.push(r);
-                        v
-                    }
-                } else {
-                    rest.split(' ').map(|s| s.to_owned()).collect::<Vec<_>>()
+            "CAP" => {
+                let mut params = split_params(rest).into_iter();
+                let (target, subcommand) = match (params.next(), params.next()) {
+                    (Some(target), Some(subcommand)) => (target, subcommand),
+                    _ => return Err(Error::MissingParts),
                 };
 
+                let (more, list) = match (params.next(), params.next()) {
+                    (Some(ref star), Some(list)) if star == "*" => (true, list),
+                    (Some(list), None) => (false, list),
+                    (None, None) => (false, String::new()),
+                    _ => return Err(Error::MissingParts),
+                };
+
+                Command::Cap {
+                    target,
+                    subcommand: subcommand.to_ascii_uppercase(),
+                    more,
+                    caps: list.split_whitespace().map(|s| s.to_owned()).collect(),
+                }
+            }
+
+            "AUTHENTICATE" => {
+                if rest.is_empty() {
+                    return Err(Error::MissingData);
+                }
+
+                Command::Authenticate {
+                    data: rest.to_owned(),
+                }
+            }
+
+            command => {
+                let params = split_params(rest);
                 if let Ok(n) = command.parse::<u16>() {
                     Command::Reply { numeric: n, params }
                 } else {
@@ -188,6 +252,24 @@ impl Command {
     }
 }
 
+fn split_params(rest: &str) -> Vec<String> {
+    if let Some(pos) = rest.find(':') {
+        let (l, r) = rest.split_at(pos);
+        let (l, r) = (l.trim(), r.trim());
+        let r = if r.get(0..1) == Some(":") { &r[1..] } else { r }.to_owned();
+
+        if l.is_empty() {
+            vec![r]
+        } else {
+            let mut v = l.split(' ').map(|s| s.to_owned()).collect::<Vec<_>>();
+            v.push(r);
+            v
+        }
+    } else {
+        rest.split(' ').map(|s| s.to_owned()).collect::<Vec<_>>()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -347,6 +429,50 @@ mod tests {
         assert_eq!(Command::parse("ERROR test"), Err(Error::MissingData));
     }
 
+    #[test]
+    fn parse_mode() {
+        assert_eq!(
+            Command::parse("MODE #test +o-v alice bob"),
+            Ok(Command::Mode {
+                target: "#test".into(),
+                modes: "+o-v".into(),
+                args: vec!["alice".into(), "bob".into()]
+            })
+        );
+
+        assert_eq!(
+            Command::parse("MODE me :+i"),
+            Ok(Command::Mode {
+                target: "me".into(),
+                modes: "+i".into(),
+                args: vec![]
+            })
+        );
+
+        assert_eq!(Command::parse("MODE #test"), Err(Error::MissingData));
+    }
+
+    #[test]
+    fn parse_topic() {
+        assert_eq!(
+            Command::parse("TOPIC #test :a new topic"),
+            Ok(Command::Topic {
+                channel: "#test".into(),
+                topic: "a new topic".into()
+            })
+        );
+
+        assert_eq!(
+            Command::parse("TOPIC #test :"),
+            Ok(Command::Topic {
+                channel: "#test".into(),
+                topic: "".into()
+            })
+        );
+
+        assert_eq!(Command::parse("TOPIC #test"), Err(Error::MissingData));
+    }
+
     #[test]
     fn parse_reply() {
         assert_eq!(
@@ -369,6 +495,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_cap() {
+        let inputs = &[
+            (
+                "CAP * LS * :multi-prefix extended-join sasl=PLAIN,EXTERNAL",
+                ("*", "LS", true, vec!["multi-prefix", "extended-join", "sasl=PLAIN,EXTERNAL"]),
+            ),
+            (
+                "CAP * LS :server-time",
+                ("*", "LS", false, vec!["server-time"]),
+            ),
+            (
+                "CAP museun ACK :multi-prefix -away-notify",
+                ("museun", "ACK", false, vec!["multi-prefix", "-away-notify"]),
+            ),
+            (
+                "CAP museun NAK :sasl",
+                ("museun", "NAK", false, vec!["sasl"]),
+            ),
+            ("CAP museun NEW batch", ("museun", "NEW", false, vec!["batch"])),
+            ("CAP museun LIST :", ("museun", "LIST", false, vec![])),
+        ];
+
+        for (input, (target, subcommand, more, caps)) in inputs {
+            assert_eq!(
+                Command::parse(input),
+                Ok(Command::Cap {
+                    target: target.to_string(),
+                    subcommand: subcommand.to_string(),
+                    more: *more,
+                    caps: caps.iter().map(|s| s.to_string()).collect(),
+                })
+            );
+        }
+
+        assert_eq!(Command::parse("CAP *"), Err(Error::MissingParts));
+    }
+
+    #[test]
+    fn parse_authenticate() {
+        assert_eq!(
+            Command::parse("AUTHENTICATE +"),
+            Ok(Command::Authenticate { data: "+".into() })
+        );
+        assert_eq!(Command::parse("AUTHENTICATE "), Err(Error::MissingData));
+    }
+
     #[test]
     fn parse_other() {
         assert_eq!(
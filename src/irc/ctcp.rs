@@ -0,0 +1,177 @@
+use chrono::prelude::*;
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+pub const DELIM: char = '\x01';
+
+// what CLIENTINFO says we understand
+const SUPPORTED: &[&str] = &["ACTION", "CLIENTINFO", "PING", "TIME", "VERSION"];
+
+/// A CTCP message, a query when it's in a PRIVMSG and a reply when it's in a NOTICE
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ctcp {
+    /// always upper case
+    pub command: String,
+    pub params: String,
+}
+
+impl Ctcp {
+    pub fn new(command: impl AsRef<str>, params: impl Into<String>) -> Self {
+        Self {
+            command: command.as_ref().to_ascii_uppercase(),
+            params: params.into(),
+        }
+    }
+
+    /// the CTCP in a message body, if there is one. some clients leave off the closing delimiter
+    pub fn parse(data: &str) -> Option<Self> {
+        if !data.starts_with(DELIM) {
+            return None;
+        }
+
+        let mut parts = data[1..].trim_end_matches(DELIM).splitn(2, ' ');
+        let command = parts.next().unwrap();
+        if command.is_empty() {
+            return None;
+        }
+        Some(Self::new(command, parts.next().unwrap_or_default()))
+    }
+
+    pub fn is_action(&self) -> bool {
+        self.command == "ACTION"
+    }
+
+    /// the message body, wrapped in the delimiters
+    pub fn encode(&self) -> String {
+        if self.params.is_empty() {
+            format!("{0}{1}{0}", DELIM, self.command)
+        } else {
+            format!("{0}{1} {2}{0}", DELIM, self.command, self.params)
+        }
+    }
+
+    /// the answer to a query, if it gets one
+    pub fn reply<Tz: TimeZone>(&self, now: DateTime<Tz>) -> Option<Self>
+    where
+        Tz::Offset: fmt::Display,
+    {
+        let params = match self.command.as_str() {
+            "VERSION" => format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            "PING" => self.params.clone(),
+            "TIME" => now.to_rfc2822(),
+            "CLIENTINFO" => SUPPORTED.join(" "),
+            _ => return None,
+        };
+        Some(Self::new(&self.command, params))
+    }
+}
+
+/// Keeps the automatic replies from getting us kicked off for flooding
+#[derive(Debug)]
+pub struct Limiter {
+    max: usize,
+    per: Duration,
+    sent: VecDeque<Instant>,
+}
+
+impl Default for Limiter {
+    fn default() -> Self {
+        Self::new(3, Duration::from_secs(10))
+    }
+}
+
+impl Limiter {
+    /// at most `max` replies for any `per`
+    pub fn new(max: usize, per: Duration) -> Self {
+        Self {
+            max,
+            per,
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// whether another reply can be sent, counting it if it can
+    pub fn allow(&mut self, now: Instant) -> bool {
+        while let Some(&sent) = self.sent.front() {
+            if now.duration_since(sent) < self.per {
+                break;
+            }
+            self.sent.pop_front();
+        }
+
+        if self.sent.len() >= self.max {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Ctcp::parse("\x01ACTION waves hello\x01"),
+            Some(Ctcp::new("ACTION", "waves hello"))
+        );
+        assert_eq!(Ctcp::parse("\x01version"), Some(Ctcp::new("VERSION", "")));
+        assert!(Ctcp::parse("\x01ACTION \x01").unwrap().is_action());
+        assert_eq!(Ctcp::parse("\x01\x01"), None);
+        assert_eq!(Ctcp::parse("hello"), None);
+    }
+
+    #[test]
+    fn encode() {
+        assert_eq!(
+            Ctcp::new("action", "waves").encode(),
+            "\x01ACTION waves\x01"
+        );
+        assert_eq!(Ctcp::new("VERSION", "").encode(), "\x01VERSION\x01");
+    }
+
+    #[test]
+    fn replies() {
+        let now = Utc.timestamp_opt(1_500_000_000, 0).unwrap();
+        let reply = |data: &str| Ctcp::parse(data).and_then(|ctcp| ctcp.reply(now));
+
+        assert_eq!(
+            reply("\x01PING 12345\x01"),
+            Some(Ctcp::new("PING", "12345"))
+        );
+        assert_eq!(
+            reply("\x01TIME\x01"),
+            Some(Ctcp::new("TIME", "Fri, 14 Jul 2017 02:40:00 +0000"))
+        );
+        assert_eq!(
+            reply("\x01CLIENTINFO\x01"),
+            Some(Ctcp::new(
+                "CLIENTINFO",
+                "ACTION CLIENTINFO PING TIME VERSION"
+            ))
+        );
+        assert!(reply("\x01VERSION\x01")
+            .unwrap()
+            .params
+            .starts_with("riirc "));
+        assert_eq!(reply("\x01ACTION waves\x01"), None);
+        assert_eq!(reply("\x01FINGER\x01"), None);
+    }
+
+    #[test]
+    fn limiter() {
+        let now = Instant::now();
+        let mut limiter = Limiter::new(2, Duration::from_secs(10));
+        assert!(limiter.allow(now));
+        assert!(limiter.allow(now + Duration::from_secs(1)));
+        assert!(!limiter.allow(now + Duration::from_secs(2)));
+
+        // the first one has aged out
+        assert!(limiter.allow(now + Duration::from_secs(10)));
+        assert!(!limiter.allow(now + Duration::from_secs(10)));
+        assert!(limiter.allow(now + Duration::from_secs(11)));
+    }
+}
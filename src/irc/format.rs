@@ -0,0 +1,284 @@
+pub const BOLD: char = '\x02';
+pub const COLOR: char = '\x03';
+pub const HEX_COLOR: char = '\x04';
+pub const RESET: char = '\x0F';
+pub const MONOSPACE: char = '\x11';
+pub const REVERSE: char = '\x16';
+pub const ITALIC: char = '\x1D';
+pub const STRIKETHROUGH: char = '\x1E';
+pub const UNDERLINE: char = '\x1F';
+
+/// A color from a formatting code
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    /// 0 to 98, 99 is the default color so it never shows up here
+    Palette(u8),
+    Rgb(u8, u8, u8),
+}
+
+// the 99 colors, the first 16 are the common ones
+#[rustfmt::skip]
+const PALETTE: [u32; 99] = [
+    0xFFFFFF, 0x000000, 0x00007F, 0x009300, 0xFF0000, 0x7F0000, 0x9C009C, 0xFC7F00,
+    0xFFFF00, 0x00FC00, 0x009393, 0x00FFFF, 0x0000FC, 0xFF00FF, 0x7F7F7F, 0xD2D2D2,
+    0x470000, 0x472100, 0x474700, 0x324700, 0x004700, 0x00472C, 0x004747, 0x002747, 0x000047, 0x2E0047, 0x470047, 0x47002A,
+    0x740000, 0x743A00, 0x747400, 0x517400, 0x007400, 0x007449, 0x007474, 0x004074, 0x000074, 0x4B0074, 0x740074, 0x740045,
+    0xB50000, 0xB56300, 0xB5B500, 0x7DB500, 0x00B500, 0x00B571, 0x00B5B5, 0x0063B5, 0x0000B5, 0x7500B5, 0xB500B5, 0xB5006B,
+    0xFF0000, 0xFF8C00, 0xFFFF00, 0xB2FF00, 0x00FF00, 0x00FFA0, 0x00FFFF, 0x008CFF, 0x0000FF, 0xA500FF, 0xFF00FF, 0xFF0098,
+    0xFF5959, 0xFFB459, 0xFFFF71, 0xCFFF60, 0x6FFF6F, 0x65FFC9, 0x6DFFFF, 0x59B4FF, 0x5959FF, 0xC459FF, 0xFF66FF, 0xFF59BC,
+    0xFF9C9C, 0xFFD39C, 0xFFFF9C, 0xE2FF9C, 0x9CFF9C, 0x9CFFDB, 0x9CFFFF, 0x9CD3FF, 0x9C9CFF, 0xDC9CFF, 0xFF9CFF, 0xFF94D3,
+    0x000000, 0x131313, 0x282828, 0x363636, 0x4D4D4D, 0x656565, 0x818181, 0x9F9F9F, 0xBCBCBC, 0xE2E2E2, 0xFFFFFF,
+];
+
+impl Color {
+    /// what the color looks like, for when it has to be approximated
+    pub fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            Color::Palette(n) => {
+                let c = PALETTE[n as usize % PALETTE.len()];
+                ((c >> 16) as u8, (c >> 8) as u8, c as u8)
+            }
+            Color::Rgb(r, g, b) => (r, g, b),
+        }
+    }
+}
+
+/// How a run of text should look
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Style {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+    pub monospace: bool,
+    pub reverse: bool,
+    /// `None` leaves it up to whatever is showing the text
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+}
+
+/// splits `input` into runs of text and how each one looks, without the codes
+pub fn parse(input: &str) -> Vec<(String, Style)> {
+    let mut runs = vec![];
+    let mut style = Style::default();
+    let mut text = String::new();
+
+    let mut chars = input.chars().peekable();
+    while let Some(ch) = chars.next() {
+        let mut next = style;
+        match ch {
+            BOLD => next.bold = !next.bold,
+            ITALIC => next.italic = !next.italic,
+            UNDERLINE => next.underline = !next.underline,
+            STRIKETHROUGH => next.strikethrough = !next.strikethrough,
+            MONOSPACE => next.monospace = !next.monospace,
+            REVERSE => next.reverse = !next.reverse,
+            RESET => next = Style::default(),
+            COLOR => {
+                // 99 is the default color
+                let colors = read_colors(&mut chars, 2, |s| {
+                    s.parse().ok().filter(|&n| n < 99).map(Color::Palette)
+                });
+                apply_colors(&mut next, colors)
+            }
+            HEX_COLOR => {
+                let colors = read_colors(&mut chars, 6, |s| {
+                    u32::from_str_radix(s, 16)
+                        .ok()
+                        .map(|c| Color::Rgb((c >> 16) as u8, (c >> 8) as u8, c as u8))
+                });
+                apply_colors(&mut next, colors)
+            }
+            ch => {
+                text.push(ch);
+                continue;
+            }
+        }
+
+        if next != style && !text.is_empty() {
+            runs.push((text.clone(), style));
+            text.clear();
+        }
+        style = next;
+    }
+
+    if !text.is_empty() {
+        runs.push((text, style));
+    }
+    runs
+}
+
+/// the text without any of the formatting
+pub fn strip(input: &str) -> String {
+    parse(input).into_iter().map(|(text, _)| text).collect()
+}
+
+// the foreground and background a color code set, if it set them
+type Colors = (Option<Option<Color>>, Option<Option<Color>>);
+
+// a color code without any colors after it resets them
+fn apply_colors(style: &mut Style, (fg, bg): Colors) {
+    match fg {
+        Some(fg) => style.fg = fg,
+        None => {
+            style.fg = None;
+            style.bg = None;
+        }
+    }
+    if let Some(bg) = bg {
+        style.bg = bg
+    }
+}
+
+// `fg[,bg]`, where each is up to `len` digits. the comma is only eaten if a color follows it
+fn read_colors<I, F>(chars: &mut ::std::iter::Peekable<I>, len: usize, parse: F) -> Colors
+where
+    I: Iterator<Item = char> + Clone,
+    F: Fn(&str) -> Option<Color>,
+{
+    let digit = |c: char| {
+        if len == 2 {
+            c.is_ascii_digit()
+        } else {
+            c.is_ascii_hexdigit()
+        }
+    };
+    let read = |chars: &mut ::std::iter::Peekable<I>| {
+        let mut s = String::new();
+        while s.len() < len {
+            match chars.peek() {
+                Some(&c) if digit(c) => s.push(c),
+                _ => break,
+            }
+            chars.next();
+        }
+        s
+    };
+
+    // hex colors have to be all six digits, or it's just text
+    let valid = |s: &str| !s.is_empty() && (len == 2 || s.len() == len);
+
+    let mut ahead = chars.clone();
+    let fg = read(&mut ahead);
+    if !valid(&fg) {
+        return (None, None);
+    }
+    *chars = ahead;
+
+    let mut ahead = chars.clone();
+    if ahead.next() != Some(',') {
+        return (Some(parse(&fg)), None);
+    }
+    let bg = read(&mut ahead);
+    if !valid(&bg) {
+        return (Some(parse(&fg)), None);
+    }
+    *chars = ahead;
+    (Some(parse(&fg)), Some(parse(&bg)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn styled(f: impl Fn(&mut Style)) -> Style {
+        let mut style = Style::default();
+        f(&mut style);
+        style
+    }
+
+    #[test]
+    fn attributes() {
+        let runs = parse("plain \x02bold\x02 \x1D\x1Fboth\x0F done");
+        assert_eq!(
+            runs,
+            vec![
+                ("plain ".into(), Style::default()),
+                ("bold".into(), styled(|s| s.bold = true)),
+                (" ".into(), Style::default()),
+                (
+                    "both".into(),
+                    styled(|s| {
+                        s.italic = true;
+                        s.underline = true;
+                    })
+                ),
+                (" done".into(), Style::default()),
+            ]
+        );
+        assert_eq!(
+            parse("\x16x"),
+            vec![("x".into(), styled(|s| s.reverse = true))]
+        );
+        assert_eq!(parse(""), vec![]);
+        assert_eq!(parse("\x02\x02"), vec![]);
+    }
+
+    #[test]
+    fn colors() {
+        let red = Some(Color::Palette(4));
+        let blue = Some(Color::Palette(2));
+
+        assert_eq!(
+            parse("\x034red"),
+            vec![("red".into(), styled(|s| s.fg = red))]
+        );
+        assert_eq!(
+            parse("\x0304,02x\x03y"),
+            vec![
+                (
+                    "x".into(),
+                    styled(|s| {
+                        s.fg = red;
+                        s.bg = blue;
+                    })
+                ),
+                ("y".into(), Style::default()),
+            ]
+        );
+        // only two digits are read, and the comma stays when no color follows it
+        assert_eq!(parse("\x03041"), vec![("1".into(), styled(|s| s.fg = red))]);
+        assert_eq!(
+            parse("\x034,x"),
+            vec![(",x".into(), styled(|s| s.fg = red))]
+        );
+        // 99 is the default
+        assert_eq!(
+            parse("\x0399,02x"),
+            vec![("x".into(), styled(|s| s.bg = blue))]
+        );
+        // the background is kept when only the foreground changes
+        assert_eq!(
+            parse("\x034,2\x033x"),
+            vec![(
+                "x".into(),
+                styled(|s| {
+                    s.fg = Some(Color::Palette(3));
+                    s.bg = blue;
+                })
+            )]
+        );
+    }
+
+    #[test]
+    fn hex_colors() {
+        assert_eq!(
+            parse("\x04FF8000,000000x"),
+            vec![(
+                "x".into(),
+                styled(|s| {
+                    s.fg = Some(Color::Rgb(0xFF, 0x80, 0x00));
+                    s.bg = Some(Color::Rgb(0, 0, 0));
+                })
+            )]
+        );
+        assert_eq!(parse("\x04FFx"), vec![("FFx".into(), Style::default())]);
+    }
+
+    #[test]
+    fn stripping() {
+        assert_eq!(strip("\x02\x0304,02hello\x0F \x1Fworld\x1F"), "hello world");
+        assert_eq!(strip("no codes"), "no codes");
+        assert_eq!(Color::Palette(52).rgb(), (0xFF, 0, 0));
+    }
+}
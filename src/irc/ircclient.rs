@@ -3,6 +3,10 @@ pub trait IrcClient {
         self.write(&format!("PRIVMSG {} :{}\r\n", target.as_ref(), data.as_ref()).as_bytes())
     }
 
+    fn notice(&self, target: impl AsRef<str>, data: impl AsRef<str>) {
+        self.write(format!("NOTICE {} :{}\r\n", target.as_ref(), data.as_ref()).as_bytes())
+    }
+
     fn pass(&self, pass: impl AsRef<str>) {
         self.write(&format!("PASS {}\r\n", pass.as_ref()).as_bytes())
     }
@@ -15,6 +19,26 @@ pub trait IrcClient {
         self.write(&format!("NICK {}\r\n", nick.as_ref()).as_bytes())
     }
 
+    fn cap_ls(&self) {
+        self.write(b"CAP LS 302\r\n")
+    }
+
+    fn cap_req<S>(&self, caps: impl AsRef<[S]>)
+    where
+        S: AsRef<str>,
+    {
+        let caps = caps.as_ref().iter().map(|s| s.as_ref()).collect::<Vec<_>>();
+        self.write(format!("CAP REQ :{}\r\n", caps.join(" ")).as_bytes())
+    }
+
+    fn cap_end(&self) {
+        self.write(b"CAP END\r\n")
+    }
+
+    fn authenticate(&self, data: impl AsRef<str>) {
+        self.write(format!("AUTHENTICATE {}\r\n", data.as_ref()).as_bytes())
+    }
+
     fn ping(&self, token: impl AsRef<str>) {
         self.write(&format!("PING {}\r\n", token.as_ref()).as_bytes())
     }
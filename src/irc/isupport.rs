@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+
+/// How the server decides two nicks (or channel names) are the same
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CaseMapping {
+    Ascii,
+    /// `[]\~` are the upper case `{}|^`
+    #[default]
+    Rfc1459,
+    /// like rfc1459, but without `~` and `^`
+    StrictRfc1459,
+}
+
+impl CaseMapping {
+    pub fn parse(input: &str) -> Option<Self> {
+        match input {
+            "ascii" => Some(CaseMapping::Ascii),
+            "rfc1459" => Some(CaseMapping::Rfc1459),
+            "strict-rfc1459" => Some(CaseMapping::StrictRfc1459),
+            _ => None,
+        }
+    }
+
+    pub fn to_lower(self, c: char) -> char {
+        match (self, c) {
+            (CaseMapping::Rfc1459, '~') => '^',
+            (CaseMapping::Rfc1459, c) | (CaseMapping::StrictRfc1459, c) => match c {
+                '[' => '{',
+                ']' => '}',
+                '\\' => '|',
+                c => c.to_ascii_lowercase(),
+            },
+            (CaseMapping::Ascii, c) => c.to_ascii_lowercase(),
+        }
+    }
+
+    /// the lower case form of `input`, for comparing and sorting
+    pub fn fold(self, input: &str) -> String {
+        input.chars().map(|c| self.to_lower(c)).collect()
+    }
+
+    pub fn eq_ignore_case(self, left: &str, right: &str) -> bool {
+        left.len() == right.len()
+            && left
+                .chars()
+                .zip(right.chars())
+                .all(|(l, r)| self.to_lower(l) == self.to_lower(r))
+    }
+}
+
+/// The channel modes, by the kind of argument they take
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChanModes {
+    /// lists, like bans. always take an argument
+    pub a: String,
+    /// settings that always take an argument, like the key
+    pub b: String,
+    /// settings that only take an argument when set, like the limit
+    pub c: String,
+    /// flags, which never take an argument
+    pub d: String,
+}
+
+impl Default for ChanModes {
+    fn default() -> Self {
+        Self {
+            a: "b".into(),
+            b: "k".into(),
+            c: "l".into(),
+            d: "imnpst".into(),
+        }
+    }
+}
+
+/// What the server told us about itself in RPL_ISUPPORT (005)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ISupport {
+    pub casemapping: CaseMapping,
+    /// the mode and the symbol shown in front of a nick, highest rank first
+    pub prefix: Vec<(char, char)>,
+    pub chantypes: String,
+    pub chanmodes: ChanModes,
+    pub nicklen: usize,
+    pub topiclen: Option<usize>,
+    /// how many modes with an argument can be sent at once
+    pub modes: Option<usize>,
+    /// how many targets a command takes, `None` for no limit
+    pub targmax: HashMap<String, Option<usize>>,
+    tokens: HashMap<String, String>,
+}
+
+impl Default for ISupport {
+    fn default() -> Self {
+        Self {
+            casemapping: CaseMapping::default(),
+            prefix: vec![('o', '@'), ('v', '+')],
+            chantypes: "#&".into(),
+            chanmodes: ChanModes::default(),
+            nicklen: 9,
+            topiclen: None,
+            modes: Some(3),
+            targmax: HashMap::new(),
+            tokens: HashMap::new(),
+        }
+    }
+}
+
+impl ISupport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `KEY`, `KEY=value` or `-KEY`, which puts it back to the default
+    pub fn apply(&mut self, token: &str) {
+        if let Some(key) = token.strip_prefix('-') {
+            self.tokens.remove(key);
+            return self.set(key, None);
+        }
+
+        let mut parts = token.splitn(2, '=');
+        let key = parts.next().unwrap();
+        let value = parts.next().unwrap_or_default();
+        self.tokens.insert(key.to_owned(), value.to_owned());
+        self.set(key, Some(value))
+    }
+
+    /// the raw value of a token, tokens without a value are empty
+    pub fn get(&self, key: impl AsRef<str>) -> Option<&str> {
+        self.tokens.get(key.as_ref()).map(|s| s.as_str())
+    }
+
+    pub fn is_channel(&self, name: impl AsRef<str>) -> bool {
+        match name.as_ref().chars().next() {
+            Some(c) => self.chantypes.contains(c),
+            None => false,
+        }
+    }
+
+    pub fn is_valid_nick(&self, nick: impl AsRef<str>) -> bool {
+        let nick = nick.as_ref();
+        super::is_valid_nick(nick) && nick.len() <= self.nicklen && !self.is_channel(nick)
+    }
+
+    /// the lower case form of a nick or channel, for comparing and sorting
+    pub fn fold(&self, name: impl AsRef<str>) -> String {
+        self.casemapping.fold(name.as_ref())
+    }
+
+    pub fn eq_ignore_case(&self, left: impl AsRef<str>, right: impl AsRef<str>) -> bool {
+        self.casemapping
+            .eq_ignore_case(left.as_ref(), right.as_ref())
+    }
+
+    /// splits a nick from a NAMES reply into its prefix symbols and the nick
+    pub fn split_prefix<'a>(&self, name: &'a str) -> (&'a str, &'a str) {
+        let pos = name
+            .find(|c| !self.prefix.iter().any(|&(_, symbol)| symbol == c))
+            .unwrap_or(name.len());
+        name.split_at(pos)
+    }
+
+    /// how many targets `command` can be sent to at once, `None` for no limit
+    pub fn targmax(&self, command: impl AsRef<str>) -> Option<usize> {
+        self.targmax
+            .get(&command.as_ref().to_ascii_uppercase())
+            .cloned()
+            .unwrap_or(None)
+    }
+
+    fn set(&mut self, key: &str, value: Option<&str>) {
+        let default = Self::default();
+        let number = |value: Option<&str>| value.and_then(|s| s.parse::<usize>().ok());
+
+        match key {
+            "CASEMAPPING" => {
+                self.casemapping = value
+                    .and_then(CaseMapping::parse)
+                    .unwrap_or(default.casemapping)
+            }
+            "PREFIX" => {
+                self.prefix = match value {
+                    Some(value) => parse_prefix(value).unwrap_or(default.prefix),
+                    None => default.prefix,
+                }
+            }
+            "CHANTYPES" => self.chantypes = value.map_or(default.chantypes, |s| s.to_owned()),
+            "CHANMODES" => {
+                self.chanmodes = match value {
+                    Some(value) => parse_chanmodes(value),
+                    None => default.chanmodes,
+                }
+            }
+            "NICKLEN" => self.nicklen = number(value).unwrap_or(default.nicklen),
+            "TOPICLEN" => self.topiclen = number(value),
+            // an empty MODES means there's no limit
+            "MODES" => {
+                self.modes = match value {
+                    Some("") => None,
+                    Some(_) => number(value),
+                    None => default.modes,
+                }
+            }
+            "TARGMAX" => self.targmax = value.map(parse_targmax).unwrap_or_default(),
+            _ => {}
+        }
+    }
+}
+
+// (qaohv)~&@%+
+fn parse_prefix(input: &str) -> Option<Vec<(char, char)>> {
+    if input.is_empty() {
+        return Some(vec![]);
+    }
+
+    if !input.starts_with('(') {
+        return None;
+    }
+    let end = input.find(')')?;
+    let (modes, symbols) = (&input[1..end], &input[end + 1..]);
+    if modes.chars().count() != symbols.chars().count() {
+        return None;
+    }
+    Some(modes.chars().zip(symbols.chars()).collect())
+}
+
+// beI,k,l,imnpst -- anything past the fourth group is new, and ignored
+fn parse_chanmodes(input: &str) -> ChanModes {
+    let mut groups = input.split(',').map(|s| s.to_owned());
+    let mut next = || groups.next().unwrap_or_default();
+    ChanModes {
+        a: next(),
+        b: next(),
+        c: next(),
+        d: next(),
+    }
+}
+
+// PRIVMSG:4,NOTICE:4,JOIN:
+fn parse_targmax(input: &str) -> HashMap<String, Option<usize>> {
+    input
+        .split(',')
+        .filter_map(|pair| {
+            let mut parts = pair.splitn(2, ':');
+            let command = parts.next()?;
+            if command.is_empty() {
+                return None;
+            }
+            let max = parts.next().and_then(|s| s.parse().ok());
+            Some((command.to_ascii_uppercase(), max))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn isupport(tokens: &[&str]) -> ISupport {
+        let mut isupport = ISupport::new();
+        for token in tokens {
+            isupport.apply(token)
+        }
+        isupport
+    }
+
+    #[test]
+    fn casemapping() {
+        let rfc = CaseMapping::Rfc1459;
+        assert_eq!(rfc.fold("Nick[]\\~"), "nick{}|^");
+        assert!(rfc.eq_ignore_case("#Test[away]", "#test{AWAY}"));
+        assert_eq!(CaseMapping::StrictRfc1459.fold("A~"), "a~");
+        assert_eq!(CaseMapping::Ascii.fold("A[]"), "a[]");
+        assert!(!rfc.eq_ignore_case("#test", "#testing"));
+    }
+
+    #[test]
+    fn tokens() {
+        let isupport = isupport(&[
+            "CASEMAPPING=ascii",
+            "PREFIX=(qaohv)~&@%+",
+            "CHANTYPES=#",
+            "CHANMODES=beI,k,l,imnpst,X",
+            "NICKLEN=30",
+            "TOPICLEN=390",
+            "MODES=",
+            "TARGMAX=PRIVMSG:4,NOTICE:4,JOIN:",
+            "EXCEPTS",
+        ]);
+
+        assert_eq!(isupport.casemapping, CaseMapping::Ascii);
+        assert_eq!(
+            isupport.prefix,
+            vec![('q', '~'), ('a', '&'), ('o', '@'), ('h', '%'), ('v', '+')]
+        );
+        assert_eq!(isupport.chantypes, "#");
+        assert_eq!(isupport.chanmodes.a, "beI");
+        assert_eq!(isupport.chanmodes.d, "imnpst");
+        assert_eq!(isupport.nicklen, 30);
+        assert_eq!(isupport.topiclen, Some(390));
+        assert_eq!(isupport.modes, None);
+        assert_eq!(isupport.targmax("privmsg"), Some(4));
+        assert_eq!(isupport.targmax("JOIN"), None);
+        assert_eq!(isupport.get("EXCEPTS"), Some(""));
+        assert_eq!(isupport.get("NICKLEN"), Some("30"));
+    }
+
+    #[test]
+    fn negated() {
+        let mut isupport = isupport(&["NICKLEN=30", "CHANTYPES=#", "EXCEPTS"]);
+        isupport.apply("-NICKLEN");
+        isupport.apply("-CHANTYPES");
+        isupport.apply("-EXCEPTS");
+
+        assert_eq!(isupport, ISupport::default());
+    }
+
+    #[test]
+    fn channels_and_nicks() {
+        let isupport = isupport(&["CHANTYPES=#", "NICKLEN=8"]);
+        assert!(isupport.is_channel("#test"));
+        assert!(!isupport.is_channel("&test"));
+        assert!(!isupport.is_channel(""));
+
+        assert!(isupport.is_valid_nick("someone"));
+        assert!(isupport.is_valid_nick("[away]"));
+        assert!(!isupport.is_valid_nick("somebody_else"));
+        assert!(!isupport.is_valid_nick("#test"));
+        assert!(!isupport.is_valid_nick("some one"));
+        assert!(!isupport.is_valid_nick(""));
+    }
+
+    #[test]
+    fn split_prefix() {
+        let isupport = isupport(&["PREFIX=(ov)@+"]);
+        assert_eq!(isupport.split_prefix("@+nick"), ("@+", "nick"));
+        assert_eq!(isupport.split_prefix("nick"), ("", "nick"));
+        assert_eq!(isupport.split_prefix("%nick"), ("", "%nick"));
+    }
+}
@@ -1,21 +1,30 @@
 use crate::irc::command::Error as CommandError;
 use crate::irc::prefix::Error as PrefixError;
-use crate::irc::{command::Command, prefix::Prefix};
+use crate::irc::tags::Error as TagsError;
+use crate::irc::{command::Command, prefix::Prefix, tags::Tags};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Error {
+    Tags(TagsError),
     PrefixError(PrefixError),
     CommandError(CommandError),
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Message {
+    pub tags: Tags,
     pub prefix: Option<Prefix>,
     pub command: Command,
 }
 
 impl Message {
     pub fn parse(input: &str) -> Result<Self, Error> {
+        let (tags, input) = match Tags::parse(input) {
+            Ok((tags, end)) => (tags, input[end + 1..].trim_start()),
+            Err(TagsError::MissingLead) => (Tags::new(), input),
+            Err(err) => return Err(Error::Tags(err)),
+        };
+
         let prefix = match Prefix::parse(input) {
             Ok((prefix, end)) => Some((prefix, end)),
             Err(PrefixError::MissingLead) => None,
@@ -29,6 +38,7 @@ impl Message {
         };
 
         Ok(Message {
+            tags,
             command: Command::parse(&input).map_err(Error::CommandError)?,
             prefix: prefix.map(|(p, _)| p),
         })
@@ -71,4 +81,39 @@ mod tests {
             assert!(msg.is_ok());
         }
     }
+
+    #[test]
+    fn parse_tagged_message() {
+        let input = "@badge-info=;badges=moderator/1;color=#0000FF;display-name=museun;\
+                     id=b34ccfc7-4977-403a-8a94-33c6bac34fb8;mod=1;room-id=1337;\
+                     tmi-sent-ts=1507246572675;user-id=1337 \
+                     :museun!museun@museun.tmi.twitch.tv PRIVMSG #museun :Kappa Keepo Kappa";
+
+        let msg = Message::parse(input).unwrap();
+        assert_eq!(msg.tags.get("display-name"), Some("museun"));
+        assert_eq!(msg.tags.get("badge-info"), Some(""));
+        assert_eq!(msg.tags.msgid(), Some("b34ccfc7-4977-403a-8a94-33c6bac34fb8"));
+        assert_eq!(msg.tags.badges()[0].name, "moderator");
+        assert_eq!(msg.get_nick(), "museun");
+        assert_eq!(
+            msg.command,
+            Command::Privmsg {
+                target: "#museun".into(),
+                data: "Kappa Keepo Kappa".into(),
+                is_notice: false,
+            }
+        );
+
+        let msg = Message::parse("@msgid=abc PING :token").unwrap();
+        assert_eq!(msg.tags.msgid(), Some("abc"));
+        assert_eq!(msg.prefix, None);
+
+        let msg = Message::parse(":tmi.twitch.tv PING :token").unwrap();
+        assert!(msg.tags.is_empty());
+
+        assert_eq!(
+            Message::parse("@msgid=abc"),
+            Err(Error::Tags(TagsError::MissingTags))
+        );
+    }
 }
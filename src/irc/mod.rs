@@ -1,13 +1,18 @@
 pub mod client;
 pub mod command;
+pub mod ctcp;
 pub mod ircclient;
 pub mod message;
+pub mod format;
 pub mod prefix;
+pub mod tags;
 
 pub mod queue;
 pub use self::queue::*;
 
 mod channel;
+mod isupport;
+mod mode;
 mod state;
 
 pub use self::ircclient::IrcClient;
@@ -15,12 +20,18 @@ pub use self::ircclient::IrcClient;
 use self::channel::*;
 use self::state::*;
 
-pub use self::client::{Client, Error};
+pub use self::client::{
+    Client, Error, FloodControl, Mechanism, Registration, Sasl, Status, TlsConfig,
+};
 pub use self::command::Command;
+pub use self::isupport::{CaseMapping, ISupport};
+pub use self::channel::{Channel, Member};
+pub use self::mode::ModeChange;
 pub use self::message::Message;
 pub use self::prefix::Prefix;
 
-pub(crate) fn is_valid_nick(_nick: &str) -> bool {
+/// only the rules every server agrees on, `ISupport::is_valid_nick` knows the rest
+pub(crate) fn is_valid_nick(nick: &str) -> bool {
     /*
    No specific character set is specified. The protocol is based on a
    set of codes which are composed of eight (8) bits, making up an
@@ -38,5 +49,10 @@ pub(crate) fn is_valid_nick(_nick: &str) -> bool {
    equivalence of two nicknames or channel names.
    */
 
-    true
+    match nick.chars().next() {
+        Some(c) if c.is_ascii_digit() || c == '-' || c == ':' || c == '$' => return false,
+        None => return false,
+        _ => {}
+    }
+    !nick.contains(|c| " ,*?!@\r\n\0".contains(c))
 }
@@ -0,0 +1,92 @@
+use super::ISupport;
+
+use std::fmt;
+
+/// A single `+x arg` or `-x` out of a MODE line
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModeChange {
+    pub set: bool,
+    pub mode: char,
+    pub arg: Option<String>,
+}
+
+impl fmt::Display for ModeChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", if self.set { '+' } else { '-' }, self.mode)?;
+        if let Some(arg) = &self.arg {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+impl ModeChange {
+    /// splits `+ov-k nick nick key` into each change, the server's CHANMODES says which take an argument
+    pub fn parse(modes: &str, args: &[String], isupport: &ISupport) -> Vec<Self> {
+        let mut args = args.iter();
+        let mut set = true;
+        let mut changes = vec![];
+
+        for mode in modes.chars() {
+            match mode {
+                '+' => set = true,
+                '-' => set = false,
+                mode => {
+                    let arg = if takes_arg(mode, set, isupport) {
+                        args.next().cloned()
+                    } else {
+                        None
+                    };
+                    changes.push(ModeChange { set, mode, arg })
+                }
+            }
+        }
+        changes
+    }
+}
+
+fn takes_arg(mode: char, set: bool, isupport: &ISupport) -> bool {
+    let chanmodes = &isupport.chanmodes;
+    if isupport.prefix.iter().any(|&(m, _)| m == mode) {
+        return true;
+    }
+
+    if chanmodes.a.contains(mode) || chanmodes.b.contains(mode) {
+        true
+    } else if chanmodes.c.contains(mode) {
+        set
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(modes: &str, args: &[&str]) -> Vec<String> {
+        let args = args.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        ModeChange::parse(modes, &args, &ISupport::default())
+            .iter()
+            .map(|c| c.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!(parse("+nt", &[]), vec!["+n", "+t"]);
+        assert_eq!(
+            parse("+o-v+b", &["alice", "bob", "*!*@host"]),
+            vec!["+o alice", "-v bob", "+b *!*@host"]
+        );
+        // type C only takes an argument when it's set
+        assert_eq!(parse("+l-l", &["10"]), vec!["+l 10", "-l"]);
+        // type B always does
+        assert_eq!(parse("+k-k", &["key", "key"]), vec!["+k key", "-k key"]);
+    }
+
+    #[test]
+    fn missing_args() {
+        assert_eq!(parse("+oo", &["alice"]), vec!["+o alice", "+o"]);
+    }
+}
@@ -41,6 +41,10 @@ where
         self.queue.iter().rev().nth(n)
     }
 
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
     pub fn is_empty(&self) -> bool {
         self.queue.len() == 0
     }
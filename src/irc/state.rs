@@ -1,13 +1,19 @@
 use super::*;
 
+use std::collections::HashSet;
 use std::sync::{Arc, RwLock};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 const MAX_BACKLOG: usize = 512;
 
 struct Inner {
     nickname: Option<String>,
-    backlog: Queue<(Instant, Message)>,
+    caps: HashSet<String>,
+    isupport: ISupport,
+    lag: Option<Duration>,
+    away: bool,
+    // NICK and QUIT come with the channels they were seen in
+    backlog: Queue<(Instant, Message, Vec<String>)>,
 }
 
 pub struct State {
@@ -20,6 +26,10 @@ impl Default for State {
         Self {
             inner: RwLock::new(Inner {
                 nickname: None,
+                caps: HashSet::new(),
+                isupport: ISupport::new(),
+                lag: None,
+                away: false,
                 backlog: Queue::new(MAX_BACKLOG),
             }),
             channels: Arc::new(Channels::new()),
@@ -32,6 +42,17 @@ impl State {
         Self::default()
     }
 
+    /// forgets everything tied to the connection
+    pub(crate) fn reset(&self) {
+        let inner = &mut self.inner.write().unwrap();
+        inner.nickname = None;
+        inner.caps.clear();
+        inner.isupport = ISupport::new();
+        inner.lag = None;
+        inner.away = false;
+        self.channels.set_isupport(&inner.isupport);
+    }
+
     pub fn channels(&self) -> Arc<Channels> {
         Arc::clone(&self.channels)
     }
@@ -40,15 +61,78 @@ impl State {
         self.inner.read().unwrap().nickname.clone()
     }
 
+    /// whether `nick` is us, as far as the server is concerned
+    pub fn is_me(&self, nick: impl AsRef<str>) -> bool {
+        let inner = &self.inner.read().unwrap();
+        match &inner.nickname {
+            Some(me) => inner.isupport.eq_ignore_case(me, nick.as_ref()),
+            None => false,
+        }
+    }
+
     pub(crate) fn set_nickname(&self, nick: impl Into<String>) {
         self.inner.write().unwrap().nickname = Some(nick.into());
     }
 
-    pub fn push_message(&self, msg: (Instant, Message)) {
+    /// the capabilities the server has acknowledged
+    pub fn caps(&self) -> Vec<String> {
+        let mut caps = self
+            .inner
+            .read()
+            .unwrap()
+            .caps
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        caps.sort();
+        caps
+    }
+
+    pub fn has_cap(&self, cap: impl AsRef<str>) -> bool {
+        self.inner.read().unwrap().caps.contains(cap.as_ref())
+    }
+
+    pub(crate) fn enable_cap(&self, cap: impl Into<String>) {
+        self.inner.write().unwrap().caps.insert(cap.into());
+    }
+
+    pub(crate) fn disable_cap(&self, cap: impl AsRef<str>) {
+        self.inner.write().unwrap().caps.remove(cap.as_ref());
+    }
+
+    /// what the server advertised in 005
+    pub fn isupport(&self) -> ISupport {
+        self.inner.read().unwrap().isupport.clone()
+    }
+
+    pub(crate) fn apply_isupport(&self, token: &str) {
+        let isupport = &mut self.inner.write().unwrap().isupport;
+        isupport.apply(token);
+        self.channels.set_isupport(isupport);
+    }
+
+    /// how long the server took to answer our last PING
+    pub fn lag(&self) -> Option<Duration> {
+        self.inner.read().unwrap().lag
+    }
+
+    pub(crate) fn set_lag(&self, lag: Duration) {
+        self.inner.write().unwrap().lag = Some(lag);
+    }
+
+    pub fn is_away(&self) -> bool {
+        self.inner.read().unwrap().away
+    }
+
+    pub(crate) fn set_away(&self, away: bool) {
+        self.inner.write().unwrap().away = away;
+    }
+
+    pub fn push_message(&self, msg: (Instant, Message, Vec<String>)) {
         self.inner.write().unwrap().backlog.push(msg);
     }
 
-    pub fn next_message(&self) -> Option<(Instant, Message)> {
+    pub fn next_message(&self) -> Option<(Instant, Message, Vec<String>)> {
         self.inner.write().unwrap().backlog.pop()
     }
 }
@@ -0,0 +1,208 @@
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Error {
+    MissingLead,
+    MissingTags,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::MissingLead => write!(f, "missing lead @"),
+            Error::MissingTags => write!(f, "missing space after the tags"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Tags(HashMap<String, String>);
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Badge {
+    pub name: String,
+    pub version: String,
+}
+
+impl Tags {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(input: &str) -> Result<(Self, usize), Error> {
+        if !input.starts_with('@') {
+            return Err(Error::MissingLead);
+        }
+
+        let end = input.find(' ').ok_or(Error::MissingTags)?;
+        let map = input[1..end]
+            .split(';')
+            .filter(|s| !s.is_empty())
+            .map(|s| match s.find('=') {
+                Some(pos) => (s[..pos].to_owned(), unescape(&s[pos + 1..])),
+                None => (s.to_owned(), String::new()),
+            }).collect();
+
+        Ok((Tags(map), end))
+    }
+
+    /// valueless tags are stored as an empty string
+    pub fn get(&self, key: impl AsRef<str>) -> Option<&str> {
+        self.0.get(key.as_ref()).map(|s| s.as_str())
+    }
+
+    pub fn has(&self, key: impl AsRef<str>) -> bool {
+        self.0.contains_key(key.as_ref())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.0.iter()
+    }
+
+    pub fn server_time(&self) -> Option<DateTime<Utc>> {
+        let ts = self.get("server-time").or_else(|| self.get("time"))?;
+        DateTime::parse_from_rfc3339(ts)
+            .ok()
+            .map(|dt| dt.with_timezone(&Utc))
+    }
+
+    pub fn msgid(&self) -> Option<&str> {
+        self.get("msgid").or_else(|| self.get("id"))
+    }
+
+    pub fn account(&self) -> Option<&str> {
+        self.get("account")
+    }
+
+    /// twitch sends these as `badges=name/version,name/version`
+    pub fn badges(&self) -> Vec<Badge> {
+        let badges = match self.get("badges") {
+            Some(badges) => badges,
+            None => return vec![],
+        };
+
+        badges
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                let mut parts = s.splitn(2, '/');
+                Badge {
+                    name: parts.next().unwrap().to_owned(),
+                    version: parts.next().unwrap_or_default().to_owned(),
+                }
+            }).collect()
+    }
+}
+
+fn unescape(input: &str) -> String {
+    let mut buf = String::with_capacity(input.len());
+    let mut iter = input.chars();
+    while let Some(ch) = iter.next() {
+        if ch != '\\' {
+            buf.push(ch);
+            continue;
+        }
+
+        // a trailing backslash is dropped
+        match iter.next() {
+            Some(':') => buf.push(';'),
+            Some('s') => buf.push(' '),
+            Some('\\') => buf.push('\\'),
+            Some('r') => buf.push('\r'),
+            Some('n') => buf.push('\n'),
+            Some(ch) => buf.push(ch),
+            None => {}
+        }
+    }
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tags() {
+        let input = "@aaa=bbb;ccc;example.com/ddd=eee :nick!ident@host.com PRIVMSG me :Hello";
+        let (tags, end) = Tags::parse(input).unwrap();
+        assert_eq!(&input[end..end + 2], " :");
+        assert_eq!(tags.len(), 3);
+        assert_eq!(tags.get("aaa"), Some("bbb"));
+        assert_eq!(tags.get("ccc"), Some(""));
+        assert_eq!(tags.get("example.com/ddd"), Some("eee"));
+        assert!(!tags.has("fff"));
+
+        assert_eq!(
+            Tags::parse(":nick!ident@host.com PRIVMSG me :Hello"),
+            Err(Error::MissingLead)
+        );
+        assert_eq!(Tags::parse("@aaa=bbb"), Err(Error::MissingTags));
+    }
+
+    #[test]
+    fn unescape_values() {
+        let inputs = &[
+            (r"hello\sworld", "hello world"),
+            (r"semi\:colon", "semi;colon"),
+            (r"back\\slash", "back\\slash"),
+            (r"cr\rlf\n", "cr\rlf\n"),
+            (r"unknown\b", "unknownb"),
+            (r"trailing\", "trailing"),
+            ("", ""),
+        ];
+
+        for (input, expected) in inputs {
+            assert_eq!(unescape(input), *expected);
+        }
+    }
+
+    #[test]
+    fn well_known_tags() {
+        let input = "@account=museun;msgid=63E1033A051D4B41B1AB1FA3CF4B243E;\
+                     server-time=2011-10-19T16:40:51.620Z :nick!user@host PRIVMSG #test :hi";
+        let (tags, _) = Tags::parse(input).unwrap();
+
+        assert_eq!(tags.account(), Some("museun"));
+        assert_eq!(tags.msgid(), Some("63E1033A051D4B41B1AB1FA3CF4B243E"));
+        assert_eq!(
+            tags.server_time().map(|dt| dt.timestamp_millis()),
+            Some(1_319_042_451_620)
+        );
+    }
+
+    #[test]
+    fn twitch_badges() {
+        let input = "@badges=broadcaster/1,subscriber/12,premium/1;color=#FF69B4 :tmi.twitch.tv ";
+        let (tags, _) = Tags::parse(input).unwrap();
+        assert_eq!(
+            tags.badges(),
+            vec![
+                Badge {
+                    name: "broadcaster".into(),
+                    version: "1".into()
+                },
+                Badge {
+                    name: "subscriber".into(),
+                    version: "12".into()
+                },
+                Badge {
+                    name: "premium".into(),
+                    version: "1".into()
+                },
+            ]
+        );
+
+        let (tags, _) = Tags::parse("@badges= :tmi.twitch.tv ").unwrap();
+        assert!(tags.badges().is_empty());
+    }
+}
@@ -3,6 +3,9 @@
 extern crate log;
 extern crate chrono;
 extern crate crossbeam_channel;
+extern crate native_tls;
+extern crate regex;
+extern crate sha2;
 
 #[macro_export]
 macro_rules! import {
@@ -16,8 +19,10 @@ macro_rules! import {
 }
 
 mod config;
+mod daemon;
 mod irc;
 mod ui;
 
-pub use self::config::Config;
+pub use self::config::{socket_path, Config, Network};
+pub use self::daemon::{Daemon, Error as DaemonError};
 pub use self::ui::Gui;
@@ -36,7 +36,7 @@ where
             return None;
         }
 
-        let out = if self.data.len() == self.data.capacity() {
+        let out = if self.data.len() == self.max {
             self.data.pop_front()
         } else {
             None
@@ -1,8 +1,9 @@
+use super::colors::Color;
 use super::output::Output;
 use super::request::Request;
 use super::*;
 
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::rc::Rc;
 use std::sync::RwLock;
 
@@ -11,6 +12,29 @@ const BUFFER_MAX_SIZE: usize = 25 * 5;
 struct Inner {
     buffers: VecDeque<Rc<Buffer>>,
     active: usize,
+    // how each network compares names, from its ISUPPORT
+    casemappings: HashMap<String, irc::CaseMapping>,
+}
+
+impl Inner {
+    fn position(&self, network: Option<&str>, name: &str) -> Option<usize> {
+        let casemapping = network
+            .and_then(|network| self.casemappings.get(network).cloned())
+            .unwrap_or_default();
+        self.buffers
+            .iter()
+            .position(|b| b.is(network, name, casemapping))
+    }
+
+    // the buffer being switched away from remembers where it was read up to
+    fn activate(&mut self, pos: usize) {
+        if pos != self.active {
+            if let Some(buf) = self.buffers.get(self.active) {
+                buf.leave()
+            }
+        }
+        self.active = pos;
+    }
 }
 
 // TODO Buffers should be tied to a view state
@@ -22,32 +46,56 @@ pub struct Buffers {
 impl Buffers {
     pub fn new(queue: Rc<MessageQueue<Request>>) -> Self {
         let mut buffers = VecDeque::new();
-        buffers.push_back(Rc::new(Buffer::new("*status", BUFFER_MAX_SIZE)));
+        buffers.push_back(Rc::new(Buffer::new(None, "*status", BUFFER_MAX_SIZE)));
         Self {
-            inner: RwLock::new(Inner { buffers, active: 0 }),
+            inner: RwLock::new(Inner {
+                buffers,
+                active: 0,
+                casemappings: HashMap::new(),
+            }),
             queue,
         }
     }
 
-    pub fn create(&self, name: impl AsRef<str>, activate: bool) -> (usize, Rc<Buffer>) {
+    /// `network` is None for buffers that aren't tied to a connection
+    pub fn create(
+        &self,
+        network: Option<&str>,
+        name: impl AsRef<str>,
+        activate: bool,
+    ) -> (usize, Rc<Buffer>) {
         let name = name.as_ref();
-        trace!("creating new buffer: {}", name);
+        trace!("creating new buffer: {} ({:?})", name, network);
         let (pos, buf) = {
             let inner = &mut self.inner.write().unwrap();
-            let pos = if let Some(pos) = inner.buffers.iter().position(|b| b.name() == name) {
+            let pos = if let Some(pos) = inner.position(network, name) {
                 if activate {
                     trace!("already exists, forcing activation");
-                    inner.active = pos;
+                    inner.activate(pos);
                 }
                 pos
             } else {
-                let new = Rc::new(Buffer::new(name, BUFFER_MAX_SIZE));
-                inner.buffers.push_back(new);
+                // keep a network's buffers together, after its status buffer
+                let pos = match network {
+                    Some(_) => inner
+                        .buffers
+                        .iter()
+                        .rposition(|b| b.network() == network)
+                        .map(|p| p + 1)
+                        .unwrap_or_else(|| inner.buffers.len()),
+                    None => inner.buffers.len(),
+                };
+
+                let new = Rc::new(Buffer::new(network, name, BUFFER_MAX_SIZE));
+                inner.buffers.insert(pos, new);
+                if inner.active >= pos {
+                    inner.active += 1;
+                }
                 if activate {
                     trace!("created, forcing activation");
-                    inner.active = inner.buffers.len() - 1;
+                    inner.activate(pos);
                 }
-                inner.buffers.len() - 1
+                pos
             };
             (pos, Rc::clone(&inner.buffers[pos]))
         };
@@ -58,11 +106,22 @@ impl Buffers {
         (pos, buf)
     }
 
-    pub fn delete(&self, name: impl AsRef<str>) {
+    /// names on the network are compared with `casemapping` from now on
+    pub fn set_casemapping(&self, network: impl Into<String>, casemapping: irc::CaseMapping) {
+        let inner = &mut self.inner.write().unwrap();
+        inner.casemappings.insert(network.into(), casemapping);
+    }
+
+    /// the status buffer for the network, creating it if needed
+    pub fn status_of(&self, network: impl AsRef<str>) -> usize {
+        self.create(Some(network.as_ref()), "*status", false).0
+    }
+
+    pub fn delete(&self, network: Option<&str>, name: impl AsRef<str>) {
         {
             let inner = &mut self.inner.write().unwrap();
             let name = name.as_ref();
-            if let Some(pos) = inner.buffers.iter().position(|b| b.name() == name) {
+            if let Some(pos) = inner.position(network, name) {
                 inner.buffers.remove(pos);
                 inner.active = 0;
             } else {
@@ -73,26 +132,57 @@ impl Buffers {
         self.display();
     }
 
+    /// renames the buffer, unless there's already one called `new`
+    pub fn rename(&self, network: Option<&str>, old: &str, new: &str) -> Option<usize> {
+        let inner = &self.inner.read().unwrap();
+        let pos = inner.position(network, old)?;
+        // only changing the case is still a rename
+        match inner.position(network, new) {
+            Some(other) if other != pos => return None,
+            _ => {}
+        }
+        inner.buffers[pos].rename(new);
+        Some(pos)
+    }
+
+    /// removes all of the network's buffers, except for its status buffer
+    pub fn delete_network(&self, network: impl AsRef<str>) {
+        let network = Some(network.as_ref());
+        {
+            let inner = &mut self.inner.write().unwrap();
+            let active = Rc::clone(&inner.buffers[inner.active]);
+            inner
+                .buffers
+                .retain(|b| b.network() != network || b.is_status());
+            inner.active = inner
+                .buffers
+                .iter()
+                .position(|b| Rc::ptr_eq(b, &active))
+                .unwrap_or(0);
+        }
+        self.display();
+    }
+
     pub fn activate(&self, buf: usize) {
-        if buf > self.buffers().len() {
+        if buf >= self.len() {
             return;
         }
 
         {
             let inner = &mut self.inner.write().unwrap();
-            inner.active = buf;
+            inner.activate(buf);
         }
 
         trace!("activating buffer: {}", buf);
         self.display()
     }
 
-    pub fn activate_by_name(&self, name: impl AsRef<str>) {
+    pub fn activate_by_name(&self, network: Option<&str>, name: impl AsRef<str>) {
         let name = name.as_ref();
         {
             let inner = &mut self.inner.write().unwrap();
-            if let Some(pos) = inner.buffers.iter().position(|b| b.name() == name) {
-                inner.active = pos;
+            if let Some(pos) = inner.position(network, name) {
+                inner.activate(pos);
             }
         }
 
@@ -125,48 +215,266 @@ impl Buffers {
         buffers.get(index).map(Rc::clone)
     }
 
-    pub fn index_of(&self, name: impl AsRef<str>) -> Option<usize> {
-        let name = name.as_ref();
-        let buffers = &self.inner.read().unwrap().buffers;
-        buffers.iter().position(|b| b.name() == name)
+    pub fn index_of(&self, network: Option<&str>, name: impl AsRef<str>) -> Option<usize> {
+        self.inner.read().unwrap().position(network, name.as_ref())
     }
 
-    pub fn named(&self, name: impl AsRef<str>) -> Option<(usize, Rc<Buffer>)> {
-        let name = name.as_ref();
+    pub fn named(
+        &self,
+        network: Option<&str>,
+        name: impl AsRef<str>,
+    ) -> Option<(usize, Rc<Buffer>)> {
+        let inner = &self.inner.read().unwrap();
+        let pos = inner.position(network, name.as_ref())?;
+        Some((pos, Rc::clone(&inner.buffers[pos])))
+    }
+
+    /// indices of every buffer belonging to the network
+    pub fn of_network(&self, network: impl AsRef<str>) -> Vec<usize> {
+        let network = Some(network.as_ref());
         let buffers = &self.inner.read().unwrap().buffers;
-        let pos = buffers.iter().position(|b| b.name() == name);
-        pos.and_then(|p| buffers.get(p).map(Rc::clone).and_then(|b| Some((p, b))))
+        buffers
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.network() == network)
+            .map(|(i, _)| i)
+            .collect()
     }
 
-    fn display(&self) {
+    /// redraws the active buffer, up to where it's scrolled to
+    pub fn display(&self) {
         let (index, buffer) = self.current();
+        buffer.mark_read();
+        self.queue.enqueue(Request::RefreshStatus);
         self.queue.enqueue(Request::Clear(false));
-        for output in buffer.messages() {
+        for output in buffer.visible() {
+            self.queue.enqueue(Request::Target(index, output));
+        }
+
+        let below = buffer.scroll();
+        if below > 0 {
+            let output = Output::new()
+                .fg(Color::Yellow)
+                .add(format!("-- {} more below --", below))
+                .build();
             self.queue.enqueue(Request::Target(index, output));
         }
     }
 }
 
+/// The most important thing that happened in a buffer since it was last looked at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Activity {
+    None,
+    Message,
+    Highlight,
+}
+
 pub struct Buffer {
-    name: String,
+    // queries follow the other side's nick
+    name: RwLock<String>,
+    network: Option<String>,
     messages: RwLock<irc::Queue<Output>>,
+    // how many messages are hidden below the view
+    scroll: RwLock<usize>,
+    search: RwLock<Option<String>>,
+    unread: RwLock<Unread>,
+    // show messages without their mirc formatting
+    strip: RwLock<bool>,
+}
+
+#[derive(Default)]
+struct Unread {
+    messages: usize,
+    highlights: usize,
+    // every message ever pushed, so the marker survives old ones being dropped
+    pushed: usize,
+    // `pushed` when the buffer was last switched away from
+    marker: usize,
 }
 
 impl Buffer {
-    pub fn new(name: impl Into<String>, max: usize) -> Self {
+    pub fn new(network: Option<&str>, name: impl Into<String>, max: usize) -> Self {
         Self {
-            name: name.into(),
+            name: RwLock::new(name.into()),
+            network: network.map(|s| s.to_string()),
             messages: RwLock::new(irc::Queue::new(max)),
+            scroll: RwLock::new(0),
+            search: RwLock::new(None),
+            unread: RwLock::new(Unread::default()),
+            strip: RwLock::new(false),
         }
     }
 
-    pub fn name(&self) -> &str {
-        &self.name
+    pub fn name(&self) -> String {
+        self.name.read().unwrap().clone()
+    }
+
+    fn rename(&self, name: impl Into<String>) {
+        *self.name.write().unwrap() = name.into();
+    }
+
+    pub fn network(&self) -> Option<&str> {
+        self.network.as_deref()
+    }
+
+    /// `network/name`, or just the name if it isn't tied to a network
+    pub fn label(&self) -> String {
+        match &self.network {
+            Some(network) => format!("{}/{}", network, self.name()),
+            None => self.name(),
+        }
+    }
+
+    fn is(&self, network: Option<&str>, name: &str, casemapping: irc::CaseMapping) -> bool {
+        self.network() == network && casemapping.eq_ignore_case(&self.name.read().unwrap(), name)
     }
 
     pub fn push_message(&self, msg: &Output) {
         trace!("{} <- {}", self.name(), msg.data);
         self.messages.write().unwrap().push(msg.clone());
+        self.unread.write().unwrap().pushed += 1;
+
+        // keep the view where it is if it's been scrolled up
+        let scroll = self.scroll();
+        if scroll > 0 {
+            self.scroll_to(scroll + 1)
+        }
+    }
+
+    pub fn activity(&self) -> Activity {
+        let unread = &self.unread.read().unwrap();
+        if unread.highlights > 0 {
+            Activity::Highlight
+        } else if unread.messages > 0 {
+            Activity::Message
+        } else {
+            Activity::None
+        }
+    }
+
+    pub fn unread(&self) -> usize {
+        self.unread.read().unwrap().messages
+    }
+
+    pub fn highlights(&self) -> usize {
+        self.unread.read().unwrap().highlights
+    }
+
+    pub fn mark_unread(&self) {
+        self.unread.write().unwrap().messages += 1;
+    }
+
+    pub fn mark_highlight(&self) {
+        self.unread.write().unwrap().highlights += 1;
+    }
+
+    pub fn mark_read(&self) {
+        let unread = &mut self.unread.write().unwrap();
+        unread.messages = 0;
+        unread.highlights = 0;
+    }
+
+    /// moves the unread marker to the bottom
+    fn leave(&self) {
+        let unread = &mut self.unread.write().unwrap();
+        unread.marker = unread.pushed;
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.read().unwrap().is_empty()
+    }
+
+    pub fn scroll(&self) -> usize {
+        *self.scroll.read().unwrap()
+    }
+
+    /// hides `below` messages under the view, atleast one message is always shown
+    pub fn scroll_to(&self, below: usize) {
+        let max = self.len().saturating_sub(1);
+        *self.scroll.write().unwrap() = ::std::cmp::min(below, max);
+    }
+
+    /// highlights `query` and scrolls up to the closest match,
+    /// starting from the bottom of the view. `None` stops highlighting
+    pub fn search(&self, query: Option<String>) {
+        if let Some(query) = &query {
+            let bottom = self.len().saturating_sub(self.scroll() + 1);
+            self.scroll_to_match(query, bottom);
+        }
+        *self.search.write().unwrap() = query;
+    }
+
+    /// scrolls up to the next match, past the one at the bottom of the view.
+    /// after the oldest match it starts over from the newest one
+    pub fn search_next(&self) {
+        let query = match self.search.read().unwrap().clone() {
+            Some(query) => query,
+            None => return,
+        };
+
+        let bottom = self.len().saturating_sub(self.scroll() + 1);
+        if bottom == 0 || !self.scroll_to_match(&query, bottom - 1) {
+            self.scroll_to_match(&query, self.len().saturating_sub(1));
+        }
+    }
+
+    /// the messages down to the bottom of the view, with any search highlighted
+    /// and a line where the unread messages start
+    pub fn visible(&self) -> Vec<Output> {
+        let messages = &self.messages.read().unwrap();
+        let shown = messages.len().saturating_sub(self.scroll());
+        let mut visible = messages
+            .iter()
+            .take(shown)
+            .map(|msg| self.highlight(msg))
+            .collect::<Vec<_>>();
+
+        let marker = {
+            let unread = &self.unread.read().unwrap();
+            messages.len().checked_sub(unread.pushed - unread.marker)
+        };
+        match marker {
+            Some(pos) if pos > 0 && pos < visible.len() => {
+                let line = Output::new().fg(Color::Red).add("-- unread --").build();
+                visible.insert(pos, line)
+            }
+            _ => {}
+        }
+        visible
+    }
+
+    pub fn highlight(&self, msg: &Output) -> Output {
+        match &*self.search.read().unwrap() {
+            Some(query) => msg.highlight(query),
+            None => msg.clone(),
+        }
+    }
+
+    // makes the newest match at or above `from` the bottom of the view
+    fn scroll_to_match(&self, query: &str, from: usize) -> bool {
+        let len = self.len();
+        let pos = {
+            let messages = &self.messages.read().unwrap();
+            messages
+                .iter()
+                .take(from + 1)
+                .enumerate()
+                .filter(|(_, msg)| msg.contains(query))
+                .map(|(pos, _)| pos)
+                .last()
+        };
+        match pos {
+            Some(pos) => {
+                self.scroll_to(len - 1 - pos);
+                true
+            }
+            None => false,
+        }
     }
 
     pub fn most_recent(&self) -> Option<Output> {
@@ -175,6 +483,10 @@ impl Buffer {
 
     pub fn clear(&self) {
         self.messages.write().unwrap().clear();
+        *self.scroll.write().unwrap() = 0;
+
+        let unread = &mut self.unread.write().unwrap();
+        unread.marker = unread.pushed;
     }
 
     /// this copies all of the messages
@@ -183,7 +495,145 @@ impl Buffer {
         messages.iter().cloned().collect()
     }
 
+    pub fn strips_formatting(&self) -> bool {
+        *self.strip.read().unwrap()
+    }
+
+    pub fn set_strip_formatting(&self, strip: bool) {
+        *self.strip.write().unwrap() = strip;
+    }
+
     pub fn is_status(&self) -> bool {
-        self.name.starts_with('*')
+        self.name.read().unwrap().starts_with('*')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(lines: &[&str]) -> Buffer {
+        let buf = Buffer::new(None, "#test", 10);
+        for line in lines {
+            buf.push_message(&Output::from(*line));
+        }
+        buf
+    }
+
+    #[test]
+    fn search() {
+        let buf = buffer(&["Hello there", "nothing", "oh, hello", "bye", "nope"]);
+
+        // the newest match, ignoring case
+        buf.search(Some("HELLO".into()));
+        assert_eq!(buf.scroll(), 2);
+
+        buf.search_next();
+        assert_eq!(buf.scroll(), 4);
+
+        // back around to the newest one
+        buf.search_next();
+        assert_eq!(buf.scroll(), 2);
+
+        buf.search(Some("missing".into()));
+        assert_eq!(buf.scroll(), 2);
+
+        buf.search(None);
+        buf.search_next();
+        assert_eq!(buf.scroll(), 2);
+    }
+
+    #[test]
+    fn search_at_the_ends() {
+        let buf = buffer(&["match", "a", "b", "match"]);
+
+        // the one already at the bottom counts
+        buf.search(Some("match".into()));
+        assert_eq!(buf.scroll(), 0);
+
+        buf.search_next();
+        assert_eq!(buf.scroll(), 3);
+        buf.search_next();
+        assert_eq!(buf.scroll(), 0);
+
+        // a single match stays put
+        let buf = buffer(&["a", "match", "b"]);
+        buf.search(Some("match".into()));
+        assert_eq!(buf.scroll(), 1);
+        buf.search_next();
+        assert_eq!(buf.scroll(), 1);
+    }
+
+    #[test]
+    fn queries() {
+        let buffers = Buffers::new(Rc::new(MessageQueue::new()));
+        let net = Some("net");
+        let (pos, _) = buffers.create(net, "Bob[m]", false);
+
+        // names are the same under the network's casemapping
+        assert_eq!(buffers.create(net, "bob{M}", false).0, pos);
+        assert_eq!(buffers.index_of(net, "BOB[M]"), Some(pos));
+        assert_eq!(buffers.index_of(None, "bob[m]"), None);
+
+        buffers.set_casemapping("net", irc::CaseMapping::Ascii);
+        assert_eq!(buffers.index_of(net, "BOB[M]"), Some(pos));
+        assert_eq!(buffers.index_of(net, "bob{m}"), None);
+        buffers.set_casemapping("net", irc::CaseMapping::Rfc1459);
+
+        // a query follows the nick, case changes and all
+        assert_eq!(buffers.rename(net, "bob{m}", "Robert"), Some(pos));
+        assert_eq!(buffers.get(pos).unwrap().name(), "Robert");
+        assert_eq!(buffers.rename(net, "robert", "ROBERT"), Some(pos));
+        assert_eq!(buffers.get(pos).unwrap().name(), "ROBERT");
+
+        // but not onto another buffer
+        let (alice, _) = buffers.create(net, "alice", false);
+        assert_eq!(buffers.rename(net, "robert", "Alice"), None);
+        assert_eq!(buffers.get(alice).unwrap().name(), "alice");
+        assert_eq!(buffers.rename(net, "carol", "dave"), None);
+    }
+
+    #[test]
+    fn unread_marker() {
+        let marker = |buf: &Buffer| {
+            buf.visible()
+                .iter()
+                .position(|line| line.data == "-- unread --")
+        };
+
+        let buf = buffer(&["a", "b"]);
+        assert_eq!(marker(&buf), None);
+
+        buf.leave();
+        assert_eq!(marker(&buf), None);
+        buf.push_message(&Output::from("c"));
+        assert_eq!(marker(&buf), Some(2));
+
+        // coming back and leaving again moves it down
+        buf.leave();
+        assert_eq!(marker(&buf), None);
+        buf.push_message(&Output::from("d"));
+        assert_eq!(marker(&buf), Some(3));
+
+        buf.clear();
+        assert_eq!(marker(&buf), None);
+        buf.push_message(&Output::from("e"));
+        assert_eq!(marker(&buf), None);
+    }
+
+    #[test]
+    fn search_highlights() {
+        let buf = buffer(&["foo bar FOO"]);
+        buf.search(Some("foo".into()));
+
+        let visible = buf.visible();
+        assert_eq!(visible.len(), 1);
+        let reversed = visible[0]
+            .colors
+            .iter()
+            .filter(|(_, color)| color.reverse)
+            .map(|(range, _)| range.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(reversed, vec![0..3, 8..11]);
     }
 }
@@ -1,9 +1,15 @@
+use super::irc;
+use std::collections::HashMap;
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct ColorPair {
     pub bold: bool,
+    pub italic: bool,
     pub underline: bool,
+    pub reverse: bool,
     pub fg: Color,
-    pub bg: Color,
+    /// `None` is the terminal's own background
+    pub bg: Option<Color>,
 }
 
 impl ColorPair {
@@ -13,12 +19,11 @@ impl ColorPair {
     {
         ColorPair {
             bold,
+            italic: false,
             underline: false,
+            reverse: false,
             fg: fg.into(),
-            bg: bg
-                .map(|bg| bg.into())
-                .or_else(|| Some(0i16.into()))
-                .unwrap(),
+            bg: bg.map(|bg| bg.into()),
         }
     }
 
@@ -26,6 +31,245 @@ impl ColorPair {
         self.bold = !self.bold;
         *self
     }
+
+    /// a color name, with any of `bold`, `italic` or `underline` before it
+    pub fn parse(input: &str) -> Option<Self> {
+        let mut pair = ColorPair::new(false, Color::White, None);
+        for word in input.split_whitespace() {
+            match word {
+                "bold" => pair.bold = true,
+                "italic" => pair.italic = true,
+                "underline" => pair.underline = true,
+                color => pair.fg = Color::parse(color)?,
+            }
+        }
+        Some(pair)
+    }
+
+    /// this with a run of formatted text's style on top
+    pub fn styled(self, style: &irc::format::Style) -> Self {
+        ColorPair {
+            bold: self.bold || style.bold,
+            italic: self.italic || style.italic,
+            underline: self.underline || style.underline,
+            reverse: self.reverse || style.reverse,
+            fg: style.fg.map_or(self.fg, Color::from),
+            bg: style.bg.map(Color::from).or(self.bg),
+        }
+    }
+}
+
+// mirc's first 16 colors, the rest are converted from what they look like
+const MIRC: [Color; 16] = [
+    Color::BrightWhite,
+    Color::Black,
+    Color::Blue,
+    Color::Green,
+    Color::BrightRed,
+    Color::Red,
+    Color::Magenta,
+    Color::Yellow,
+    Color::BrightYellow,
+    Color::BrightGreen,
+    Color::Cyan,
+    Color::BrightCyan,
+    Color::BrightBlue,
+    Color::BrightMagenta,
+    Color::BrightBlack,
+    Color::White,
+];
+
+impl From<irc::format::Color> for Color {
+    fn from(c: irc::format::Color) -> Self {
+        match c {
+            irc::format::Color::Palette(n) if (n as usize) < MIRC.len() => MIRC[n as usize],
+            c => {
+                let (r, g, b) = c.rgb();
+                Color::Rgb(r, g, b)
+            }
+        }
+    }
+}
+
+// xterm's defaults for the first 16, terminals are free to change these
+#[rustfmt::skip]
+const XTERM: [u32; 16] = [
+    0x000000, 0xCD0000, 0x00CD00, 0xCDCD00, 0x0000EE, 0xCD00CD, 0x00CDCD, 0xE5E5E5,
+    0x7F7F7F, 0xFF0000, 0x00FF00, 0xFFFF00, 0x5C5CFF, 0xFF00FF, 0x00FFFF, 0xFFFFFF,
+];
+
+// the steps of the 6x6x6 cube that makes up 16 to 231
+const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// what an indexed color usually looks like
+fn indexed_rgb(n: u8) -> (u8, u8, u8) {
+    match n {
+        0..=15 => {
+            let c = XTERM[n as usize];
+            ((c >> 16) as u8, (c >> 8) as u8, c as u8)
+        }
+        16..=231 => {
+            let n = (n - 16) as usize;
+            (LEVELS[n / 36], LEVELS[(n / 6) % 6], LEVELS[n % 6])
+        }
+        n => {
+            let grey = 8 + (n - 232) * 10;
+            (grey, grey, grey)
+        }
+    }
+}
+
+fn distance((r, g, b): (u8, u8, u8), (or, og, ob): (u8, u8, u8)) -> i32 {
+    let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+    d(r, or) + d(g, og) + d(b, ob)
+}
+
+/// the closest color out of the cube and the greys, which look the same everywhere
+fn nearest_indexed(rgb: (u8, u8, u8)) -> u8 {
+    let level = |c: u8| {
+        (0..LEVELS.len())
+            .min_by_key(|&i| (i32::from(LEVELS[i]) - i32::from(c)).abs())
+            .unwrap() as u8
+    };
+    let (r, g, b) = rgb;
+    let cube = 16 + 36 * level(r) + 6 * level(g) + level(b);
+
+    let average = (u32::from(r) + u32::from(g) + u32::from(b)) / 3;
+    let grey = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+
+    if distance(rgb, indexed_rgb(grey)) < distance(rgb, indexed_rgb(cube)) {
+        grey
+    } else {
+        cube
+    }
+}
+
+/// Hands out curses color pairs as they're needed, rather than setting them all up front.
+/// When they run out, the one that was used the longest ago is reused
+pub struct Palette {
+    colors: i32,
+    // whether colors past the first 256 can be defined, for rgb colors
+    custom: bool,
+    pairs: Pairs,
+    defined: HashMap<(u8, u8, u8), i16>,
+}
+
+// which pair each (fg, bg) is in
+struct Pairs {
+    max: i32,
+    slots: HashMap<(i16, i16), Slot>,
+    tick: u64,
+}
+
+struct Slot {
+    pair: i16,
+    used: u64,
+}
+
+impl Pairs {
+    fn new(max: i32) -> Self {
+        Self {
+            max,
+            slots: HashMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// the pair for the colors, and whether it still has to be set up
+    fn get(&mut self, colors: (i16, i16)) -> (i16, bool) {
+        self.tick += 1;
+        if let Some(slot) = self.slots.get_mut(&colors) {
+            slot.used = self.tick;
+            return (slot.pair, false);
+        }
+
+        let pair = if (self.slots.len() as i32) < self.max - 1 {
+            self.slots.len() as i16 + 1
+        } else {
+            let oldest = self
+                .slots
+                .iter()
+                .min_by_key(|(_, slot)| slot.used)
+                .map(|(key, _)| *key)
+                .unwrap();
+            self.slots.remove(&oldest).unwrap().pair
+        };
+
+        let used = self.tick;
+        self.slots.insert(colors, Slot { pair, used });
+        (pair, true)
+    }
+}
+
+impl Palette {
+    /// curses has to have started its colors already
+    pub fn new() -> Self {
+        let colors = pancurses::COLORS();
+        let max_pairs = ::std::cmp::min(pancurses::COLOR_PAIRS(), i32::from(i16::MAX));
+        Self {
+            colors,
+            custom: pancurses::can_change_color() && colors > 256,
+            pairs: Pairs::new(max_pairs),
+            defined: HashMap::new(),
+        }
+    }
+
+    /// the pair to draw `color` with, setting one up if needed
+    pub fn pair(&mut self, color: &ColorPair) -> i16 {
+        // pair 0 is the terminal's own colors, and can't be changed
+        if self.pairs.max < 2 {
+            return 0;
+        }
+
+        let fg = self.color(color.fg);
+        let bg = color.bg.map_or(-1, |bg| self.color(bg));
+
+        let (pair, new) = self.pairs.get((fg, bg));
+        if new {
+            pancurses::init_pair(pair, fg, bg);
+        }
+        pair
+    }
+
+    /// the curses color for `color`, or the closest one the terminal has
+    fn color(&mut self, color: Color) -> i16 {
+        let rgb = match color {
+            Color::Rgb(r, g, b) => (r, g, b),
+            color => {
+                let n = i16::from(color);
+                if i32::from(n) < self.colors {
+                    return n;
+                }
+                indexed_rgb(n as u8)
+            }
+        };
+
+        if self.custom {
+            if let Some(&n) = self.defined.get(&rgb) {
+                return n;
+            }
+
+            let n = 256 + self.defined.len() as i32;
+            if n < self.colors && n <= i32::from(i16::MAX) {
+                // curses wants each part out of 1000
+                let scale = |c: u8| (i32::from(c) * 1000 / 255) as i16;
+                pancurses::init_color(n as i16, scale(rgb.0), scale(rgb.1), scale(rgb.2));
+                self.defined.insert(rgb, n as i16);
+                return n as i16;
+            }
+        }
+
+        if self.colors >= 256 {
+            return i16::from(nearest_indexed(rgb));
+        }
+
+        // only the basic colors, the brights might not even be there
+        let basic = ::std::cmp::min(self.colors, 16).max(1) as u8;
+        (0..basic)
+            .min_by_key(|&n| distance(rgb, indexed_rgb(n)))
+            .map(i16::from)
+            .unwrap_or(-1)
+    }
 }
 
 impl From<Color> for i16 {
@@ -48,6 +292,9 @@ impl From<Color> for i16 {
             Color::BrightRed => pancurses::COLOR_RED + 8,
             Color::BrightWhite => pancurses::COLOR_WHITE + 8,
             Color::BrightYellow => pancurses::COLOR_YELLOW + 8,
+
+            Color::Indexed(n) => i16::from(n),
+            Color::Rgb(r, g, b) => i16::from(nearest_indexed((r, g, b))),
         }
     }
 }
@@ -73,12 +320,48 @@ impl From<i16> for Color {
                 pancurses::COLOR_RED => Color::BrightRed,
                 pancurses::COLOR_WHITE => Color::BrightWhite,
                 pancurses::COLOR_YELLOW => Color::BrightYellow,
-                _ => unreachable!(),
+                _ => Color::Indexed(c as u8),
             },
         }
     }
 }
 
+impl Color {
+    /// `bright_red`, a number for one of the 256 colors, or `#rrggbb`
+    pub fn parse(input: &str) -> Option<Self> {
+        use self::Color::*;
+
+        if input.starts_with('#') && input.len() == 7 {
+            let c = u32::from_str_radix(&input[1..], 16).ok()?;
+            return Some(Rgb((c >> 16) as u8, (c >> 8) as u8, c as u8));
+        }
+        if let Ok(n) = input.parse() {
+            return Some(Indexed(n));
+        }
+
+        let color = match input {
+            "black" => Black,
+            "blue" => Blue,
+            "green" => Green,
+            "cyan" => Cyan,
+            "red" => Red,
+            "magenta" => Magenta,
+            "yellow" => Yellow,
+            "white" => White,
+            "bright_black" => BrightBlack,
+            "bright_blue" => BrightBlue,
+            "bright_green" => BrightGreen,
+            "bright_cyan" => BrightCyan,
+            "bright_red" => BrightRed,
+            "bright_magenta" => BrightMagenta,
+            "bright_yellow" => BrightYellow,
+            "bright_white" => BrightWhite,
+            _ => return None,
+        };
+        Some(color)
+    }
+}
+
 impl From<Color> for ColorPair {
     fn from(ck: Color) -> ColorPair {
         ColorPair::new(false, ck, None)
@@ -104,4 +387,59 @@ pub enum Color {
     BrightMagenta,
     BrightYellow,
     BrightWhite,
+
+    /// one of the terminal's 256 colors
+    Indexed(u8),
+    /// the closest indexed color is used if the terminal can't show it
+    Rgb(u8, u8, u8),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexed() {
+        assert_eq!(indexed_rgb(9), (0xFF, 0, 0));
+        assert_eq!(indexed_rgb(16), (0, 0, 0));
+        assert_eq!(indexed_rgb(196), (255, 0, 0));
+        assert_eq!(indexed_rgb(67), (95, 135, 175));
+        assert_eq!(indexed_rgb(231), (255, 255, 255));
+        assert_eq!(indexed_rgb(232), (8, 8, 8));
+        assert_eq!(indexed_rgb(255), (238, 238, 238));
+    }
+
+    #[test]
+    fn nearest() {
+        // every color in the cube finds itself
+        for n in 16..=231 {
+            assert_eq!(nearest_indexed(indexed_rgb(n)), n);
+        }
+        // and so do the greys
+        for n in 232..=255 {
+            assert_eq!(nearest_indexed(indexed_rgb(n)), n);
+        }
+
+        assert_eq!(nearest_indexed((250, 5, 5)), 196);
+        assert_eq!(nearest_indexed((100, 101, 99)), 241);
+        assert_eq!(nearest_indexed((0, 0, 0)), 16);
+        assert_eq!(nearest_indexed((255, 255, 255)), 231);
+    }
+
+    #[test]
+    fn pairs() {
+        // pair 0 is taken, so this has room for 3
+        let mut pairs = Pairs::new(4);
+        assert_eq!(pairs.get((1, -1)), (1, true));
+        assert_eq!(pairs.get((2, -1)), (2, true));
+        assert_eq!(pairs.get((3, -1)), (3, true));
+        assert_eq!(pairs.get((1, -1)), (1, false));
+
+        // (2, -1) was used the longest ago
+        assert_eq!(pairs.get((4, -1)), (2, true));
+        assert_eq!(pairs.get((1, -1)), (1, false));
+        assert_eq!(pairs.get((5, 0)), (3, true));
+        assert_eq!(pairs.get((2, -1)), (2, true));
+        assert_eq!(pairs.slots.len(), 3);
+    }
 }
@@ -0,0 +1,23 @@
+use super::*;
+
+pub(crate) fn close_command(ctx: &Context) -> CommandResult {
+    let (_, buf) = ctx.state.buffers().current();
+    let network = match buf.network() {
+        Some(network) if !buf.is_status() => network.to_owned(),
+        // TODO get rid of this string
+        _ => return Err(Error::InvalidBuffer("cannot /close a *window".into())),
+    };
+
+    // closing a channel leaves it
+    let name = buf.name();
+    let is_channel = ctx
+        .state
+        .client_for(&network)
+        .is_some_and(|client| client.state().isupport().is_channel(&name));
+    if is_channel {
+        ctx.request(Request::Part(network, name));
+    } else {
+        ctx.request(Request::Close(network, name));
+    }
+    Ok(Response::Nothing)
+}
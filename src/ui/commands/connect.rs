@@ -1,28 +1,112 @@
 use super::*;
 
 pub(crate) fn connect_command(ctx: &Context) -> CommandResult {
-    use super::irc::IrcClient;
+    let config = &ctx.config.borrow();
+
+    // try: /connect [network | ircs://host:port]
+    let network = match ctx.parts.first() {
+        Some(name) => match config.network(name) {
+            Some(network) => network.clone(),
+            None => ad_hoc(config, name)
+                .ok_or_else(|| Error::InvalidArgument("try: /connect ircs://host:port".into()))?,
+        },
+        None if config.networks.len() == 1 => config.networks[0].clone(),
+        None => {
+            let names = config
+                .networks
+                .iter()
+                .map(|n| n.name.as_str())
+                .collect::<Vec<_>>();
+            Err(Error::InvalidArgument(format!(
+                "try: /connect <{}>",
+                names.join("|")
+            )))?
+        }
+    };
 
-    if ctx.state.client().is_some() {
+    if ctx.state.client_for(&network.name).is_some() {
         Err(Error::AlreadyConnected)?;
     };
 
-    let config = &ctx.config.borrow();
+    let (server, tls) = network
+        .address()
+        .ok_or_else(|| Error::InvalidArgument(format!("invalid server for {}", network.name)))?;
+    let reg = network.registration().map_err(Error::InvalidArgument)?;
 
-    let output = Output::new()
+    let output = ctx
+        .state
+        .theme()
+        .stamp()
         .fg(Color::Green)
         .add("connecting to ")
         .fg(Color::Cyan)
-        .add(&config.server)
+        .add(&server)
+        .add(if tls.is_some() { " (tls)" } else { "" })
         .build();
 
-    let client = irc::Client::connect(config.server.clone()).map_err(Error::ClientError)?;
-    if !&config.pass.is_empty() {
-        client.pass(&config.pass)
-    }
-    client.nick(&config.nick);
-    client.user(&config.user, &config.real);
-    ctx.state.set_client(client);
+    let client = irc::Client::connect(server, tls, reg, network.flood_control())
+        .map_err(Error::ClientError)?;
+    ctx.state.set_client(network.name.clone(), client);
+    ctx.state
+        .buffers()
+        .create(Some(&network.name), "*status", true);
 
     Ok(Response::Output(output))
 }
+
+// an address borrows the identity of the first network, but none of its secrets
+fn ad_hoc(config: &Config, address: &str) -> Option<config::Network> {
+    let (server, tls) = config::parse_address(address, false)?;
+    let mut network = config::Network::default();
+    if let Some(first) = config.networks.first() {
+        network.nick = first.nick.clone();
+        network.user = first.user.clone();
+        network.real = first.real.clone();
+    }
+    network.name = server.clone();
+    network.server = server;
+    network.tls = tls;
+    Some(network)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ad_hoc_address() {
+        let mut config = Config::default();
+        config.networks = vec![config::Network {
+            name: "home".into(),
+            server: "irc.home.net:6697".into(),
+            nick: "me".into(),
+            user: "me".into(),
+            real: "just me".into(),
+            pass: "secret".into(),
+            sasl_mechanism: "PLAIN".into(),
+            sasl_user: "me".into(),
+            sasl_pass: "hunter2".into(),
+            tls: true,
+            tls_fingerprint: "ab:cd".into(),
+            tls_ca: "/etc/ca.pem".into(),
+            ..config::Network::default()
+        }];
+
+        let network = ad_hoc(&config, "ircs://irc.elsewhere.net").unwrap();
+        assert_eq!(network.server, "irc.elsewhere.net:6697");
+        assert!(network.tls);
+        assert_eq!(
+            (network.nick.as_str(), network.real.as_str()),
+            ("me", "just me")
+        );
+
+        assert!(network.pass.is_empty());
+        assert!(network.sasl_mechanism.is_empty());
+        assert!(network.sasl_user.is_empty());
+        assert!(network.sasl_pass.is_empty());
+        assert!(network.tls_fingerprint.is_empty());
+        assert!(network.tls_ca.is_empty());
+
+        assert!(ad_hoc(&config, "ircs://").is_none());
+    }
+}
@@ -0,0 +1,28 @@
+use super::*;
+use chrono::Utc;
+
+pub(crate) fn ctcp_command(ctx: &Context) -> CommandResult {
+    client_command(ctx, ctcp_request)
+}
+
+pub(crate) fn ctcp_request(network: String, _buffer: &str, parts: &[&str]) -> RequestResult {
+    if parts.len() < 2 {
+        Err(Error::InvalidArgument(
+            "try: /ctcp <target> <command> [params]".into(),
+        ))?;
+    }
+
+    // the reply to a PING says how long it took, from the time it was sent
+    let command = parts[1];
+    let mut params = parts[2..].join(" ");
+    if params.is_empty() && command.eq_ignore_ascii_case("ping") {
+        params = Utc::now().timestamp_millis().to_string();
+    }
+
+    let ctcp = irc::ctcp::Ctcp::new(command, params);
+    Ok(Request::Privmsg(
+        network,
+        parts[0].to_owned(),
+        ctcp.encode(),
+    ))
+}
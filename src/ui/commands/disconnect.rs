@@ -0,0 +1,15 @@
+use super::*;
+
+pub(crate) fn disconnect_command(ctx: &Context) -> CommandResult {
+    let network = match ctx.parts.first() {
+        Some(network) => network.to_string(),
+        None => ctx.state.network().ok_or(Error::NotConnected)?,
+    };
+
+    if ctx.state.client_for(&network).is_none() {
+        Err(Error::NotConnected)?;
+    }
+
+    ctx.request(Request::Disconnect(network));
+    Ok(Response::Nothing)
+}
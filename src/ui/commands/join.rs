@@ -1,10 +1,18 @@
 use super::*;
 
 pub(crate) fn join_command(ctx: &Context) -> CommandResult {
-    assume_connected(&ctx)?;
-    assume_args(&ctx, "try: /join <chan>")?;
+    client_command(ctx, join_request)
+}
+
+pub(crate) fn join_request(network: String, _buffer: &str, parts: &[&str]) -> RequestResult {
+    if parts.is_empty() {
+        Err(Error::InvalidArgument("try: /join <chan> [key]".into()))?;
+    }
 
-    // TODO make this actually work on multiple channels + keys
-    ctx.request(Request::Join(ctx.parts[0].to_owned()));
-    Ok(Response::Nothing)
+    // TODO make this actually work on multiple channels
+    Ok(Request::Join(
+        network,
+        parts[0].to_owned(),
+        parts.get(1).map(|s| s.to_string()),
+    ))
 }
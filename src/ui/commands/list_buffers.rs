@@ -12,7 +12,7 @@ pub(crate) fn list_buffers_command(ctx: &Context) -> CommandResult {
             .fg(Color::BrightWhite)
             .add(format!("{}", n))
             .fg(Color::Cyan)
-            .add(buffer.name());
+            .add(buffer.label());
 
         if n < len {
             output.add(",");
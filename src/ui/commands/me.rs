@@ -0,0 +1,21 @@
+use super::*;
+
+pub(crate) fn me_command(ctx: &Context) -> CommandResult {
+    client_command(ctx, me_request)
+}
+
+pub(crate) fn me_request(network: String, buffer: &str, parts: &[&str]) -> RequestResult {
+    if parts.is_empty() {
+        Err(Error::InvalidArgument("try: /me <action>".into()))?;
+    }
+    if buffer.starts_with('*') {
+        Err(Error::InvalidBuffer(buffer.to_string()))?;
+    }
+
+    let action = irc::ctcp::Ctcp::new("ACTION", parts.join(" "));
+    Ok(Request::Privmsg(
+        network,
+        buffer.to_string(),
+        action.encode(),
+    ))
+}
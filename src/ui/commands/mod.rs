@@ -9,16 +9,38 @@ import!(
     buffer,
     clear,
     clear_history,
+    close,
     connect,
+    ctcp,
+    disconnect,
     echo,
     exit,
     join,
     list_buffers,
+    me,
+    msg,
     part,
+    query,
     quit,
-    rehash
+    rehash,
+    search,
+    strip
 );
 
+// these only touch the ui, so they're handled locally when attached to a daemon
+const LOCAL_COMMANDS: &[&str] = &[
+    "/echo",
+    "/exit",
+    "/clear",
+    "/buffer",
+    "/buffers",
+    "/bind",
+    "/rehash",
+    "/clearhistory",
+    "/search",
+    "/strip",
+];
+
 #[derive(Debug, PartialEq)]
 pub enum Error {
     InvalidArgument(String),
@@ -43,6 +65,33 @@ pub enum Response {
 type CommandResult = Result<Response, Error>;
 type Command = fn(&Context) -> CommandResult;
 
+type RequestResult = Result<Request, Error>;
+type ClientCommand = fn(String, &str, &[&str]) -> RequestResult; // network, buffer, args
+
+// these only make a request of the client, so the daemon runs them too
+const CLIENT_COMMANDS: &[(&str, ClientCommand)] = &[
+    ("/join", join_request),
+    ("/part", part_request),
+    ("/quit", quit_request),
+    ("/msg", msg_request),
+    ("/me", me_request),
+    ("/ctcp", ctcp_request),
+];
+
+/// the request `input` makes of the client for `network`, typed into `buffer`
+///
+/// none if it isn't one of the client commands
+pub(crate) fn client_request(network: &str, buffer: &str, input: &str) -> Option<RequestResult> {
+    let mut parts = input.split(' ');
+    let query = parts.next().unwrap_or_default();
+    let parts = parts.collect::<Vec<_>>();
+
+    CLIENT_COMMANDS
+        .iter()
+        .find(|(name, _)| *name == query)
+        .map(|(_, func)| func(network.to_string(), buffer, &parts))
+}
+
 pub(crate) struct Context<'a> {
     pub(crate) state: Rc<State>,
     pub(crate) queue: Rc<MessageQueue<Request>>,
@@ -77,15 +126,23 @@ impl CommandProcessor {
         this.map.insert("/echo", echo_command);
         this.map.insert("/exit", exit_command);
         this.map.insert("/connect", connect_command);
+        this.map.insert("/disconnect", disconnect_command);
         this.map.insert("/quit", quit_command);
         this.map.insert("/clear", clear_command);
         this.map.insert("/join", join_command);
         this.map.insert("/part", part_command);
+        this.map.insert("/msg", msg_command);
+        this.map.insert("/me", me_command);
+        this.map.insert("/ctcp", ctcp_command);
+        this.map.insert("/query", query_command);
+        this.map.insert("/close", close_command);
         this.map.insert("/buffer", buffer_command);
         this.map.insert("/buffers", list_buffers_command);
         this.map.insert("/bind", bind_command);
         this.map.insert("/rehash", rehash_command);
         this.map.insert("/clearhistory", clear_history_command);
+        this.map.insert("/search", search_command);
+        this.map.insert("/strip", strip_command);
         this
     }
 
@@ -119,33 +176,62 @@ impl CommandProcessor {
         func(&ctx)
     }
 
+    /// every command, sorted
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names = self.map.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    pub fn is_local(&self, input: &str) -> bool {
+        let query = input.split(' ').next().unwrap_or_default();
+        LOCAL_COMMANDS.contains(&query)
+    }
+
     fn try_send_message(&self, data: &str) -> CommandResult {
         use super::irc::IrcClient;
         let client = self.state.client().ok_or_else(|| Error::NotConnected)?;
+        // before the welcome, or while reconnecting, there's no nick to send as
+        let nickname = client.state().nickname().ok_or(Error::NotConnected)?;
 
         let (_index, buffer) = self.state.buffers().current();
         if buffer.is_status() {
-            return Err(Error::InvalidBuffer(buffer.name().into()));
+            return Err(Error::InvalidBuffer(buffer.name()));
         }
 
         client.privmsg(&buffer.name(), data);
 
-        let nickname = client
-            .state()
-            .nickname()
-            .expect("client should have a valid nickname");
-
-        let mut output = Output::stamp();
-        output.fg(Color::Green).add(nickname).add(" ").add(data);
+        let data = if buffer.strips_formatting() {
+            irc::format::strip(data)
+        } else {
+            data.to_owned()
+        };
+        let nicks = Nicks {
+            casemapping: client.state().isupport().casemapping,
+            channel: client.state().channels().get(buffer.name()),
+        };
+        let theme = self.state.theme();
+        let mut output = theme.stamp();
+        let vars = [("nick", nickname.as_str()), ("text", data.as_str())];
+        theme.render(&mut output, "own_privmsg", &vars, &nicks);
         Ok(Response::Output(output.build()))
     }
 }
 
-fn assume_connected(ctx: &Context) -> Result<(), Error> {
-    if ctx.state.client().is_none() {
-        Err(Error::NotConnected)?;
+/// returns the network of the active buffer
+fn assume_connected(ctx: &Context) -> Result<String, Error> {
+    match ctx.state.network() {
+        Some(ref network) if ctx.state.client_for(network).is_some() => Ok(network.clone()),
+        _ => Err(Error::NotConnected),
     }
-    Ok(())
+}
+
+/// queues the request of a client command for the active buffer
+fn client_command(ctx: &Context, func: ClientCommand) -> CommandResult {
+    let network = assume_connected(ctx)?;
+    let (_, buffer) = ctx.state.buffers().current();
+    ctx.request(func(network, &buffer.name(), ctx.parts)?);
+    Ok(Response::Nothing)
 }
 
 fn assume_args(ctx: &Context, msg: &'static str) -> Result<(), Error> {
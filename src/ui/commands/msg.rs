@@ -0,0 +1,19 @@
+use super::*;
+
+pub(crate) fn msg_command(ctx: &Context) -> CommandResult {
+    client_command(ctx, msg_request)
+}
+
+pub(crate) fn msg_request(network: String, _buffer: &str, parts: &[&str]) -> RequestResult {
+    if parts.len() < 2 {
+        Err(Error::InvalidArgument(
+            "try: /msg <target> <message>".into(),
+        ))?;
+    }
+
+    Ok(Request::Privmsg(
+        network,
+        parts[0].to_owned(),
+        parts[1..].join(" "),
+    ))
+}
@@ -1,20 +1,15 @@
 use super::*;
 
 pub(crate) fn part_command(ctx: &Context) -> CommandResult {
-    assume_connected(&ctx)?;
+    client_command(ctx, part_request)
+}
 
-    let (_, buf) = ctx.state.buffers().current();
-    if buf.is_status() {
+pub(crate) fn part_request(network: String, buffer: &str, parts: &[&str]) -> RequestResult {
+    if buffer.starts_with('*') {
         // TODO get rid of this string
         return Err(Error::InvalidBuffer("cannot /part in a *window".into()));
     };
 
-    let ch = if ctx.parts.is_empty() {
-        buf.name().to_string()
-    } else {
-        ctx.parts[0].to_string()
-    };
-
-    ctx.request(Request::Part(ch));
-    Ok(Response::Nothing)
+    let ch = parts.first().map_or(buffer, |s| *s);
+    Ok(Request::Part(network, ch.to_string()))
 }
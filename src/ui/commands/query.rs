@@ -0,0 +1,25 @@
+use super::*;
+
+pub(crate) fn query_command(ctx: &Context) -> CommandResult {
+    let network = assume_connected(ctx)?;
+    assume_args(ctx, "try: /query <nick> [message]")?;
+
+    let nick = ctx.parts[0];
+    let is_channel = ctx
+        .state
+        .client_for(&network)
+        .is_some_and(|client| client.state().isupport().is_channel(nick));
+    if is_channel {
+        Err(Error::InvalidArgument("try: /join <chan>".into()))?;
+    }
+
+    ctx.request(Request::Query(network.clone(), nick.to_owned()));
+    if ctx.parts.len() > 1 {
+        ctx.request(Request::Privmsg(
+            network,
+            nick.to_owned(),
+            ctx.parts[1..].join(" "),
+        ));
+    }
+    Ok(Response::Nothing)
+}
@@ -1,14 +1,15 @@
 use super::*;
 
 pub(crate) fn quit_command(ctx: &Context) -> CommandResult {
-    assume_connected(&ctx)?;
+    client_command(ctx, quit_request)
+}
 
-    let msg = if ctx.parts.is_empty() {
+pub(crate) fn quit_request(network: String, _buffer: &str, parts: &[&str]) -> RequestResult {
+    let msg = if parts.is_empty() {
         None
     } else {
-        Some(ctx.parts.join(" "))
+        Some(parts.join(" "))
     };
 
-    ctx.request(Request::Quit(msg));
-    Ok(Response::Nothing)
+    Ok(Request::Quit(network, msg))
 }
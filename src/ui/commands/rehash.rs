@@ -2,7 +2,7 @@ use super::*;
 
 pub(crate) fn rehash_command(ctx: &Context) -> CommandResult {
     if let Ok(config) = Config::load("riirc.toml") {
-        ctx.config.replace(config);
+        ctx.state.set_config(config);
         Ok(Response::Nothing)
     } else {
         Err(Error::ReloadConfig)
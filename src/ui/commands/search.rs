@@ -0,0 +1,9 @@
+use super::*;
+
+pub(crate) fn search_command(ctx: &Context) -> CommandResult {
+    // without a query, it stops highlighting the last one
+    let query = ctx.parts.join(" ");
+    let query = if query.is_empty() { None } else { Some(query) };
+    ctx.request(Request::Search(query));
+    Ok(Response::Nothing)
+}
@@ -0,0 +1,22 @@
+use super::*;
+
+pub(crate) fn strip_command(ctx: &Context) -> CommandResult {
+    let (_, buf) = ctx.state.buffers().current();
+    let strip = match ctx.parts.first() {
+        None => !buf.strips_formatting(),
+        Some(&"on") => true,
+        Some(&"off") => false,
+        _ => Err(Error::InvalidArgument("try: /strip [on|off]".into()))?,
+    };
+    buf.set_strip_formatting(strip);
+
+    let output = Output::new()
+        .fg(Color::Yellow)
+        .add(if strip {
+            "formatting will be stripped in this buffer"
+        } else {
+            "formatting will be shown in this buffer"
+        })
+        .build();
+    Ok(Response::Output(output))
+}
@@ -0,0 +1,171 @@
+use super::irc;
+
+/// Cycles through the ways the word before the cursor can be finished
+#[derive(Debug)]
+pub struct Completion {
+    // where the word starts in the line
+    start: usize,
+    candidates: Vec<String>,
+    // the candidate in the line, `None` until the first one is put there
+    index: Option<usize>,
+    // the line after the last candidate was put in, so edits can be noticed
+    line: Vec<char>,
+}
+
+impl Completion {
+    /// `None` if there's nothing to cycle through
+    pub fn new(start: usize, candidates: Vec<String>) -> Option<Self> {
+        if candidates.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            start,
+            candidates,
+            index: None,
+            line: vec![],
+        })
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// where the current candidate ends, or the start before there is one
+    pub fn end(&self) -> usize {
+        self.start + self.current().map_or(0, |s| s.chars().count())
+    }
+
+    /// whether `line` is still what the last candidate left it as
+    pub fn is_current(&self, line: &[char]) -> bool {
+        self.index.is_some() && self.line.as_slice() == line
+    }
+
+    /// moves to the next (or previous) candidate, wrapping around at the ends
+    pub fn advance(&mut self, forward: bool) -> &str {
+        let len = self.candidates.len();
+        let index = match (self.index, forward) {
+            (None, true) => 0,
+            (None, false) => len - 1,
+            (Some(n), true) => (n + 1) % len,
+            (Some(n), false) => (n + len - 1) % len,
+        };
+        self.index = Some(index);
+        &self.candidates[index]
+    }
+
+    /// remembers the line with the candidate in it
+    pub fn set_line(&mut self, line: &[char]) {
+        self.line = line.to_vec();
+    }
+
+    fn current(&self) -> Option<&str> {
+        self.index.map(|n| self.candidates[n].as_str())
+    }
+}
+
+/// Everything a word could be finished as
+#[derive(Debug, Default)]
+pub struct Candidates {
+    pub commands: Vec<String>,
+    /// the names `/bind` takes
+    pub keys: Vec<String>,
+    pub channels: Vec<String>,
+    /// who is in the active channel
+    pub nicks: Vec<String>,
+}
+
+impl Candidates {
+    /// the ones `word` could be, given what comes `before` it in the line
+    pub fn matching(self, before: &str, word: &str, isupport: &irc::ISupport) -> Vec<String> {
+        let mut candidates = if before.is_empty() && word.starts_with('/') {
+            self.commands
+        } else if before.trim_end() == "/bind" {
+            self.keys
+        } else if isupport.is_channel(word) {
+            self.channels
+        } else {
+            // a nick at the start of the line is who it's for
+            let suffix = if before.is_empty() { ": " } else { "" };
+            self.nicks
+                .into_iter()
+                .map(|nick| format!("{}{}", nick, suffix))
+                .collect()
+        };
+
+        let prefix = isupport.fold(word);
+        candidates.retain(|s| isupport.fold(s).starts_with(&prefix));
+        candidates.sort_by_key(|s| isupport.fold(s));
+        candidates.dedup();
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates() -> Candidates {
+        let strings = |list: &[&str]| list.iter().map(|s| s.to_string()).collect();
+        Candidates {
+            commands: strings(&["/join", "/part", "/quit", "/query"]),
+            keys: strings(&["SwitchBuffer0", "ScrollUp", "ScrollDown"]),
+            channels: strings(&["#rust", "#Riirc", "#test"]),
+            nicks: strings(&["bob", "Bobby", "alice", "[bot]"]),
+        }
+    }
+
+    fn matching(before: &str, word: &str) -> Vec<String> {
+        candidates().matching(before, word, &irc::ISupport::new())
+    }
+
+    #[test]
+    fn sources() {
+        assert_eq!(matching("", "/qu"), vec!["/query", "/quit"]);
+        assert_eq!(matching("/bind ", "scroll"), vec!["ScrollDown", "ScrollUp"]);
+        assert_eq!(matching("/join ", "#r"), vec!["#Riirc", "#rust"]);
+        assert_eq!(matching("hi ", "bo"), vec!["bob", "Bobby"]);
+
+        // commands only come first
+        assert_eq!(matching("hi ", "/qu"), Vec::<String>::new());
+        // `{` is the lower case `[`
+        assert_eq!(matching("hi ", "{b"), vec!["[bot]"]);
+        assert_eq!(matching("hi ", "carol"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn suffix() {
+        assert_eq!(matching("", "al"), vec!["alice: "]);
+        assert_eq!(matching("hey ", "al"), vec!["alice"]);
+        assert_eq!(
+            matching("", ""),
+            vec!["alice: ", "bob: ", "Bobby: ", "[bot]: "]
+        );
+    }
+
+    #[test]
+    fn cycling() {
+        assert!(Completion::new(0, vec![]).is_none());
+
+        let mut completion = Completion::new(3, vec!["bob".into(), "Bobby".into()]).unwrap();
+        assert_eq!(completion.end(), 3);
+        assert!(!completion.is_current(&[]));
+
+        assert_eq!(completion.advance(true), "bob");
+        assert_eq!(completion.end(), 6);
+        assert_eq!(completion.advance(true), "Bobby");
+        assert_eq!(completion.end(), 8);
+        assert_eq!(completion.advance(true), "bob");
+        assert_eq!(completion.advance(false), "Bobby");
+        assert_eq!(completion.advance(false), "bob");
+
+        let line = "hi bob".chars().collect::<Vec<_>>();
+        completion.set_line(&line);
+        assert!(completion.is_current(&line));
+        assert!(!completion.is_current(&"hi bo".chars().collect::<Vec<_>>()));
+
+        // backwards starts at the end
+        let mut completion = Completion::new(0, vec!["a".into(), "b".into()]).unwrap();
+        assert_eq!(completion.advance(false), "b");
+    }
+}
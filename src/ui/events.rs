@@ -1,12 +1,15 @@
-use super::colors::Color;
+use super::buffer::Activity;
+use super::irc::ctcp::Ctcp;
 use super::output::Output;
 use super::request::Request;
 use super::state::State;
 use super::windows::Container;
 use super::*;
 
+use chrono::{TimeZone, Utc};
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::sync::Arc;
 
 pub struct EventProcessor {
     state: Rc<State>,
@@ -28,32 +31,77 @@ impl EventProcessor {
     }
 
     pub fn process(&self) {
+        self.read_status();
         self.read_irc_events();
         self.read_requests();
     }
 
+    /// reports connection progress to the status buffer and every channel
+    fn read_status(&self) {
+        for (network, client) in self.state.clients() {
+            self.read_client_status(&network, &client)
+        }
+    }
+
+    fn read_client_status(&self, network: &str, client: &irc::Client) {
+        let status = client.status();
+        while let Some(status) = status.try_recv() {
+            let (event, vars) = match status {
+                // the connect command already said this
+                irc::Status::Connecting { attempt: 1, .. } => continue,
+                irc::Status::Connecting { addr, attempt } => (
+                    "connecting",
+                    vec![("addr", addr.to_string()), ("attempt", attempt.to_string())],
+                ),
+                irc::Status::Connected { addr } => ("connected", vec![("addr", addr.to_string())]),
+                irc::Status::Disconnected(err) => {
+                    ("disconnected", vec![("reason", format!("{:?}", err))])
+                }
+                irc::Status::Reconnecting { delay } => {
+                    ("reconnecting", vec![("delay", delay.as_secs().to_string())])
+                }
+            };
+
+            let theme = self.state.theme();
+            let vars = vars
+                .iter()
+                .map(|(k, v)| (*k, v.as_str()))
+                .collect::<Vec<_>>();
+            let mut output = theme.stamp();
+            theme.render(&mut output, event, &vars, &Nicks::default());
+
+            let output = output.build();
+            let buffers = self.state.buffers();
+            buffers.status_of(network);
+            for pos in buffers.of_network(network) {
+                self.queue.enqueue(Request::Queue(pos, output.clone()));
+            }
+        }
+    }
+
+    fn read_irc_events(&self) {
+        for (network, client) in self.state.clients() {
+            self.read_client_events(&network, &client)
+        }
+    }
+
     // TODO finish this
     #[allow(unused_variables)]
-    fn read_irc_events(&self) {
-        let client = match self.state.client() {
-            Some(client) => client,
+    fn read_client_events(&self, network: &str, client: &irc::Client) {
+        let client_state = client.state();
+        let (ts, msg, channels) = match client_state.next_message() {
+            Some(next) => next,
             None => return,
         };
-
-        let (ts, msg, me) = match {
-            let state = client.state();
-            (state.next_message(), state.nickname())
-        } {
-            (Some((ts, msg)), me) => (ts, msg, me),
-            (None, _) => return,
-        };
+        let me = client_state.nickname();
 
         let buffers = self.state.buffers();
-        let (index, active) = buffers.current();
+        let isupport = client.state().isupport();
+        let theme = self.state.theme();
 
         macro_rules! send_to_buf {
             ($e:expr, $output:expr) => {
-                if let Some(pos) = buffers.index_of($e) {
+                if let Some(pos) = buffers.index_of(Some(network), $e) {
                     self.queue.enqueue(Request::Queue(pos, $output))
                 } else {
                     warn!("unknown buffer: {} ({:?})", $e, &msg)
@@ -61,7 +109,82 @@ impl EventProcessor {
             };
         }
 
+        // replies about a channel go in its buffer, if there is one
+        let reply_to = |name: &str, output: Output| {
+            let pos = buffers
+                .index_of(Some(network), name)
+                .unwrap_or_else(|| buffers.status_of(network));
+            self.queue.enqueue(Request::Queue(pos, output))
+        };
+
+        // mirc formatting is dropped in buffers that strip it
+        let text = |name: &str, data: &str| {
+            let strip = buffers
+                .named(Some(network), name)
+                .is_some_and(|(_, buf)| buf.strips_formatting());
+            if strip {
+                irc::format::strip(data)
+            } else {
+                data.to_owned()
+            }
+        };
+
+        // nicks get their colors, and so does anyone in the channel that's mentioned
+        let nicks = |name: &str| Nicks {
+            casemapping: isupport.casemapping,
+            channel: client.state().channels().get(name),
+        };
+
+        // prefer the time the server saw the message, if it told us
+        let stamp = || match msg.tags.server_time() {
+            Some(ts) => theme.stamp_at(ts),
+            None => theme.stamp(),
+        };
+
         match &msg.command {
+            // the client answers these, this just says who asked
+            IrcCommand::Privmsg {
+                target,
+                data,
+                is_notice: false,
+            } if Ctcp::parse(data).is_some_and(|ctcp| !ctcp.is_action()) => {
+                let ctcp = Ctcp::parse(data).expect("ctcp");
+                let params = ctcp_params(&ctcp, false);
+                let vars = [
+                    ("nick", msg.get_nick()),
+                    ("command", ctcp.command.as_str()),
+                    ("params", params.as_str()),
+                ];
+                let mut output = stamp();
+                theme.render(&mut output, "ctcp", &vars, &nicks(target));
+                self.queue
+                    .enqueue(Request::Queue(buffers.status_of(network), output.build()))
+            }
+
+            // replies to a /ctcp go wherever we're looking
+            IrcCommand::Privmsg {
+                target,
+                data,
+                is_notice: true,
+            } if Ctcp::parse(data).is_some() => {
+                let ctcp = Ctcp::parse(data).expect("ctcp");
+                let from = match &msg.prefix {
+                    Some(irc::Prefix::User { nick, .. }) => nick.as_str(),
+                    Some(irc::Prefix::Server { host }) => host.as_str(),
+                    None => "*",
+                };
+                let params = ctcp_params(&ctcp, true);
+                let vars = [
+                    ("nick", from),
+                    ("command", ctcp.command.as_str()),
+                    ("params", params.as_str()),
+                ];
+                let mut output = stamp();
+                theme.render(&mut output, "ctcp_reply", &vars, &nicks(target));
+                self.queue
+                    .enqueue(Request::Queue(buffers.current().0, output.build()))
+            }
+
             IrcCommand::Privmsg {
                 target,
                 data,
@@ -69,49 +192,383 @@ impl EventProcessor {
             }
                 if !is_notice =>
             {
-                send_to_buf!(
-                    target,
-                    Output::stamp()
-                        .add(msg.get_nick())
-                        .add(" ")
-                        .add(data)
-                        .build()
-                );
+                let nick = msg.get_nick();
+                let from_me = me
+                    .as_ref()
+                    .is_some_and(|me| isupport.eq_ignore_case(me, nick));
+                let highlight = !from_me && self.state.is_highlight(me.as_deref(), data);
+
+                // a private message opens a query named after whoever is on the other side
+                let query = !isupport.is_channel(target);
+                let name = if query && !from_me { nick } else { target };
+                if query {
+                    buffers.create(Some(network), name, false);
+                }
+
+                let mut output = stamp();
+                if (highlight || query) && !from_me {
+                    match buffers.named(Some(network), name) {
+                        Some((pos, buf)) if pos != buffers.current().0 => buf.mark_highlight(),
+                        _ => {}
+                    }
+                }
+
+                // anything but an action was handled above
+                let ctcp = Ctcp::parse(data);
+                let (event, data) = match &ctcp {
+                    Some(ctcp) if from_me => ("own_action", ctcp.params.as_str()),
+                    Some(ctcp) => ("action", ctcp.params.as_str()),
+                    None if from_me => ("own_privmsg", data.as_str()),
+                    None if highlight => ("highlight", data.as_str()),
+                    None => ("privmsg", data.as_str()),
+                };
+                let data = text(name, data);
+                let vars = [("nick", nick), ("text", data.as_str())];
+                theme.render(&mut output, event, &vars, &nicks(name));
+                send_to_buf!(name, output.build());
             }
             IrcCommand::Privmsg { target, data, .. } => {
-                send_to_buf!(
-                    target,
-                    Output::stamp()
-                        .add(msg.get_nick())
-                        .add(" ")
-                        .add(data)
-                        .build()
-                );
+                // notices from servers and services only go in a query that's already open
+                let name = match &msg.prefix {
+                    _ if isupport.is_channel(target) => target.as_str(),
+                    Some(irc::Prefix::User { nick, .. }) => nick.as_str(),
+                    _ => "",
+                };
+                let from = match &msg.prefix {
+                    Some(irc::Prefix::User { nick, .. }) => nick.as_str(),
+                    Some(irc::Prefix::Server { host }) => host.as_str(),
+                    None => "*",
+                };
+                let data = text(name, data);
+                let mut output = stamp();
+                let vars = [("nick", from), ("text", data.as_str())];
+                theme.render(&mut output, "notice", &vars, &nicks(name));
+                reply_to(name, output.build());
+            }
+
+            IrcCommand::Nick { nickname } => {
+                // queries follow the other side's new nick
+                let old = msg.get_nick();
+                let query = buffers
+                    .rename(Some(network), old, nickname)
+                    .map(|_| nickname.as_str());
+
+                for name in query.into_iter().chain(channels.iter().map(|s| s.as_str())) {
+                    if let Some(pos) = buffers.index_of(Some(network), name) {
+                        let mut output = stamp();
+                        let vars = [("nick", old), ("new", nickname.as_str())];
+                        theme.render(&mut output, "nick", &vars, &nicks(name));
+                        self.queue.enqueue(Request::Queue(pos, output.build()))
+                    }
+                }
             }
 
             IrcCommand::Join { channel, .. } => {
-                send_to_buf!(
-                    channel,
-                    Output::stamp()
-                        .add(msg.get_nick())
-                        .add(" join ")
-                        .add(channel)
-                        .build()
-                );
+                let mut output = stamp();
+                let vars = [("nick", msg.get_nick()), ("channel", channel.as_str())];
+                theme.render(&mut output, "join", &vars, &nicks(channel));
+                send_to_buf!(channel, output.build());
             }
 
             IrcCommand::Part { channel, reason } => {
-                let mut output = Output::stamp();
-                output.add(msg.get_nick()).add(" left ").add(channel);
-                if reason.is_some() {
-                    output.add(": ").add(reason.as_ref().unwrap());
+                let reason = reason
+                    .as_ref()
+                    .map(|reason| format!(": {}", text(channel, reason)))
+                    .unwrap_or_default();
+
+                let mut output = stamp();
+                let vars = [
+                    ("nick", msg.get_nick()),
+                    ("channel", channel.as_str()),
+                    ("reason", reason.as_str()),
+                ];
+                theme.render(&mut output, "part", &vars, &nicks(channel));
+                send_to_buf!(channel, output.build());
+            }
+
+            IrcCommand::Quit { reason } => {
+                // in every channel they were in, and an open query
+                let nick = msg.get_nick();
+                let names = Some(nick)
+                    .into_iter()
+                    .chain(channels.iter().map(|s| s.as_str()));
+                for name in names {
+                    if let Some(pos) = buffers.index_of(Some(network), name) {
+                        let reason = if reason.is_empty() {
+                            String::new()
+                        } else {
+                            format!(": {}", text(name, reason))
+                        };
+                        let mut output = stamp();
+                        let vars = [("nick", nick), ("reason", reason.as_str())];
+                        theme.render(&mut output, "quit", &vars, &nicks(name));
+                        self.queue.enqueue(Request::Queue(pos, output.build()))
+                    }
                 }
+            }
 
+            IrcCommand::Topic { channel, topic } => {
+                let (event, topic) = if topic.is_empty() {
+                    ("topic_clear", String::new())
+                } else {
+                    ("topic", text(channel, topic))
+                };
+
+                let mut output = stamp();
+                let vars = [
+                    ("nick", msg.get_nick()),
+                    ("channel", channel.as_str()),
+                    ("topic", topic.as_str()),
+                ];
+                theme.render(&mut output, event, &vars, &nicks(channel));
                 send_to_buf!(channel, output.build());
             }
 
+            IrcCommand::Mode {
+                target,
+                modes,
+                args,
+            } => {
+                let modes = Some(modes)
+                    .into_iter()
+                    .chain(args)
+                    .map(|s| s.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                let mut output = stamp();
+                let vars = [
+                    ("nick", msg.get_nick()),
+                    ("target", target.as_str()),
+                    ("modes", modes.as_str()),
+                ];
+                theme.render(&mut output, "mode", &vars, &nicks(target));
+                reply_to(target, output.build())
+            }
+
+            // RPL_CHANNELMODEIS
+            IrcCommand::Reply {
+                numeric: 324,
+                params,
+            } if params.len() > 2 => {
+                let modes = params[2..].join(" ");
+                let mut output = stamp();
+                let vars = [("channel", params[1].as_str()), ("modes", modes.as_str())];
+                theme.render(&mut output, "channel_modes", &vars, &nicks(&params[1]));
+                reply_to(&params[1], output.build())
+            }
+
+            // RPL_CREATIONTIME
+            IrcCommand::Reply {
+                numeric: 329,
+                params,
+            } if params.len() > 2 => {
+                let time = params[2]
+                    .parse()
+                    .ok()
+                    .and_then(|time| chrono::Local.timestamp_opt(time, 0).single());
+                if let Some(time) = time {
+                    let time = time.format("%Y-%m-%d %H:%M:%S").to_string();
+                    let mut output = stamp();
+                    let vars = [("channel", params[1].as_str()), ("time", time.as_str())];
+                    theme.render(&mut output, "created", &vars, &nicks(&params[1]));
+                    reply_to(&params[1], output.build())
+                }
+            }
+
+            // RPL_NOTOPIC
+            IrcCommand::Reply {
+                numeric: 331,
+                params,
+            } if params.len() > 1 => {
+                let mut output = stamp();
+                let vars = [("channel", params[1].as_str())];
+                theme.render(&mut output, "no_topic", &vars, &nicks(&params[1]));
+                reply_to(&params[1], output.build())
+            }
+
+            // RPL_TOPIC
+            IrcCommand::Reply {
+                numeric: 332,
+                params,
+            } if params.len() > 2 => {
+                let topic = text(&params[1], &params[2]);
+                let mut output = stamp();
+                let vars = [("channel", params[1].as_str()), ("topic", topic.as_str())];
+                theme.render(&mut output, "topic_is", &vars, &nicks(&params[1]));
+                reply_to(&params[1], output.build())
+            }
+
+            // RPL_TOPICWHOTIME
+            IrcCommand::Reply {
+                numeric: 333,
+                params,
+            } if params.len() > 1 => {
+                let info = client
+                    .state()
+                    .channels()
+                    .get(&params[1])
+                    .and_then(|ch| ch.topic_info());
+                if let Some((setter, time)) = info {
+                    let time = time.with_timezone(&chrono::Local);
+                    let time = time.format("%Y-%m-%d %H:%M:%S").to_string();
+                    let mut output = stamp();
+                    let vars = [
+                        ("nick", setter.as_str()),
+                        ("channel", params[1].as_str()),
+                        ("time", time.as_str()),
+                    ];
+                    theme.render(&mut output, "topic_set_by", &vars, &nicks(&params[1]));
+                    reply_to(&params[1], output.build())
+                }
+            }
+
+            // RPL_NAMREPLY
+            IrcCommand::Reply {
+                numeric: 353,
+                params,
+            } if params.len() > 3 => {
+                let mut output = stamp();
+                let vars = [
+                    ("channel", params[2].as_str()),
+                    ("users", params[3].as_str()),
+                ];
+                theme.render(&mut output, "names", &vars, &nicks(&params[2]));
+                reply_to(&params[2], output.build())
+            }
+
+            // RPL_ENDOFNAMES, the names have already been shown
+            IrcCommand::Reply { numeric: 366, .. } => {}
+
+            // RPL_ISUPPORT, the last param is "are supported by this server"
+            IrcCommand::Reply { numeric: 5, params } if params.len() > 2 => {
+                // the buffers compare names the way the server does
+                buffers.set_casemapping(network, isupport.casemapping);
+
+                let tokens = params[1..params.len() - 1].join(" ");
+                let mut output = stamp();
+                let vars = [("tokens", tokens.as_str())];
+                theme.render(&mut output, "isupport", &vars, &Nicks::default());
+                self.queue
+                    .enqueue(Request::Queue(buffers.status_of(network), output.build()))
+            }
+
+            // the motd
+            IrcCommand::Reply { numeric, params } if [375, 372, 376, 422].contains(numeric) => {
+                let line = params.last().map(|s| s.as_str()).unwrap_or_default();
+                let mut output = stamp();
+                theme.render(&mut output, "motd", &[("line", line)], &Nicks::default());
+                self.queue
+                    .enqueue(Request::Queue(buffers.status_of(network), output.build()))
+            }
+
+            // errors usually say what they're about before the reason
+            IrcCommand::Reply { numeric, params }
+                if *numeric >= 400 && *numeric < 600 && params.len() > 1 =>
+            {
+                let (subject, about) = if params.len() > 2 {
+                    let subject = &params[1..params.len() - 1];
+                    (subject[0].as_str(), format!("{}: ", subject.join(" ")))
+                } else {
+                    ("", String::new())
+                };
+
+                let numeric = numeric.to_string();
+                let mut output = stamp();
+                let vars = [
+                    ("numeric", numeric.as_str()),
+                    ("subject", about.as_str()),
+                    ("text", params[params.len() - 1].as_str()),
+                ];
+                theme.render(&mut output, "error", &vars, &Nicks::default());
+                reply_to(subject, output.build())
+            }
+
+            IrcCommand::Reply { numeric, params } if *numeric >= 900 && *numeric <= 908 => {
+                // `sasl_success`, `sasl_failure` or `sasl_info`
+                let status = |kind: &str, message: &str| {
+                    let mut output = stamp();
+                    let event = format!("sasl_{}", kind);
+                    let vars = [("message", message)];
+                    theme.render(&mut output, &event, &vars, &Nicks::default());
+                    let pos = buffers.status_of(network);
+                    self.queue.enqueue(Request::Queue(pos, output.build()))
+                };
+
+                let trailing = params.last().map(|s| s.as_str()).unwrap_or_default();
+                match numeric {
+                    900 => status(
+                        "success",
+                        &format!("logged in as {}", params.get(2).map_or(trailing, |s| s)),
+                    ),
+                    901 => status("info", "logged out"),
+                    902 => status("failure", "nickname is locked, cannot log in"),
+                    903 => status("success", "authentication successful"),
+                    904 => status("failure", "authentication failed"),
+                    905 => status("failure", "authentication failed, message too long"),
+                    906 => status("info", "authentication aborted"),
+                    907 => status("info", "already authenticated"),
+                    908 => status(
+                        "info",
+                        &format!("server supports: {}", params.get(1).map_or("", |s| s)),
+                    ),
+                    _ => unreachable!(),
+                }
+            }
+
+            // the first param is always our nick
+            IrcCommand::Reply { params, .. } if params.len() > 1 => {
+                let output = stamp().add(params[1..].join(" ")).build();
+                self.queue
+                    .enqueue(Request::Queue(buffers.status_of(network), output))
+            }
+
             _ => debug!("unknown: {:?}", msg),
         };
+
+        // the client has already applied these to the channels
+        match &msg.command {
+            IrcCommand::Join { .. }
+            | IrcCommand::Part { .. }
+            | IrcCommand::Quit { .. }
+            | IrcCommand::Nick { .. }
+            | IrcCommand::Mode { .. }
+            | IrcCommand::Reply { numeric: 366, .. } => self.sync_nicklist(),
+            _ => {}
+        }
+
+        // things the status bar shows
+        match &msg.command {
+            IrcCommand::Nick { .. }
+            | IrcCommand::Pong { .. }
+            | IrcCommand::Reply { numeric: 1, .. }
+            | IrcCommand::Reply { numeric: 305, .. }
+            | IrcCommand::Reply { numeric: 306, .. } => self.queue.enqueue(Request::RefreshStatus),
+            _ => {}
+        }
+    }
+
+    /// the channel of the active buffer, if it is one
+    fn active_channel(&self) -> Option<Arc<irc::Channel>> {
+        let (_, buf) = self.state.buffers().current();
+        let client = self.state.client_for(buf.network()?)?;
+        client.state().channels().get(buf.name())
+    }
+
+    /// keeps an open nicklist showing the active channel, closing it when that isn't a channel
+    fn sync_nicklist(&self) {
+        let nicklist = self.container.borrow().nicklist();
+        if !nicklist.is_visible() {
+            return;
+        }
+
+        match self.active_channel() {
+            Some(channel) => nicklist.set_members(channel.users()),
+            None => {
+                self.container.borrow_mut().toggle_nicklist();
+                self.state.buffers().display();
+            }
+        }
     }
 
     fn read_requests(&self) {
@@ -133,15 +590,23 @@ impl EventProcessor {
         match req {
             Request::Queue(pos, data) => {
                 let buffers = self.state.buffers();
-                if let Some(buf) = buffers.get(*pos) {
-                    buf.push_message(&data);
+                let buf = buffers.get(*pos)?;
+                buf.push_message(data);
+
+                let (index, _) = buffers.current();
+                if index != *pos {
+                    buf.mark_unread();
+                    self.queue.enqueue(Request::RefreshStatus);
+                    return None;
                 }
 
-                let (index, buf) = buffers.current();
-                if index == *pos {
-                    let msg = buf.most_recent()?;
-                    self.container.borrow().output().output(msg, true);
+                // the "more below" count has to be redrawn
+                if buf.scroll() > 0 {
+                    buffers.display();
+                    return None;
                 }
+                let msg = buf.highlight(&buf.most_recent()?);
+                self.container.borrow().output().output(msg, true);
             }
 
             Request::Target(pos, data) => {
@@ -154,49 +619,104 @@ impl EventProcessor {
 
             Request::Clear(scrollback) => {
                 let buffers = self.state.buffers();
-                self.container
-                    .borrow()
-                    .output()
-                    .queue(ui::Request::Clear(*scrollback));
+                ui::Outputter::clear(&*self.container.borrow().output());
                 if *scrollback {
                     let (_, buf) = buffers.current();
                     buf.clear();
                 }
             }
 
+            Request::RefreshStatus => self.container.borrow().status().refresh(),
+
+            Request::Resize => {
+                self.container.borrow_mut().resize();
+                self.state.buffers().display();
+            }
+
             Request::ToggleNickList => {
-                let buffers = self.state.buffers();
-                let (index, buf) = buffers.current();
-                if index == 0 {
-                    return None;
+                let nicklist = self.container.borrow().nicklist();
+                if !nicklist.is_visible() {
+                    nicklist.set_members(self.active_channel()?.users());
                 }
+                self.container.borrow_mut().toggle_nicklist();
+                self.state.buffers().display();
+            }
 
+            Request::ScrollNickList(up) => {
                 let nicklist = self.container.borrow().nicklist();
-                if nicklist.is_visible() {
-                    nicklist.toggle();
+                if !nicklist.is_visible() {
                     return None;
                 }
-
-                let ch = self.state.client()?.state().channels().get(&buf.name())?;
-                nicklist.toggle();
-                for user in ch.users() {
-                    nicklist.output(Output::new().add(&user.as_ref()).build(), true)
+                if *up {
+                    nicklist.scroll_up()
+                } else {
+                    nicklist.scroll_down()
                 }
             }
 
-            Request::ClearHistory(_buf) => {
-                //self.container.input().clear_history()
+            Request::Scroll(scroll) => {
+                use super::request::Scroll::*;
+
+                let buffers = self.state.buffers();
+                let (_, buf) = buffers.current();
+                // keep a line from the last page on screen
+                let page = self.container.borrow().output().rows().saturating_sub(1);
+                let page = ::std::cmp::max(page, 1);
+                let top = buf.len().saturating_sub(page);
+
+                let below = match scroll {
+                    PageUp => ::std::cmp::min(buf.scroll() + page, top),
+                    PageDown => buf.scroll().saturating_sub(page),
+                    Top => top,
+                    Bottom => 0,
+                };
+                buf.scroll_to(below);
+                buffers.display();
             }
 
-            Request::SwitchBuffer(buf) => {
-                let nicklist = self.container.borrow().nicklist();
-                if *buf == 0 && nicklist.is_visible() {
-                    nicklist.toggle();
-                    return None;
+            Request::Search(query) => {
+                let buffers = self.state.buffers();
+                let (_, buf) = buffers.current();
+                buf.search(query.clone());
+                buffers.display();
+            }
+
+            Request::SearchNext => {
+                let buffers = self.state.buffers();
+                let (_, buf) = buffers.current();
+                buf.search_next();
+                buffers.display();
+            }
+
+            Request::ClearHistory(buf) => {
+                if let Some(buf) = self.state.buffers().get(*buf) {
+                    let input = self.container.borrow().input();
+                    input.borrow_mut().clear_history(buf.network(), &buf.name());
                 }
+            }
 
+            Request::SwitchBuffer(buf) => {
                 let buffers = self.state.buffers();
-                buffers.activate(*buf)
+                buffers.activate(*buf);
+                self.sync_nicklist();
+            }
+
+            Request::NextActive => {
+                let buffers = self.state.buffers();
+                let (index, _) = buffers.current();
+                let all = buffers.buffers();
+                // the closest one after the active buffer, highlights first
+                let after = (1..all.len()).map(|n| (index + n) % all.len());
+                let pos = after
+                    .clone()
+                    .find(|&pos| all[pos].activity() == Activity::Highlight)
+                    .or_else(|| {
+                        after
+                            .clone()
+                            .find(|&pos| all[pos].activity() != Activity::None)
+                    })?;
+                buffers.activate(pos);
+                self.sync_nicklist();
             }
 
             Request::NextBuffer => {
@@ -205,6 +725,7 @@ impl EventProcessor {
                 let (index, _) = buffers.current();
                 let pos = if index == len - 1 { 0 } else { index + 1 };
                 buffers.activate(pos);
+                self.sync_nicklist();
             }
 
             Request::PrevBuffer => {
@@ -213,38 +734,151 @@ impl EventProcessor {
                 let (index, _) = buffers.current();
                 let pos = if index == 0 { len - 1 } else { index - 1 };
                 buffers.activate(pos);
+                self.sync_nicklist();
             }
 
-            Request::Join(ref chan) => {
+            Request::Join(ref network, ref chan, ref key) => {
                 let buffers = self.state.buffers();
-                if buffers.named(&chan).is_some() {
-                    buffers.activate_by_name(&chan)
+                if buffers.named(Some(network), chan).is_some() {
+                    buffers.activate_by_name(Some(network), chan)
                 } else {
-                    self.state.client()?.join(&chan, None);
-                    buffers.create(&chan, true);
+                    self.state.client_for(network)?.join(chan, key.as_deref());
+                    buffers.create(Some(network), chan, true);
                 }
             }
 
-            Request::Part(ref chan) => {
+            Request::Part(ref network, ref chan) => {
                 let buffers = self.state.buffers();
-                if buffers.named(&chan).is_some() {
-                    self.state.client()?.part(&chan, "leaving");
-                    buffers.delete(&chan);
+                if buffers.named(Some(network), chan).is_some() {
+                    self.state.client_for(network)?.part(chan, "leaving");
+                    buffers.delete(Some(network), chan);
                 } else {
-                    self.container.borrow().output().output(
-                        Output::new()
-                            .add("not on ")
-                            .fg(Color::Cyan)
-                            .add(&chan)
-                            .build(),
-                        false,
-                    );
+                    let mut output = Output::new();
+                    let vars = [("channel", chan.as_str())];
+                    let theme = self.state.theme();
+                    theme.render(&mut output, "not_on", &vars, &Nicks::default());
+                    self.container
+                        .borrow()
+                        .output()
+                        .output(output.build(), false);
                 }
             }
 
-            Request::Quit(msg) => self.state.client()?.quit(msg.clone()),
+            Request::Query(ref network, ref nick) => {
+                self.state.buffers().create(Some(network), nick, true);
+                self.sync_nicklist();
+            }
+
+            Request::Close(ref network, ref name) => {
+                self.state.buffers().delete(Some(network), name);
+                self.sync_nicklist();
+            }
+
+            Request::Privmsg(ref network, ref target, ref data) => {
+                let client = self.state.client_for(network)?;
+                client.privmsg(target, data);
+
+                let nicks = Nicks {
+                    casemapping: client.state().isupport().casemapping,
+                    channel: client.state().channels().get(target),
+                };
+                let theme = self.state.theme();
+                let mut output = theme.stamp();
+                let buffers = self.state.buffers();
+
+                // only actions are shown like a message
+                let ctcp = Ctcp::parse(data);
+                if let Some(ctcp) = ctcp.as_ref().filter(|ctcp| !ctcp.is_action()) {
+                    let params = if ctcp.params.is_empty() {
+                        String::new()
+                    } else {
+                        format!(": {}", ctcp.params)
+                    };
+                    let vars = [
+                        ("nick", target.as_str()),
+                        ("command", ctcp.command.as_str()),
+                        ("params", params.as_str()),
+                    ];
+                    theme.render(&mut output, "own_ctcp", &vars, &nicks);
+                    self.queue
+                        .enqueue(Request::Queue(buffers.current().0, output.build()));
+                    return None;
+                }
+
+                // talking to someone opens a query with them
+                let pos = if client.state().isupport().is_channel(target) {
+                    buffers
+                        .index_of(Some(network), target)
+                        .unwrap_or_else(|| buffers.status_of(network))
+                } else {
+                    buffers.create(Some(network), target, false).0
+                };
+
+                let (event, data) = match &ctcp {
+                    Some(ctcp) => ("own_action", ctcp.params.as_str()),
+                    None => ("own_privmsg", data.as_str()),
+                };
+                let data = if buffers.get(pos)?.strips_formatting() {
+                    irc::format::strip(data)
+                } else {
+                    data.to_owned()
+                };
+                let nick = client.state().nickname()?;
+                let vars = [("nick", nick.as_str()), ("text", data.as_str())];
+                theme.render(&mut output, event, &vars, &nicks);
+                self.queue.enqueue(Request::Queue(pos, output.build()));
+            }
+
+            Request::Quit(network, msg) => self.state.client_for(network)?.quit(msg.clone()),
+
+            Request::Disconnect(network) => {
+                self.state.client_for(network)?.quit(None);
+                self.state.reset(network);
+
+                let buffers = self.state.buffers();
+                buffers.delete_network(network);
+                let theme = self.state.theme();
+                let mut output = theme.stamp();
+                let vars = [("network", network.as_str())];
+                theme.render(&mut output, "disconnect", &vars, &Nicks::default());
+                self.queue
+                    .enqueue(Request::Queue(buffers.status_of(network), output.build()));
+            }
         };
 
         None
     }
 }
+
+/// `: params` or nothing, with how long a PING took instead of what it sent if it's a reply
+fn ctcp_params(ctcp: &Ctcp, reply: bool) -> String {
+    match ctcp.params.parse::<i64>() {
+        // a /ctcp PING sends the time it went out, anyone else's is just a number
+        Ok(sent) if reply && ctcp.command == "PING" => {
+            let took = ::std::cmp::max(Utc::now().timestamp_millis() - sent, 0);
+            format!(": {}.{:03}s", took / 1000, took % 1000)
+        }
+        _ if ctcp.params.is_empty() => String::new(),
+        _ => format!(": {}", ctcp.params),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ping_params() {
+        let sent = Utc::now().timestamp_millis() - 1500;
+        let ping = Ctcp::new("PING", sent.to_string());
+
+        // only our own PING coming back says how long it took
+        let reply = ctcp_params(&ping, true);
+        assert!(reply.starts_with(": 1."), "{}", reply);
+        assert!(reply.ends_with('s'), "{}", reply);
+        assert_eq!(ctcp_params(&ping, false), format!(": {}", sent));
+
+        assert_eq!(ctcp_params(&Ctcp::new("VERSION", ""), false), "");
+        assert_eq!(ctcp_params(&Ctcp::new("TIME", "noon"), true), ": noon");
+    }
+}
@@ -1,6 +1,9 @@
 use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
 use std::rc::Rc;
 
+use super::daemon::Packet;
 use super::windows::*;
 use super::*;
 
@@ -10,6 +13,7 @@ pub struct Gui {
     events: EventProcessor,
     commands: CommandProcessor,
     state: Rc<State>,
+    remote: Option<Remote>,
 }
 
 impl Gui {
@@ -21,6 +25,11 @@ impl Gui {
         let events =
             EventProcessor::new(Rc::clone(&state), Rc::clone(&queue), Rc::clone(&container));
         let commands = CommandProcessor::new(Rc::clone(&state), Rc::clone(&queue));
+        container
+            .borrow()
+            .input()
+            .borrow_mut()
+            .set_commands(commands.names());
 
         Self {
             queue,
@@ -28,9 +37,33 @@ impl Gui {
             events,
             commands,
             state,
+            remote: None,
         }
     }
 
+    /// a gui for a daemon listening on `path`, rather than one with its own connections
+    #[cfg(unix)]
+    pub fn attach(
+        config: Config,
+        path: impl AsRef<::std::path::Path>,
+    ) -> Result<Self, super::daemon::Error> {
+        use super::daemon::Error;
+        use std::os::unix::net::UnixStream;
+
+        let stream = UnixStream::connect(path).map_err(Error::Io)?;
+        let reader = stream.try_clone().map_err(Error::Io)?;
+
+        // only what was missed since the last time is replayed
+        let last_seen = last_seen_file()
+            .and_then(|file| fs::read_to_string(file).ok())
+            .and_then(|data| data.trim().parse().ok())
+            .unwrap_or(0);
+
+        let mut this = Self::new(config);
+        this.remote = Some(Remote::attach(reader, stream, last_seen)?);
+        Ok(this)
+    }
+
     pub fn run(&mut self) {
         loop {
             match {
@@ -38,47 +71,15 @@ impl Gui {
                 s.clone()
             } {
                 windows::ReadType::Line(line) => {
-                    if let Err(err) = self.commands.dispatch(&line) {
-                        debug!("command error: {:?}", err);
-                        match err {
-                            // TODO output-ize this
-                            ui::Error::InvalidArgument(s) | ui::Error::InvalidBuffer(s) => {
-                                trace!("{:?}", ui::Output::new().add(s).build())
-                            }
-                            ui::Error::ClientError(err) => {
-                                let output = ui::Output::new()
-                                    .fg(ui::Color::Red)
-                                    .add("error: ")
-                                    .add("irc client error: ")
-                                    .fg(ui::Color::Cyan)
-                                    .add(format!("{:?}", err))
-                                    .build();
-                                trace!("{:?}", output);
-                            }
-                            ui::Error::AlreadyConnected => {
-                                let output = ui::Output::new()
-                                    .fg(ui::Color::Red)
-                                    .add("error: ")
-                                    .add("already connected")
-                                    .build();
-                                trace!("{:?}", output);
-                            }
-                            ui::Error::NotConnected => {
-                                let output = ui::Output::new()
-                                    .fg(ui::Color::Red)
-                                    .add("error: ")
-                                    .add("not connected")
-                                    .build();
-                                trace!("{:?}", output);
-                            }
-                            ui::Error::ForceExit => break,
-
-                            _ => error!("unknown error: {:?}", err),
-                        }
-                    };
-
-                    {
-                        self.container.borrow_mut().add_and_clear();
+                    if !self.submit(&line) {
+                        break;
+                    }
+                    self.container.borrow_mut().add_and_clear();
+                }
+                // a confirmed paste, or lines that were composed, each sent as if it was typed
+                windows::ReadType::Lines(lines) => {
+                    if !lines.iter().all(|line| self.submit(line)) {
+                        break;
                     }
                 }
 
@@ -86,43 +87,182 @@ impl Gui {
                 windows::ReadType::FKey(key) if key == pancurses::Input::KeyF10 => break,
                 windows::ReadType::FKey(key) => trace!("fkey: {:?}", key),
 
-                windows::ReadType::None => if !self.read_buffers() {
-                    debug!("resetting the state");
-                    // flush the queue before clearing
-                    self.events.process();
+                windows::ReadType::None => self.read_buffers(),
+            }
+        }
+
+        if let Some(remote) = self.remote.take() {
+            remote.detach();
+            save_last_seen(&remote);
+        }
+    }
 
-                    // wipe out the state
-                    self.state.reset();
-                    self.state.buffers().activate(0);
-                },
+    /// runs a command or sends a message, false if it was time to quit
+    fn submit(&mut self, line: &str) -> bool {
+        if let Some(remote) = &self.remote {
+            // the daemon owns the connections, so it gets anything that isn't for the ui
+            if !self.commands.is_local(line) {
+                let (_, buf) = self.state.buffers().current();
+                remote.send_input(buf.network(), buf.name(), line);
+                return true;
             }
         }
+
+        match self.commands.dispatch(line) {
+            Ok(ui::Response::Output(output)) => {
+                let (index, _) = self.state.buffers().current();
+                self.queue.enqueue(ui::Request::Queue(index, output));
+            }
+            Ok(ui::Response::Nothing) => {}
+            Err(err) => {
+                debug!("command error: {:?}", err);
+                match err {
+                    // TODO output-ize this
+                    ui::Error::InvalidArgument(s) | ui::Error::InvalidBuffer(s) => {
+                        trace!("{:?}", ui::Output::new().add(s).build())
+                    }
+                    ui::Error::ClientError(err) => {
+                        let output = ui::Output::new()
+                            .fg(ui::Color::Red)
+                            .add("error: ")
+                            .add("irc client error: ")
+                            .fg(ui::Color::Cyan)
+                            .add(format!("{:?}", err))
+                            .build();
+                        trace!("{:?}", output);
+                    }
+                    ui::Error::AlreadyConnected => {
+                        let output = ui::Output::new()
+                            .fg(ui::Color::Red)
+                            .add("error: ")
+                            .add("already connected")
+                            .build();
+                        trace!("{:?}", output);
+                    }
+                    ui::Error::NotConnected => {
+                        let output = ui::Output::new()
+                            .fg(ui::Color::Red)
+                            .add("error: ")
+                            .add("not connected")
+                            .build();
+                        let (index, _) = self.state.buffers().current();
+                        self.queue.enqueue(ui::Request::Queue(index, output));
+                    }
+                    ui::Error::ForceExit => return false,
+
+                    _ => error!("unknown error: {:?}", err),
+                }
+            }
+        };
+        true
     }
 
-    fn read_buffers(&mut self) -> bool {
-        if !self.read_errors() {
-            return false;
+    fn read_buffers(&mut self) {
+        self.read_remote();
+
+        let failed = self.read_errors();
+        if !failed.is_empty() {
+            // flush the queue before clearing
+            self.events.process();
+        }
+
+        for network in failed {
+            debug!("resetting the state for {}", network);
+            self.state.reset(&network);
+            self.state.buffers().delete_network(&network);
         }
 
         self.events.process();
-        true
     }
 
-    fn read_errors(&mut self) -> bool {
-        if let Some(errors) = self.state.read_errors() {
+    fn read_remote(&mut self) {
+        let mut detached = false;
+        if let Some(remote) = &self.remote {
+            while let Some(packet) = remote.try_recv() {
+                match packet {
+                    Packet::Line {
+                        network,
+                        buffer,
+                        data,
+                        ..
+                    } => {
+                        let network = if network.is_empty() {
+                            None
+                        } else {
+                            Some(network.as_str())
+                        };
+                        let (pos, _) = self.state.buffers().create(network, buffer, false);
+                        let output = ui::Output::new().add(data).build();
+                        self.queue.enqueue(ui::Request::Queue(pos, output));
+                    }
+                    Packet::Error { message } => {
+                        let output = self
+                            .state
+                            .theme()
+                            .stamp()
+                            .fg(ui::Color::Red)
+                            .add("daemon: ")
+                            .fg(ui::Color::Cyan)
+                            .add(message)
+                            .build();
+                        self.queue.enqueue(ui::Request::Queue(0, output));
+                        detached = true;
+                        break;
+                    }
+                    packet => warn!("unexpected packet from the daemon: {:?}", packet),
+                }
+            }
+        }
+
+        if detached {
+            if let Some(remote) = self.remote.take() {
+                save_last_seen(&remote);
+            }
+        }
+    }
+
+    /// returns the networks whose client gave up
+    fn read_errors(&mut self) -> Vec<String> {
+        let mut failed = vec![];
+        for (network, errors) in self.state.read_errors() {
             if let Some(err) = errors.try_recv() {
-                let output = ui::Output::new()
+                let output = self
+                    .state
+                    .theme()
+                    .stamp()
                     .fg(ui::Color::Red)
                     .add("error: ")
-                    .add("irc c;ient error ")
+                    .add("irc client error: ")
                     .fg(ui::Color::Cyan)
                     .add(format!("{:?}", err))
                     .build();
 
                 trace!("{:?}", output);
-                return false;
+                let pos = self.state.buffers().status_of(&network);
+                self.queue.enqueue(ui::Request::Queue(pos, output));
+                failed.push(network);
             }
-        };
-        true
+        }
+        failed
+    }
+}
+
+/// where an attached ui remembers the newest line it got from the daemon
+fn last_seen_file() -> Option<PathBuf> {
+    config::data_dir().map(|dir| dir.join("last_seen"))
+}
+
+fn save_last_seen(remote: &Remote) {
+    let file = match last_seen_file() {
+        Some(file) => file,
+        None => return,
+    };
+
+    let res = file
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&file, remote.last_seen().to_string()));
+    if let Err(err) = res {
+        warn!("cannot save {}: {}", file.display(), err);
     }
 }
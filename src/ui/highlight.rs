@@ -0,0 +1,105 @@
+use super::config::Highlight;
+use regex::Regex;
+
+/// Decides which messages mention us
+#[derive(Debug, Default)]
+pub struct Highlighter {
+    words: Vec<String>,
+    regexes: Vec<Regex>,
+}
+
+impl Highlighter {
+    /// patterns that don't compile are skipped
+    pub fn new(config: &Highlight) -> Self {
+        let regexes = config
+            .regexes
+            .iter()
+            .filter_map(|re| match Regex::new(re) {
+                Ok(re) => Some(re),
+                Err(err) => {
+                    warn!("invalid highlight regex: {}: {}", re, err);
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            words: config.words.clone(),
+            regexes,
+        }
+    }
+
+    /// whether `text` has our nick, one of the words or matches one of the regexes
+    pub fn is_match(&self, nick: Option<&str>, text: &str) -> bool {
+        nick.into_iter()
+            .chain(self.words.iter().map(|s| s.as_str()))
+            .any(|word| contains_word(text, word))
+            || self.regexes.iter().any(|re| re.is_match(text))
+    }
+}
+
+// ignoring ascii case, with something other than a letter or number on either side
+fn contains_word(text: &str, word: &str) -> bool {
+    if word.is_empty() {
+        return false;
+    }
+
+    let text = text.to_ascii_lowercase();
+    let word = word.to_ascii_lowercase();
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric());
+
+    let mut pos = 0;
+    while let Some(start) = text[pos..].find(word.as_str()) {
+        let start = pos + start;
+        let end = start + word.len();
+        if !is_word(text[..start].chars().next_back()) && !is_word(text[end..].chars().next()) {
+            return true;
+        }
+        pos = start + text[start..].chars().next().map_or(1, |c| c.len_utf8());
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words() {
+        assert!(contains_word("hey test", "test"));
+        assert!(contains_word("TEST: hi", "test"));
+        assert!(contains_word("(test)", "test"));
+        assert!(contains_word("hi @test, you there?", "Test"));
+
+        // inside of a longer word doesn't count
+        assert!(!contains_word("testing", "test"));
+        assert!(!contains_word("a latest b", "test"));
+        assert!(!contains_word("test2", "test"));
+        assert!(!contains_word("ütest", "test"));
+        // but a later one can
+        assert!(contains_word("testing test", "test"));
+        assert!(!contains_word("anything", ""));
+    }
+
+    #[test]
+    fn matching() {
+        let highlighter = Highlighter::new(&Highlight {
+            words: vec!["rust".into()],
+            regexes: vec![r"^riirc\b".into(), "(".into()],
+        });
+
+        assert!(highlighter.is_match(Some("test"), "test: hello"));
+        assert!(!highlighter.is_match(Some("test"), "testing, hello"));
+        assert!(!highlighter.is_match(None, "test: hello"));
+
+        assert!(highlighter.is_match(None, "i like Rust."));
+        assert!(!highlighter.is_match(None, "it's rusty"));
+
+        assert!(highlighter.is_match(None, "riirc is slow"));
+        assert!(!highlighter.is_match(None, "use riirc"));
+        assert!(!highlighter.is_match(None, "riircs"));
+
+        // the broken pattern is skipped
+        assert_eq!(highlighter.regexes.len(), 1);
+    }
+}
@@ -1,5 +1,12 @@
 use super::*;
 
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, ErrorKind, Write};
+use std::path::Path;
+use std::rc::Rc;
+
 #[derive(Debug)]
 pub struct History {
     queue: BoundedSet<String>,
@@ -8,10 +15,7 @@ pub struct History {
 
 impl Default for History {
     fn default() -> Self {
-        Self {
-            queue: BoundedSet::new(32),
-            pos: -1,
-        }
+        Self::with_size(32)
     }
 }
 
@@ -20,13 +24,26 @@ impl History {
         Self::default()
     }
 
+    /// keeps at most `size` lines, dropping the oldest
+    pub fn with_size(size: usize) -> Self {
+        Self {
+            queue: BoundedSet::new(size),
+            pos: -1,
+        }
+    }
+
     pub fn clear(&mut self) {
         self.queue.clear();
         self.pos = -1;
     }
 
+    /// lines with a password in them are never kept
     pub fn append(&mut self, data: impl Into<String>) {
-        self.queue.insert(data.into());
+        let data = data.into();
+        if is_secret(&data) {
+            return;
+        }
+        self.queue.insert(data);
         self.pos = -1;
     }
 
@@ -50,4 +67,229 @@ impl History {
 
         self.queue.iter().rev().nth(self.pos as usize)
     }
+
+    /// the newest line with `query` in it, skipping the `skip` newest lines.
+    /// also returns how far back from the newest it is
+    pub fn find(&self, query: &str, skip: usize) -> Option<(usize, &str)> {
+        self.queue
+            .iter()
+            .rev()
+            .enumerate()
+            .skip(skip)
+            .find(|(_, line)| line.contains(query))
+            .map(|(n, line)| (n, line.as_str()))
+    }
+
+    /// oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.queue.iter()
+    }
+}
+
+/// Every history the input uses, and the file they're kept in between sessions
+pub struct Histories {
+    file: String,
+    per_buffer: bool,
+    size: usize,
+    map: BTreeMap<String, Rc<RefCell<History>>>,
+}
+
+impl Histories {
+    /// reads in the file, if there is one
+    pub fn new(config: &config::History) -> Self {
+        let mut this = Self {
+            file: config.file.clone(),
+            per_buffer: config.per_buffer,
+            size: config.size,
+            map: BTreeMap::new(),
+        };
+
+        if this.file.is_empty() {
+            return this;
+        }
+
+        let data = match fs::read_to_string(&this.file) {
+            Ok(data) => data,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => return this,
+            Err(err) => {
+                warn!("cannot read history: {}: {}", this.file, err);
+                return this;
+            }
+        };
+
+        // each line is the buffer's key, a tab and then what was typed
+        for line in data.lines() {
+            let mut parts = line.splitn(2, '\t');
+            if let (Some(key), Some(line)) = (parts.next(), parts.next()) {
+                // the file could have been saved with `per_buffer` set the other way
+                let key = if this.per_buffer { key } else { "" };
+                this.get(key).borrow_mut().append(line);
+            }
+        }
+        this
+    }
+
+    /// which history a buffer uses, they all share one unless `per_buffer` is set
+    pub fn key(&self, network: Option<&str>, name: &str) -> String {
+        match network {
+            _ if !self.per_buffer => String::new(),
+            Some(network) => format!("{}/{}", network, name),
+            None => name.to_string(),
+        }
+    }
+
+    pub fn get(&mut self, key: &str) -> Rc<RefCell<History>> {
+        let size = self.size;
+        let history = self
+            .map
+            .entry(key.to_string())
+            .or_insert_with(|| Rc::new(RefCell::new(History::with_size(size))));
+        Rc::clone(history)
+    }
+
+    /// writes out every history, the whole file is replaced
+    pub fn save(&self) {
+        if self.file.is_empty() {
+            return;
+        }
+
+        let mut data = String::new();
+        for (key, history) in &self.map {
+            for line in history.borrow().iter() {
+                data.push_str(key);
+                data.push('\t');
+                data.push_str(line);
+                data.push('\n');
+            }
+        }
+
+        if let Err(err) = write_private(Path::new(&self.file), data.as_bytes()) {
+            warn!("cannot save history: {}: {}", self.file, err);
+        }
+    }
+}
+
+// things that get sent with a password: `/pass`, `/oper`, anything for services
+fn is_secret(line: &str) -> bool {
+    let is_service = |target: &str| {
+        let nick = target.split('@').next().unwrap_or_default();
+        let nick = nick.to_ascii_lowercase();
+        nick.ends_with("serv") || nick == "ns"
+    };
+
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or_default().to_ascii_lowercase();
+    match command.as_str() {
+        "/pass" | "/oper" | "/ns" | "/nickserv" | "/cs" | "/chanserv" => true,
+        "/msg" => words.next().is_some_and(is_service),
+        _ => false,
+    }
+}
+
+// creates the directory it's in, and only the owner can read it
+fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // an older file could have been made with the default permissions
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    options.open(path)?.write_all(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find() {
+        let mut history = History::with_size(10);
+        for line in &["/join #a", "hello", "/join #b", "bye"] {
+            history.append(*line);
+        }
+
+        assert_eq!(history.find("join", 0), Some((1, "/join #b")));
+        assert_eq!(history.find("join", 2), Some((3, "/join #a")));
+        assert_eq!(history.find("join", 4), None);
+        assert_eq!(history.find("", 0), Some((0, "bye")));
+        assert_eq!(history.find("nope", 0), None);
+    }
+
+    #[test]
+    fn secrets() {
+        let mut history = History::new();
+        for line in &[
+            "/msg NickServ IDENTIFY hunter2",
+            "/msg nickserv@services.example.com identify hunter2",
+            "/msg ChanServ op #test",
+            "/pass hunter2",
+            "/OPER admin hunter2",
+            "/ns ghost test hunter2",
+            "/msg bob hi",
+            "nickserv is down",
+        ] {
+            history.append(*line);
+        }
+
+        let kept = history.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+        assert_eq!(kept, vec!["/msg bob hi", "nickserv is down"]);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = ::std::env::temp_dir().join(format!("riirc-history-{}", ::std::process::id()));
+        let file = dir.join("history");
+        let config = config::History {
+            file: file.to_string_lossy().into_owned(),
+            per_buffer: true,
+            size: 10,
+        };
+
+        let mut histories = Histories::new(&config);
+        let key = histories.key(Some("net"), "#test");
+        assert_eq!(key, "net/#test");
+        histories.get(&key).borrow_mut().append("hello");
+        histories.get(&key).borrow_mut().append("with\ttabs\tin it");
+        histories.get("*status").borrow_mut().append("/connect net");
+        histories.save();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let mut loaded = Histories::new(&config);
+        let lines = |histories: &mut Histories, key: &str| {
+            let history = histories.get(key);
+            let lines = history.borrow().iter().cloned().collect::<Vec<_>>();
+            lines
+        };
+        assert_eq!(
+            lines(&mut loaded, "net/#test"),
+            vec!["hello", "with\ttabs\tin it"]
+        );
+        assert_eq!(lines(&mut loaded, "*status"), vec!["/connect net"]);
+
+        // everything goes into the one history without `per_buffer`
+        let shared = config::History {
+            per_buffer: false,
+            ..config.clone()
+        };
+        let mut loaded = Histories::new(&shared);
+        assert_eq!(loaded.key(Some("net"), "#test"), "");
+        assert_eq!(lines(&mut loaded, "").len(), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
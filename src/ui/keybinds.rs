@@ -96,8 +96,18 @@ impl Default for Keybinds {
             (KeyRequest::Clear, "C-l".into()),
             (KeyRequest::RecallBackward, "A-p".into()),
             (KeyRequest::RecallForward, "A-n".into()),
+            (KeyRequest::SearchHistory, "C-r".into()),
+            (KeyRequest::Compose, "A-m".into()),
             //
             (KeyRequest::ToggleNickList, "A-k".into()),
+            (KeyRequest::NickListUp, "A-i".into()),
+            (KeyRequest::NickListDown, "A-j".into()),
+            //
+            (KeyRequest::PageUp, "".into()),
+            (KeyRequest::PageDown, "".into()),
+            (KeyRequest::ScrollTop, "A-t".into()),
+            (KeyRequest::ScrollBottom, "A-e".into()),
+            (KeyRequest::Search, "A-s".into()),
             //
             (KeyRequest::MoveForward, "C-f".into()),
             (KeyRequest::MoveBackward, "C-b".into()),
@@ -120,8 +130,16 @@ impl Default for Keybinds {
             (KeyRequest::SwapCaseStart, "".into()),
             (KeyRequest::SwapCaseEnd, "".into()),
             //
+            (KeyRequest::InsertBold, "C-t".into()),
+            (KeyRequest::InsertColor, "A-c".into()),
+            (KeyRequest::InsertItalic, "".into()),
+            (KeyRequest::InsertUnderline, "".into()),
+            (KeyRequest::InsertReverse, "".into()),
+            (KeyRequest::InsertReset, "C-o".into()),
+            //
             (KeyRequest::PrevBuffer, "C-p".into()),
             (KeyRequest::NextBuffer, "C-n".into()),
+            (KeyRequest::NextActive, "A-a".into()),
             //
             (KeyRequest::SwitchBuffer0, "C-0".into()),
             (KeyRequest::SwitchBuffer1, "C-1".into()),
@@ -144,12 +162,23 @@ pub enum KeyRequest {
     Clear,
 
     ToggleNickList,
+    NickListUp,
+    NickListDown,
+
+    PageUp,
+    PageDown,
+    ScrollTop,
+    ScrollBottom,
+    Search,
 
     PrevBuffer,
     NextBuffer,
+    NextActive,
 
     RecallBackward,
     RecallForward,
+    SearchHistory,
+    Compose,
 
     MoveForward,
     MoveBackward,
@@ -172,6 +201,13 @@ pub enum KeyRequest {
     SwapCaseStart,
     SwapCaseEnd,
 
+    InsertBold,
+    InsertColor,
+    InsertItalic,
+    InsertUnderline,
+    InsertReverse,
+    InsertReset,
+
     SwitchBuffer0,
     SwitchBuffer1,
     SwitchBuffer2,
@@ -227,9 +263,19 @@ impl KeyRequest {
             "Clear" => Clear,
             "RecallBackward" => RecallBackward,
             "RecallForward" => RecallForward,
+            "SearchHistory" => SearchHistory,
+            "Compose" => Compose,
             "ToggleNickList" => ToggleNickList,
+            "NickListUp" => NickListUp,
+            "NickListDown" => NickListDown,
+            "PageUp" => PageUp,
+            "PageDown" => PageDown,
+            "ScrollTop" => ScrollTop,
+            "ScrollBottom" => ScrollBottom,
+            "Search" => Search,
             "PrevBuffer" => PrevBuffer,
             "NextBuffer" => NextBuffer,
+            "NextActive" => NextActive,
             "MoveForward" => MoveForward,
             "MoveBackward" => MoveBackward,
             "MoveForwardWord" => MoveForwardWord,
@@ -248,6 +294,12 @@ impl KeyRequest {
             "SwapCaseBackwardWord" => SwapCaseBackwardWord,
             "SwapCaseStart" => SwapCaseStart,
             "SwapCaseEnd" => SwapCaseEnd,
+            "InsertBold" => InsertBold,
+            "InsertColor" => InsertColor,
+            "InsertItalic" => InsertItalic,
+            "InsertUnderline" => InsertUnderline,
+            "InsertReverse" => InsertReverse,
+            "InsertReset" => InsertReset,
             "SwitchBuffer0" => SwitchBuffer0,
             "SwitchBuffer1" => SwitchBuffer1,
             "SwitchBuffer2" => SwitchBuffer2,
@@ -274,9 +326,17 @@ impl request::Request {
             Clear => Request::Clear(true),
 
             ToggleNickList => Request::ToggleNickList,
+            NickListUp => Request::ScrollNickList(true),
+            NickListDown => Request::ScrollNickList(false),
+
+            PageUp => Request::Scroll(request::Scroll::PageUp),
+            PageDown => Request::Scroll(request::Scroll::PageDown),
+            ScrollTop => Request::Scroll(request::Scroll::Top),
+            ScrollBottom => Request::Scroll(request::Scroll::Bottom),
 
             PrevBuffer => Request::PrevBuffer,
             NextBuffer => Request::NextBuffer,
+            NextActive => Request::NextActive,
 
             SwitchBuffer0 => Request::SwitchBuffer(0),
             SwitchBuffer1 => Request::SwitchBuffer(1),
@@ -298,6 +358,7 @@ impl request::Request {
 impl request::Command {
     pub fn parse(kr: KeyRequest) -> Option<Self> {
         use self::KeyRequest::*;
+        use super::irc::format;
         use super::request::Command::*;
         use super::request::Move::*;
 
@@ -326,6 +387,13 @@ impl request::Command {
             SwapCaseBackwardWord => SwapCase(BackwardWord),
             SwapCaseStart => SwapCase(StartOfLine),
             SwapCaseEnd => SwapCase(EndOfLine),
+
+            InsertBold => Append(format::BOLD),
+            InsertColor => Append(format::COLOR),
+            InsertItalic => Append(format::ITALIC),
+            InsertUnderline => Append(format::UNDERLINE),
+            InsertReverse => Append(format::REVERSE),
+            InsertReset => Append(format::RESET),
             _ => return None,
         };
 
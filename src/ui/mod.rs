@@ -1,7 +1,7 @@
 extern crate pancurses;
 
 use super::irc::Command as IrcCommand;
-use super::{irc, ui, Config};
+use super::{config, daemon, irc, ui, Config};
 
 // TODO determine if these need to exist
 pub mod messagequeue;
@@ -11,19 +11,28 @@ pub mod boundedset;
 pub use self::boundedset::*;
 
 import!(
-    buffer,   //
-    colors,   //
-    commands, //
-    events,   //
-    history,  //
-    keybinds, //
-    output,   //
-    request,  //
-    state,    //
-    windows   //
+    buffer,     //
+    colors,     //
+    commands,   //
+    completion, //
+    events,     //
+    highlight,  //
+    history,    //
+    keybinds,   //
+    output,     //
+    remote,     //
+    request,    //
+    state,      //
+    theme,      //
+    windows     //
 );
 
 pub mod gui;
 
 pub use self::gui::Gui;
 pub use self::keybinds::*;
+pub use self::remote::Remote;
+
+// the daemon parses what it's sent the same way
+pub(crate) use self::commands::{client_request, Error as CommandError};
+pub(crate) use self::request::Request;
@@ -1,4 +1,5 @@
 use super::colors::*;
+use super::irc;
 use std::collections::BTreeMap;
 
 // TODO: use new types so they can be converted into intermediate outputs automatically
@@ -20,18 +21,65 @@ pub struct Output {
 }
 
 impl Output {
-    pub fn stamp() -> OutputBuilder {
-        use chrono::prelude::*;
-        let mut builder = OutputBuilder::new();
-        let now: DateTime<Local> = Local::now();
-        let ts = format!("{:02}{:02}{:02} ", now.hour(), now.minute(), now.second());
-        builder.add(&ts);
-        builder
-    }
-
     pub fn new() -> OutputBuilder {
         OutputBuilder::new()
     }
+
+    /// whether `needle` shows up anywhere, ignoring ascii case
+    pub fn contains(&self, needle: &str) -> bool {
+        !find_all(&self.data, needle).is_empty()
+    }
+
+    /// the same output with every `needle` (ignoring ascii case) shown reversed
+    pub fn highlight(&self, needle: &str) -> Output {
+        let found = find_all(&self.data, needle);
+        if found.is_empty() {
+            return self.clone();
+        }
+
+        let mut colors = vec![];
+        for (range, color) in &self.colors {
+            // split the range wherever a match starts or ends inside of it
+            let mut cuts = found
+                .iter()
+                .flat_map(|m| vec![m.start, m.end])
+                .filter(|&cut| cut > range.start && cut < range.end)
+                .collect::<Vec<_>>();
+            cuts.push(range.end);
+
+            let mut start = range.start;
+            for end in cuts {
+                let mut color = *color;
+                color.reverse = found.iter().any(|m| m.start <= start && start < m.end);
+                colors.push((start..end, color));
+                start = end;
+            }
+        }
+
+        Output {
+            data: self.data.clone(),
+            colors,
+        }
+    }
+}
+
+fn find_all(haystack: &str, needle: &str) -> Vec<::std::ops::Range<usize>> {
+    if needle.is_empty() {
+        return vec![];
+    }
+
+    // ascii lowercasing keeps the byte offsets the same
+    let haystack = haystack.to_ascii_lowercase();
+    let needle = needle.to_ascii_lowercase();
+
+    let mut found = vec![];
+    let mut pos = 0;
+    while let Some(start) = haystack[pos..].find(needle.as_str()) {
+        let start = pos + start;
+        pos = start + needle.len();
+        found.push(start..pos);
+    }
+    found
 }
 
 #[derive(Default)]
@@ -80,7 +128,13 @@ impl OutputBuilder {
         let entry = self.colors.entry(self.index);
         entry
             .or_insert_with(|| ColorPair::new(false, Color::White, Some(c)))
-            .bg = c;
+            .bg = Some(c);
+        self
+    }
+
+    /// the colors for whatever is added next
+    pub fn color(&mut self, c: ColorPair) -> &mut Self {
+        self.colors.insert(self.index, c);
         self
     }
 
@@ -98,6 +152,24 @@ impl OutputBuilder {
         self
     }
 
+    /// adds text with mirc formatting in it, on top of the color that was set for it
+    pub fn add_formatted<T>(&mut self, s: T) -> &mut Self
+    where
+        T: AsRef<str>,
+    {
+        let base = self
+            .colors
+            .get(&self.index)
+            .cloned()
+            .unwrap_or_else(|| ColorPair::new(false, Color::White, None));
+
+        for (text, style) in irc::format::parse(s.as_ref()) {
+            self.colors.insert(self.index, base.styled(&style));
+            self.add(text);
+        }
+        self
+    }
+
     pub fn build(&self) -> Output {
         Output {
             colors: self.colors.iter().zip(self.parts.iter()).fold(
@@ -114,3 +186,60 @@ impl OutputBuilder {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reversed(output: &Output) -> Vec<(::std::ops::Range<usize>, bool)> {
+        output
+            .colors
+            .iter()
+            .map(|(range, color)| (range.clone(), color.reverse))
+            .collect()
+    }
+
+    #[test]
+    fn finding() {
+        assert_eq!(find_all("abcABCabc", "ABC"), vec![0..3, 3..6, 6..9]);
+        assert_eq!(find_all("aaaa", "aa"), vec![0..2, 2..4]);
+        assert!(find_all("hello", "world").is_empty());
+        assert!(find_all("hello", "").is_empty());
+        // non-ascii text keeps its offsets
+        assert_eq!(find_all("héllo hello", "HELLO"), vec![7..12]);
+    }
+
+    #[test]
+    fn highlighting() {
+        let output = Output::new()
+            .fg(Color::Green)
+            .add("<bob> ")
+            .fg(Color::White)
+            .add("test TEST test")
+            .build();
+
+        let highlighted = output.highlight("test");
+        assert_eq!(highlighted.data, output.data);
+        assert_eq!(
+            reversed(&highlighted),
+            vec![
+                (0..6, false),
+                (6..10, true),
+                (10..11, false),
+                (11..15, true),
+                (15..16, false),
+                (16..20, true),
+            ]
+        );
+
+        // a match across two colors reverses both parts
+        let highlighted = output.highlight("> t");
+        assert_eq!(
+            reversed(&highlighted),
+            vec![(0..4, false), (4..6, true), (6..7, true), (7..20, false)]
+        );
+
+        assert_eq!(output.highlight("nope"), output);
+        assert!(output.contains("BOB"));
+    }
+}
@@ -0,0 +1,92 @@
+use super::daemon::{self, read_packet, write_packet, Packet, VERSION};
+
+use crossbeam_channel as channel;
+use std::cell::{Cell, RefCell};
+use std::io::{BufReader, Read, Write};
+use std::thread;
+
+/// The ui's end of a daemon connection
+pub struct Remote {
+    writer: RefCell<Box<dyn Write + Send>>,
+    packets: channel::Receiver<Packet>,
+    last_seen: Cell<u64>,
+}
+
+impl Remote {
+    /// `last_seen` is the newest line this ui has, so the daemon only replays what's missing
+    pub fn attach<R, W>(reader: R, writer: W, last_seen: u64) -> Result<Self, daemon::Error>
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let mut reader = BufReader::new(reader);
+        let mut writer = Box::new(writer);
+
+        let hello = Packet::Hello {
+            version: VERSION,
+            last_seen,
+        };
+        write_packet(&mut writer, &hello).map_err(daemon::Error::Io)?;
+
+        match read_packet(&mut reader).map_err(daemon::Error::Io)? {
+            Some(Packet::Welcome { version }) if version == VERSION => {}
+            Some(Packet::Welcome { version }) => Err(daemon::Error::VersionMismatch(version))?,
+            Some(Packet::Error { message }) => Err(daemon::Error::Rejected(message))?,
+            Some(packet) => Err(daemon::Error::UnexpectedPacket(packet))?,
+            None => Err(daemon::Error::Disconnected)?,
+        }
+
+        let (tx, rx) = channel::unbounded();
+        thread::spawn(move || {
+            let message = loop {
+                match read_packet(&mut reader) {
+                    Ok(Some(packet)) => tx.send(packet),
+                    Ok(None) => break "the daemon went away".to_string(),
+                    Err(err) => break err.to_string(),
+                }
+            };
+            tx.send(Packet::Error { message });
+        });
+
+        Ok(Self {
+            writer: RefCell::new(writer),
+            packets: rx,
+            last_seen: Cell::new(last_seen),
+        })
+    }
+
+    pub fn try_recv(&self) -> Option<Packet> {
+        let packet = self.packets.try_recv()?;
+        if let Packet::Line { seq, .. } = packet {
+            self.last_seen.set(seq);
+        }
+        Some(packet)
+    }
+
+    pub fn last_seen(&self) -> u64 {
+        self.last_seen.get()
+    }
+
+    pub fn send_input(
+        &self,
+        network: Option<&str>,
+        buffer: impl Into<String>,
+        data: impl Into<String>,
+    ) {
+        self.send(&Packet::Input {
+            network: network.unwrap_or_default().to_string(),
+            buffer: buffer.into(),
+            data: data.into(),
+        })
+    }
+
+    pub fn detach(&self) {
+        self.send(&Packet::Detach)
+    }
+
+    fn send(&self, packet: &Packet) {
+        if let Err(err) = write_packet(&mut *self.writer.borrow_mut(), packet) {
+            warn!("cannot write to the daemon: {}", err)
+        }
+    }
+}
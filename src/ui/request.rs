@@ -3,21 +3,41 @@ use super::output::Output;
 #[derive(Debug, PartialEq)]
 pub enum Request {
     Clear(bool),
-    Join(String),
-    Part(String),
-    Quit(Option<String>),
+    Resize,
+    RefreshStatus,
+    Join(String, String, Option<String>), // network, channel, key
+    Part(String, String),                 // network, channel
+    Query(String, String),                // network, nick
+    Close(String, String),                // network, buffer
+    Privmsg(String, String, String),      // network, target, message
+    Quit(String, Option<String>),         // network
+    Disconnect(String),                   // network
 
     ToggleNickList,
+    ScrollNickList(bool), // up
     ClearHistory(usize),
 
+    Scroll(Scroll),
+    Search(Option<String>),
+    SearchNext,
+
     SwitchBuffer(usize),
     NextBuffer,
     PrevBuffer,
+    NextActive,
 
     Queue(usize, Output),  // buffer index
     Target(usize, Output), // buffer index
 }
 
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Scroll {
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Command {
     Delete(Move),
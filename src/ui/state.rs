@@ -3,15 +3,17 @@ use super::request::Request;
 use super::*;
 
 use crossbeam_channel as channel;
-use std::cell::RefCell;
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::RwLock;
 
 type ErrorChannel = channel::Receiver<irc::Error>;
 
+// keyed by network name
 struct Inner {
-    client: Option<Rc<irc::Client>>,
-    errors: Option<Rc<ErrorChannel>>,
+    clients: HashMap<String, Rc<irc::Client>>,
+    errors: HashMap<String, Rc<ErrorChannel>>,
 }
 
 pub struct State {
@@ -19,52 +21,126 @@ pub struct State {
     queue: Rc<MessageQueue<Request>>,
     config: Rc<RefCell<Config>>,
     buffers: Rc<Buffers>,
+    highlighter: RefCell<Highlighter>,
+    theme: RefCell<Theme>,
+    // how many lines have been composed, while composing
+    composing: Cell<Option<usize>>,
 }
 
 impl State {
     pub fn new(queue: Rc<MessageQueue<Request>>, config: Rc<RefCell<Config>>) -> Self {
+        let highlighter = Highlighter::new(&config.borrow().highlight);
+        let theme = Theme::new(&config.borrow().theme);
         Self {
             inner: RwLock::new(Inner {
-                client: None,
-                errors: None,
+                clients: HashMap::new(),
+                errors: HashMap::new(),
             }),
             buffers: Rc::new(Buffers::new(Rc::clone(&queue))),
+            highlighter: RefCell::new(highlighter),
+            theme: RefCell::new(theme),
+            composing: Cell::new(None),
             config,
             queue,
         }
     }
 
-    pub fn reset(&self) {
-        warn!("implement reset")
+    /// drops the network's client, the buffers are kept around
+    pub fn reset(&self, network: impl AsRef<str>) {
+        let inner = &mut self.inner.write().unwrap();
+        inner.clients.remove(network.as_ref());
+        inner.errors.remove(network.as_ref());
     }
 
     pub fn buffers(&self) -> Rc<Buffers> {
         Rc::clone(&self.buffers)
     }
 
+    pub fn composing(&self) -> Option<usize> {
+        self.composing.get()
+    }
+
+    /// the status bar shows how many lines are waiting to be sent
+    pub fn set_composing(&self, lines: Option<usize>) {
+        self.composing.set(lines);
+        self.queue.enqueue(Request::RefreshStatus);
+    }
+
     pub fn config(&self) -> Rc<RefCell<Config>> {
         Rc::clone(&self.config)
     }
 
+    /// swaps in a freshly loaded config
+    pub fn set_config(&self, config: Config) {
+        *self.highlighter.borrow_mut() = Highlighter::new(&config.highlight);
+        *self.theme.borrow_mut() = Theme::new(&config.theme);
+        self.config.replace(config);
+    }
+
+    /// whether `text` mentions `nick`, or anything else the config says to highlight
+    pub fn is_highlight(&self, nick: Option<&str>, text: &str) -> bool {
+        self.highlighter.borrow().is_match(nick, text)
+    }
+
+    pub fn theme(&self) -> Ref<'_, Theme> {
+        self.theme.borrow()
+    }
+
+    /// the network of the active buffer, or the only network if there's just one
+    pub fn network(&self) -> Option<String> {
+        let (_, buf) = self.buffers.current();
+        if let Some(network) = buf.network() {
+            return Some(network.to_string());
+        }
+
+        let inner = self.inner.read().unwrap();
+        if inner.clients.len() == 1 {
+            return inner.clients.keys().next().cloned();
+        }
+        None
+    }
+
+    /// the client for the active network
     pub fn client(&self) -> Option<Rc<irc::Client>> {
+        self.client_for(self.network()?)
+    }
+
+    pub fn client_for(&self, network: impl AsRef<str>) -> Option<Rc<irc::Client>> {
+        let inner = self.inner.read().unwrap();
+        inner.clients.get(network.as_ref()).map(Rc::clone)
+    }
+
+    /// every connected network, sorted by name
+    pub fn clients(&self) -> Vec<(String, Rc<irc::Client>)> {
         let inner = self.inner.read().unwrap();
-        inner.client.as_ref().map(Rc::clone)
+        let mut clients = inner
+            .clients
+            .iter()
+            .map(|(k, v)| (k.clone(), Rc::clone(v)))
+            .collect::<Vec<_>>();
+        clients.sort_by(|a, b| a.0.cmp(&b.0));
+        clients
     }
 
-    pub fn set_client(&self, client: irc::Client) {
+    pub fn set_client(&self, network: impl Into<String>, client: irc::Client) {
+        let network = network.into();
         let inner = &mut self.inner.write().unwrap();
         let errors = client.errors();
-        inner.client = Some(Rc::new(client));
-        inner.errors = Some(Rc::new(errors));
+        inner.clients.insert(network.clone(), Rc::new(client));
+        inner.errors.insert(network, Rc::new(errors));
     }
 
     pub fn queue(&self, req: Request) {
         self.queue.enqueue(req);
     }
 
-    pub fn read_errors(&self) -> Option<Rc<ErrorChannel>> {
+    pub fn read_errors(&self) -> Vec<(String, Rc<ErrorChannel>)> {
         let inner = self.inner.read().unwrap();
-        inner.errors.as_ref().map(Rc::clone)
+        inner
+            .errors
+            .iter()
+            .map(|(k, v)| (k.clone(), Rc::clone(v)))
+            .collect()
     }
 
     pub fn read_requests(&self) -> Vec<Request> {
@@ -0,0 +1,391 @@
+use super::colors::{Color, ColorPair};
+use super::config;
+use super::irc;
+use super::output::{Output, OutputBuilder};
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+// the placeholders that are always a nick
+const NICKS: &[&str] = &["nick", "new"];
+
+/// What's needed to color the nicks in a line
+#[derive(Default)]
+pub struct Nicks {
+    pub casemapping: irc::CaseMapping,
+    /// whoever is in here gets colored when they're mentioned in the `{text}`
+    pub channel: Option<Arc<irc::Channel>>,
+}
+
+/// How each kind of line looks, built from the config's `[theme]`
+#[derive(Debug)]
+pub struct Theme {
+    timestamp: String,
+    formats: HashMap<String, String>,
+    colors: HashMap<String, ColorPair>,
+    nick_colors: Vec<Color>,
+}
+
+impl Theme {
+    /// colors that can't be parsed are skipped, and a broken timestamp uses the default one
+    pub fn new(config: &config::Theme) -> Self {
+        let colors = config
+            .colors
+            .iter()
+            .filter_map(|(name, color)| match ColorPair::parse(color) {
+                Some(pair) => Some((name.clone(), pair)),
+                None => {
+                    warn!("invalid theme color: {}: {}", name, color);
+                    None
+                }
+            })
+            .collect();
+
+        let nick_colors = config
+            .nick_colors
+            .iter()
+            .filter_map(|color| match Color::parse(color) {
+                Some(color) => Some(color),
+                None => {
+                    warn!("invalid nick color: {}", color);
+                    None
+                }
+            })
+            .collect();
+
+        // chrono panics when it formats a broken one
+        let timestamp = if StrftimeItems::new(&config.timestamp).any(|item| item == Item::Error) {
+            warn!("invalid theme timestamp: {}", config.timestamp);
+            config::Theme::default().timestamp
+        } else {
+            config.timestamp.clone()
+        };
+
+        Self {
+            timestamp,
+            formats: config.formats.iter().cloned().collect(),
+            colors,
+            nick_colors,
+        }
+    }
+
+    pub fn stamp(&self) -> OutputBuilder {
+        self.stamp_at(Local::now())
+    }
+
+    pub fn stamp_at<Tz: TimeZone>(&self, time: DateTime<Tz>) -> OutputBuilder
+    where
+        Tz::Offset: fmt::Display,
+    {
+        let time = time.with_timezone(&Local);
+        let mut output = Output::new();
+        output
+            .color(self.color("timestamp"))
+            .add(time.format(&self.timestamp).to_string());
+        output
+    }
+
+    /// the color for `name`, or white if the theme doesn't have one
+    pub fn color(&self, name: &str) -> ColorPair {
+        self.colors
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| Color::White.into())
+    }
+
+    /// the same color for a nick every time, however the network lets it be written
+    pub fn nick_color(&self, nick: &str, casemapping: irc::CaseMapping) -> Option<ColorPair> {
+        if self.nick_colors.is_empty() {
+            return None;
+        }
+
+        let hash = casemapping.fold(nick).bytes().fold(0u32, |hash, b| {
+            hash.wrapping_mul(31).wrapping_add(u32::from(b))
+        });
+        let color = self.nick_colors[hash as usize % self.nick_colors.len()];
+        Some(color.into())
+    }
+
+    /// adds the line for `event`, with its `{placeholders}` filled in from `vars`
+    pub fn render(
+        &self,
+        output: &mut OutputBuilder,
+        event: &str,
+        vars: &[(&str, &str)],
+        nicks: &Nicks,
+    ) {
+        let format = match self.formats.get(event) {
+            Some(format) => format.as_str(),
+            None => {
+                warn!("no theme format for: {}", event);
+                return;
+            }
+        };
+
+        // the text around the placeholders uses the event's color
+        let base = self
+            .colors
+            .get(event)
+            .cloned()
+            .unwrap_or_else(|| self.color("text"));
+
+        let mut rest = format;
+        while let Some(start) = rest.find('{') {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => break,
+            };
+
+            let name = &rest[start + 1..end];
+            match vars.iter().find(|(var, _)| *var == name) {
+                Some((_, value)) => {
+                    add(output, base, &rest[..start]);
+                    let color = self.var_color(event, name, value, base, nicks);
+                    if name == "text" {
+                        self.add_mentions(output, color, value, nicks);
+                    } else {
+                        add(output, color, value);
+                    }
+                }
+                None => add(output, base, &rest[..=end]),
+            }
+            rest = &rest[end + 1..];
+        }
+        add(output, base, rest);
+    }
+
+    // `event_name`, then the nick's own color, then `name`, then whatever the rest of the line is
+    fn var_color(
+        &self,
+        event: &str,
+        name: &str,
+        value: &str,
+        base: ColorPair,
+        nicks: &Nicks,
+    ) -> ColorPair {
+        if let Some(color) = self.colors.get(&format!("{}_{}", event, name)) {
+            return *color;
+        }
+        if NICKS.contains(&name) {
+            if let Some(color) = self.nick_color(value, nicks.casemapping) {
+                return color;
+            }
+        }
+        self.colors.get(name).cloned().unwrap_or(base)
+    }
+
+    // like `add`, but anyone in the channel gets their nick color
+    fn add_mentions(
+        &self,
+        output: &mut OutputBuilder,
+        color: ColorPair,
+        text: &str,
+        nicks: &Nicks,
+    ) {
+        let channel = match &nicks.channel {
+            Some(channel) if !self.nick_colors.is_empty() => channel,
+            _ => return add(output, color, text),
+        };
+
+        for (run, style) in irc::format::parse(text) {
+            for (word, is_word) in words(&run) {
+                let color = match self.nick_color(word, nicks.casemapping) {
+                    Some(nick) if is_word && channel.contains(word) => nick,
+                    _ => color,
+                };
+                output.color(color.styled(&style)).add(word);
+            }
+        }
+    }
+}
+
+// splits `text` into the parts that could be a nick and the parts between them
+fn words(text: &str) -> Vec<(&str, bool)> {
+    let is_nick = |c: char| c.is_alphanumeric() || "[]\\`_^{|}-".contains(c);
+
+    let mut words = vec![];
+    let mut start = 0;
+    let mut last = None;
+    for (i, c) in text.char_indices() {
+        let nick = is_nick(c);
+        if last == Some(!nick) {
+            words.push((&text[start..i], !nick));
+            start = i;
+        }
+        last = Some(nick);
+    }
+    if let Some(nick) = last {
+        words.push((&text[start..], nick));
+    }
+    words
+}
+
+fn add(output: &mut OutputBuilder, color: ColorPair, text: &str) {
+    if !text.is_empty() {
+        output.color(color).add_formatted(text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::irc::CaseMapping;
+    use super::*;
+
+    fn theme() -> Theme {
+        Theme::new(&config::Theme::default())
+    }
+
+    fn custom(formats: &[(&str, &str)], colors: &[(&str, &str)]) -> Theme {
+        let pairs = |list: &[(&str, &str)]| {
+            list.iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        Theme::new(&config::Theme {
+            timestamp: "%H:%M ".into(),
+            nick_colors: vec!["cyan".into()],
+            formats: pairs(formats),
+            colors: pairs(colors),
+        })
+    }
+
+    // each run of text with the color it has
+    fn render(
+        theme: &Theme,
+        event: &str,
+        vars: &[(&str, &str)],
+        nicks: &Nicks,
+    ) -> Vec<(String, Color)> {
+        let mut output = Output::new();
+        theme.render(&mut output, event, vars, nicks);
+        let output = output.build();
+        output
+            .colors
+            .iter()
+            .map(|(range, color)| (output.data[range.clone()].to_string(), color.fg))
+            .collect()
+    }
+
+    fn run(text: &str, color: Color) -> (String, Color) {
+        (text.to_string(), color)
+    }
+
+    #[test]
+    fn nick_colors() {
+        let theme = theme();
+
+        // `[` is the upper case `{` in rfc1459
+        let color = theme.nick_color("Foo[", CaseMapping::Rfc1459);
+        assert!(color.is_some());
+        assert_eq!(color, theme.nick_color("foo{", CaseMapping::Rfc1459));
+        assert_eq!(color, theme.nick_color("FOO{", CaseMapping::StrictRfc1459));
+
+        // but not in ascii
+        let color = theme.nick_color("Foo[", CaseMapping::Ascii);
+        assert_eq!(color, theme.nick_color("foo[", CaseMapping::Ascii));
+        assert_ne!(color, theme.nick_color("foo{", CaseMapping::Ascii));
+
+        let theme = Theme::new(&config::Theme {
+            nick_colors: vec![],
+            ..config::Theme::default()
+        });
+        assert_eq!(theme.nick_color("foo", CaseMapping::Rfc1459), None);
+    }
+
+    #[test]
+    fn formats() {
+        let theme = custom(
+            &[
+                ("join", "{nick} joined {channel}"),
+                ("odd", "{missing} {nick} {unclosed"),
+            ],
+            &[("join", "green"), ("channel", "yellow")],
+        );
+        let nicks = Nicks::default();
+
+        let vars = [("nick", "bob"), ("channel", "#a")];
+        assert_eq!(
+            render(&theme, "join", &vars, &nicks),
+            vec![
+                run("bob", Color::Cyan),
+                run(" joined ", Color::Green),
+                run("#a", Color::Yellow),
+            ]
+        );
+
+        // placeholders without a value, or without an end, are left as they are
+        assert_eq!(
+            render(&theme, "odd", &[("nick", "bob")], &nicks),
+            vec![
+                run("{missing}", Color::White),
+                run(" ", Color::White),
+                run("bob", Color::Cyan),
+                run(" {unclosed", Color::White),
+            ]
+        );
+
+        assert!(render(&theme, "part", &[("nick", "bob")], &nicks).is_empty());
+    }
+
+    #[test]
+    fn var_colors() {
+        let theme = custom(
+            &[("nick", "{nick} is now {new}")],
+            &[("nick_new", "red"), ("new", "blue")],
+        );
+        let vars = [("nick", "bob"), ("new", "robert")];
+
+        // the event's own color for the placeholder wins over the nick color
+        assert_eq!(
+            render(&theme, "nick", &vars, &Nicks::default()),
+            vec![
+                run("bob", Color::Cyan),
+                run(" is now ", Color::White),
+                run("robert", Color::Red),
+            ]
+        );
+    }
+
+    #[test]
+    fn mentions() {
+        let theme = custom(&[("privmsg", "<{nick}> {text}")], &[]);
+        let channel = irc::Channel::new("#test", &irc::ISupport::new());
+        channel.add("alice");
+        let nicks = Nicks {
+            casemapping: CaseMapping::Rfc1459,
+            channel: Some(Arc::new(channel)),
+        };
+
+        let vars = [("nick", "bob"), ("text", "hi alice, alicea")];
+        assert_eq!(
+            render(&theme, "privmsg", &vars, &nicks),
+            vec![
+                run("<", Color::White),
+                run("bob", Color::Cyan),
+                run("> ", Color::White),
+                run("hi", Color::White),
+                run(" ", Color::White),
+                run("alice", Color::Cyan),
+                run(", ", Color::White),
+                run("alicea", Color::White),
+            ]
+        );
+    }
+
+    #[test]
+    fn timestamps() {
+        let theme = custom(&[], &[]);
+        assert_eq!(theme.timestamp, "%H:%M ");
+
+        // a bad one would panic when it's used
+        let theme = Theme::new(&config::Theme {
+            timestamp: "%H%Q ".into(),
+            ..config::Theme::default()
+        });
+        assert_eq!(theme.timestamp, config::Theme::default().timestamp);
+        assert_eq!(theme.stamp().build().data.len(), "000000 ".len());
+    }
+}
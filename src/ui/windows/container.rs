@@ -1,57 +1,50 @@
 use super::*;
 use std::cell::RefCell;
+use std::io::{self, Write};
 
 pub struct Container {
     window: Rc<Window>,
     input: Rc<RefCell<Input>>,
     output: Rc<Output>,
     nicklist: Rc<Nicklist>,
+    status: Rc<Status>,
     ctx: Rc<Context>,
     queue: Rc<ui::MessageQueue<ui::Request>>,
 }
 
 impl Container {
     pub fn new(state: Rc<ui::State>) -> Self {
+        let container = pancurses::initscr();
+        pancurses::start_color();
+        pancurses::use_default_colors();
+
         // each window should have its own message queue
         // impl_recv can merge them into the container queue
         let queue = Rc::new(ui::MessageQueue::new());
         let ctx = Rc::new(Context {
             state: Rc::clone(&state),
             queue: Rc::clone(&queue),
+            colors: RefCell::new(ui::Palette::new()),
         });
 
-        let container = pancurses::initscr();
-        pancurses::start_color();
-        pancurses::use_default_colors();
-
-        let max = pancurses::COLOR_PAIRS();
-        let (l, r, s, h) = if max >= 16 {
-            (0x0F, 0xF0, 0x04, 0x10)
-        } else {
-            (0x07, 0x38, 0x03, 0x08)
-        };
-
-        for n in (0..max).map(|s| s as i16) {
-            if n < h {
-                pancurses::init_pair(n, n, -1);
-            } else {
-                pancurses::init_pair(n, n & l, (n & r) >> s);
-            }
-        }
-
         pancurses::curs_set(1);
         pancurses::noecho();
+        bracketed_paste(true);
 
-        let window = Rc::new(container.into());
-        let input = Input::new(Rc::clone(&window), Rc::clone(&ctx));
-        let output = Output::new(Rc::clone(&window), Rc::clone(&ctx));
-        let nicklist = Nicklist::new(Rc::clone(&window), Rc::clone(&ctx));
+        let window: Rc<Window> = Rc::new(container.into());
+        let layout = Layout::new(window.get_max_yx(), false);
+        let input = Input::new(Rc::clone(&window), Rc::clone(&ctx), layout.input);
+        let output = Output::new(Rc::clone(&window), Rc::clone(&ctx), layout.output);
+        let nicklist = Nicklist::new(Rc::clone(&window), Rc::clone(&ctx), layout.nicklist);
+        let status = Status::new(Rc::clone(&window), Rc::clone(&ctx), layout.status);
+        status.refresh();
 
         Self {
             window,
             input: Rc::new(RefCell::new(input)),
             output: Rc::new(output),
             nicklist: Rc::new(nicklist),
+            status: Rc::new(status),
             ctx,
             queue,
         }
@@ -77,6 +70,52 @@ impl Container {
     pub fn nicklist(&self) -> Rc<Nicklist> {
         Rc::clone(&self.nicklist)
     }
+
+    pub fn status(&self) -> Rc<Status> {
+        Rc::clone(&self.status)
+    }
+
+    /// shows or hides the nicklist, the output is made narrower to make room for it
+    pub fn toggle_nicklist(&mut self) {
+        self.nicklist.toggle();
+        self.output.resize(self.layout().output);
+        ui::Outputter::clear(&*self.output);
+    }
+
+    /// lays every window out again for the terminal's new size.
+    /// the output is left empty, the active buffer has to be displayed again
+    pub fn resize(&mut self) {
+        self.window.erase();
+        let layout = self.layout();
+        self.output.resize(layout.output);
+        ui::Outputter::clear(&*self.output);
+        self.nicklist.resize(layout.nicklist);
+        self.status.resize(layout.status);
+        self.input.borrow_mut().resize(layout.input);
+    }
+
+    fn layout(&self) -> Layout {
+        Layout::new(self.window.get_max_yx(), self.nicklist.is_visible())
+    }
+
+    fn window(&self) -> Rc<Window> {
+        Rc::clone(&self.window)
+    }
 }
 
 impl_recv!(Container);
+
+impl Drop for Container {
+    fn drop(&mut self) {
+        bracketed_paste(false);
+    }
+}
+
+// has the terminal wrap pastes in markers, so they can be told apart from typing
+fn bracketed_paste(enable: bool) {
+    let code = if enable { "\x1b[?2004h" } else { "\x1b[?2004l" };
+    let mut stdout = io::stdout();
+    if let Err(err) = write!(stdout, "{}", code).and_then(|_| stdout.flush()) {
+        debug!("cannot set bracketed paste: {}", err);
+    }
+}
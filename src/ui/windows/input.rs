@@ -1,52 +1,195 @@
 use super::*;
-use std::sync::RwLock;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// what the terminal wraps a paste in, once bracketed paste is turned on
+const PASTE_START: &str = "\x1b[200~";
+const PASTE_END: &str = "\x1b[201~";
+
+// milliseconds without a key before a paste is over. a bracketed one should end itself
+const PASTE_GAP: u64 = 50;
+const BRACKETED_PASTE_GAP: u64 = 1000;
+
+// keys that have to come in right behind an enter for it to be a paste,
+// someone typing quickly only gets a couple in
+const PASTE_MIN: usize = 8;
 
 pub struct Input {
     parent: Rc<Window>,
     window: Rc<Window>,
     ctx: Rc<Context>,
     buffer: InputBuffer<Window>,
-    history: RwLock<ui::History>,
+    histories: ui::Histories,
+    // which of the histories the line is using
+    history_key: Option<String>,
+    // the query, while searching the output
+    search: Option<String>,
+    completion: Option<ui::Completion>,
+    // the slash commands, for completing them
+    commands: Vec<&'static str>,
+    // keys that were read ahead, to see what came after a key
+    pending: VecDeque<pancurses::Input>,
+    // a paste that's still coming in
+    burst: Option<Burst>,
+    // lines waiting on whether to send them
+    paste: Option<Vec<String>>,
+    // the lines so far, in compose mode
+    compose: Option<Vec<String>>,
+}
+
+// keys that came in together, split into lines once they stop coming
+struct Burst {
+    text: String,
+    // how much of `text` was already there when the keys started coming
+    typed: usize,
+    // what was after the cursor when the paste started
+    suffix: String,
+    bracketed: bool,
+    last: Instant,
+}
+
+// what a burst turns out to be, once it's over
+#[derive(Debug, PartialEq)]
+enum Pasted {
+    // more than one line, which have to be confirmed
+    Lines(Vec<String>),
+    // a single line for the input, and where the cursor goes in it
+    Line(String, usize),
+    // just fast typing: the line enter was pressed on, and the keys after it
+    Typed(String, String),
+}
+
+impl Burst {
+    fn split(&self) -> Pasted {
+        if !self.bracketed {
+            let rest = &self.text[self.typed..];
+            // another enter in there makes it a paste, however short the lines are
+            let enters = rest.contains(&['\n', '\r'][..]);
+            if !enters && rest.chars().count() < PASTE_MIN {
+                let line = self.text[..self.typed].trim_end_matches(&['\n', '\r'][..]);
+                return Pasted::Typed(line.to_string(), rest.to_string());
+            }
+        }
+
+        let text = format!("{}{}", self.text, self.suffix);
+        let mut lines = text
+            .split(&['\n', '\r'][..])
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+        if lines.len() > 1 {
+            return Pasted::Lines(lines);
+        }
+
+        let line = lines.pop().unwrap_or_default();
+        let cursor = line
+            .chars()
+            .count()
+            .saturating_sub(self.suffix.chars().count());
+        Pasted::Line(line, cursor)
+    }
 }
 
 impl Input {
-    pub fn new(parent: Rc<Window>, ctx: Rc<Context>) -> Self {
-        let (h, w) = parent.get_max_yx();
-        let window = parent
-            .subwin(1, w, h - 1, 0)
-            .expect("create input subwindow");
-        window.nodelay(true);
-        window.keypad(true);
+    pub fn new(parent: Rc<Window>, ctx: Rc<Context>, rect: Rect) -> Self {
+        let window = Self::create(&parent, rect);
         let width = window.get_max_x() as usize;
 
-        let window = Rc::new(window.into());
+        let window = Rc::new(window);
+        let histories = ui::Histories::new(&ctx.state.config().borrow().history);
         Self {
             parent,
             window: Rc::clone(&window),
             ctx,
-            history: RwLock::new(ui::History::new()),
+            histories,
+            history_key: None,
             buffer: InputBuffer::new(width, window),
+            search: None,
+            completion: None,
+            commands: vec![],
+            pending: VecDeque::new(),
+            burst: None,
+            paste: None,
+            compose: None,
         }
     }
 
+    pub fn set_commands(&mut self, commands: Vec<&'static str>) {
+        self.commands = commands;
+    }
+
     pub fn read_input(&mut self) -> ui::ReadType {
         use pancurses::Input::*;
-        match self.window.getch() {
-            Some(Character(ch)) => self.handle_input_key(ch),
+        if let Some(burst) = self.burst.take() {
+            return self.read_burst(burst);
+        }
+
+        let input = match self.next_key() {
+            Some(input) => input,
+            None => return ui::ReadType::None,
+        };
+
+        self.sync_history();
+        match input {
+            input if self.paste.is_some() => self.handle_paste_key(input),
+            input if self.search.is_some() => self.handle_search_key(input),
+            input if self.buffer.is_searching() => self.handle_history_search_key(input),
+            // more keys right behind an enter could be a paste
+            Character('\n') | Character('\r') if self.key_waiting() => {
+                let mut text = self.buffer.line().iter().collect::<String>();
+                text.push('\n');
+                self.start_burst(text, String::new(), false)
+            }
+            Character('\x1b') if self.paste_started() => {
+                let (before, after) = self.buffer.line().split_at(self.buffer.position());
+                let (before, after) = (before.iter().collect(), after.iter().collect());
+                self.start_burst(before, after, true)
+            }
+            Character(ch) => self.handle_input_key(ch),
             // TODO discriminate between bad keys better
-            Some(KeyBTab) => self.handle_input_key('\u{ECED}'),
-            Some(ch) => self.handle_other_key(ch),
-            _ => ui::ReadType::None,
+            KeyBTab => self.handle_input_key('\u{ECED}'),
+            ch => self.handle_other_key(ch),
+        }
+    }
+
+    /// keeps the line and where the cursor is in it
+    pub fn resize(&mut self, rect: Rect) {
+        self.window = Rc::new(Self::create(&self.parent, rect));
+        let width = self.window.get_max_x() as usize;
+        self.buffer.resize(width, Rc::clone(&self.window));
+        if self.search.is_some() {
+            self.draw_search();
         }
+        if self.paste.is_some() {
+            self.draw_paste();
+        }
+        self.window.refresh();
     }
 
     pub fn add_history(&mut self) {
-        self.buffer.add_history()
+        self.buffer.add_history();
+        self.histories.save();
     }
 
-    pub fn clear_history(&mut self) {
+    /// forgets what was typed into the buffer, which is everything unless it has its own history
+    pub fn clear_history(&mut self, network: Option<&str>, name: &str) {
         trace!("clearing history");
-        self.buffer.clear_history();
+        let key = self.histories.key(network, name);
+        self.histories.get(&key).borrow_mut().clear();
+        self.histories.save();
+    }
+
+    // uses the active buffer's history, when each buffer has its own
+    fn sync_history(&mut self) {
+        let key = {
+            let (_, buf) = self.ctx.state.buffers().current();
+            self.histories.key(buf.network(), &buf.name())
+        };
+        if self.history_key.as_ref() != Some(&key) {
+            self.buffer.set_history(self.histories.get(&key));
+            self.history_key = Some(key);
+        }
     }
 
     pub fn clear_input(&mut self) {
@@ -73,6 +216,24 @@ impl Input {
 
             KeyDC => &ui::Command::Delete(ui::Move::Forward),
 
+            KeyResize => {
+                self.ctx.state.queue(ui::Request::Resize);
+                return ui::ReadType::None;
+            }
+
+            KeyPPage => {
+                self.ctx
+                    .state
+                    .queue(ui::Request::Scroll(ui::Scroll::PageUp));
+                return ui::ReadType::None;
+            }
+            KeyNPage => {
+                self.ctx
+                    .state
+                    .queue(ui::Request::Scroll(ui::Scroll::PageDown));
+                return ui::ReadType::None;
+            }
+
             KeyF1 | KeyF2 | KeyF3 | KeyF4 | KeyF5 | KeyF6 | KeyF7 | KeyF8 | KeyF9 | KeyF10
             | KeyF11 | KeyF12 => return ui::ReadType::FKey(input),
             key => {
@@ -97,26 +258,396 @@ impl Input {
             }
             (None, Enter) => {
                 let buf = self.buffer.line().into_iter().collect();
+                if self.compose.is_some() {
+                    return self.compose_line(buf);
+                }
                 return ui::ReadType::Line(buf);
             }
+            (None, Tab) => return self.complete(true),
+            (Shift, Tab) => return self.complete(false),
             _ => {}
         }
 
-        if let Some(req) = {
-            let keybind = ui::KeyType::from(*key);
-            self.ctx.state.config().borrow().keybinds.get(&keybind)
-        } {
+        if let Some(req) = self.keybind(key) {
             trace!("req: {:?}", req);
-            if let Some(cmd) = ui::Request::parse(*req) {
+            if req == ui::KeyRequest::Search {
+                self.search = Some(String::new());
+                self.draw_search();
+            }
+            if req == ui::KeyRequest::SearchHistory {
+                self.buffer.search_history();
+                self.window.refresh();
+            }
+            if req == ui::KeyRequest::Compose {
+                self.toggle_compose();
+            }
+            if let Some(cmd) = ui::Request::parse(req) {
                 self.ctx.state.queue(cmd);
             }
-            if let Some(cmd) = ui::Command::parse(*req) {
+            if let Some(cmd) = ui::Command::parse(req) {
                 self.buffer.handle_command(&cmd);
             }
         }
         ui::ReadType::None
     }
 
+    // while searching, keys edit the query rather than the line
+    fn handle_search_key(&mut self, input: pancurses::Input) -> ui::ReadType {
+        use super::ui::{KeyKind::*, Mod};
+
+        let (ch, key) = match input {
+            pancurses::Input::Character(ch) => match ui::Key::parse(ch as u16) {
+                Some(key) => (ch, key),
+                None => return ui::ReadType::None,
+            },
+            input => return self.handle_other_key(input),
+        };
+
+        let mut query = self.search.clone().unwrap_or_default();
+        match (key.modifier, key.kind) {
+            // stop searching, but stay where the match is
+            (Mod::None, Enter) => return self.end_search(false),
+            // escape goes back to the bottom
+            (Mod::None, Other('\u{1b}')) => return self.end_search(true),
+            (Mod::None, Backspace) => {
+                query.pop();
+            }
+            (Mod::None, Char(_)) | (Mod::None, Other(_)) | (Mod::Shift, Char(_)) => query.push(ch),
+            _ => {
+                if self.keybind(&key) == Some(ui::KeyRequest::Search) {
+                    self.ctx.state.queue(ui::Request::SearchNext);
+                }
+                return ui::ReadType::None;
+            }
+        }
+
+        self.ctx
+            .state
+            .queue(ui::Request::Search(Some(query.clone())));
+        self.search = Some(query);
+        self.draw_search();
+        ui::ReadType::None
+    }
+
+    // while searching the history, keys edit the query and the line is whatever it matched
+    fn handle_history_search_key(&mut self, input: pancurses::Input) -> ui::ReadType {
+        use super::ui::{KeyKind::*, Mod};
+
+        let (ch, key) = match input {
+            pancurses::Input::Character(ch) => match ui::Key::parse(ch as u16) {
+                Some(key) => (ch, key),
+                None => return ui::ReadType::None,
+            },
+            // anything else keeps the match, and then does what it usually does
+            input => {
+                self.buffer.end_history_search(true);
+                return self.handle_other_key(input);
+            }
+        };
+
+        let query = self.buffer.search_query().unwrap_or_default().to_string();
+        match (key.modifier, key.kind) {
+            (Mod::None, Enter) => self.buffer.end_history_search(true),
+            // escape puts back the line from before the search
+            (Mod::None, Other('\u{1b}')) => self.buffer.end_history_search(false),
+            (Mod::None, Backspace) => {
+                let mut query = query;
+                query.pop();
+                self.buffer.set_search_query(query);
+            }
+            (Mod::None, Char(_)) | (Mod::None, Other(_)) | (Mod::Shift, Char(_)) => {
+                let mut query = query;
+                query.push(ch);
+                self.buffer.set_search_query(query);
+            }
+            _ if self.keybind(&key) == Some(ui::KeyRequest::SearchHistory) => {
+                self.buffer.search_history()
+            }
+            _ => {
+                self.buffer.end_history_search(true);
+                return self.handle_modified_key(&key);
+            }
+        }
+
+        self.window.refresh();
+        ui::ReadType::None
+    }
+
+    fn end_search(&mut self, bottom: bool) -> ui::ReadType {
+        self.search = None;
+        self.ctx.state.queue(ui::Request::Search(None));
+        if bottom {
+            self.ctx
+                .state
+                .queue(ui::Request::Scroll(ui::Scroll::Bottom));
+        }
+        self.buffer.redraw();
+        self.window.refresh();
+        ui::ReadType::None
+    }
+
+    fn draw_search(&self) {
+        let query = self.search.as_deref().unwrap_or_default();
+        self.window.erase();
+        self.window.mvaddstr(0, 0, format!("search: {}", query));
+        self.window.refresh();
+    }
+
+    fn next_key(&mut self) -> Option<pancurses::Input> {
+        self.pending.pop_front().or_else(|| self.window.getch())
+    }
+
+    // whether another key came in right along with the last one
+    fn key_waiting(&mut self) -> bool {
+        if self.pending.is_empty() {
+            self.pending.extend(self.window.getch());
+        }
+        !self.pending.is_empty()
+    }
+
+    // whether an escape starts a bracketed paste, otherwise the keys after it are put back
+    fn paste_started(&mut self) -> bool {
+        let mut read = vec![];
+        for ch in PASTE_START.chars().skip(1) {
+            let input = self.next_key();
+            read.extend(input);
+            if input != Some(pancurses::Input::Character(ch)) {
+                for input in read.into_iter().rev() {
+                    self.pending.push_front(input);
+                }
+                return false;
+            }
+        }
+        true
+    }
+
+    fn start_burst(&mut self, text: String, suffix: String, bracketed: bool) -> ui::ReadType {
+        self.buffer.clear();
+        self.read_burst(Burst {
+            typed: text.len(),
+            text,
+            suffix,
+            bracketed,
+            last: Instant::now(),
+        })
+    }
+
+    // takes in every key that's waiting, the paste is over once they stop coming
+    fn read_burst(&mut self, mut burst: Burst) -> ui::ReadType {
+        while let Some(input) = self.next_key() {
+            burst.last = Instant::now();
+            if let pancurses::Input::Character(ch) = input {
+                burst.text.push(ch);
+            }
+            if burst.bracketed && burst.text.ends_with(PASTE_END) {
+                let len = burst.text.len() - PASTE_END.len();
+                burst.text.truncate(len);
+                return self.finish_burst(burst);
+            }
+        }
+
+        let gap = if burst.bracketed {
+            BRACKETED_PASTE_GAP
+        } else {
+            PASTE_GAP
+        };
+        if burst.last.elapsed() < Duration::from_millis(gap) {
+            self.burst = Some(burst);
+            return ui::ReadType::None;
+        }
+        self.finish_burst(burst)
+    }
+
+    // a single line goes into the input, any more have to be confirmed first
+    fn finish_burst(&mut self, burst: Burst) -> ui::ReadType {
+        let (line, cursor) = match burst.split() {
+            Pasted::Lines(lines) => return self.confirm(lines),
+            Pasted::Line(line, cursor) => (line, cursor),
+            Pasted::Typed(line, rest) => {
+                // the keys after the enter get typed into the next line
+                self.pending
+                    .extend(rest.chars().map(pancurses::Input::Character));
+                let cursor = line.chars().count();
+                (line, cursor)
+            }
+        };
+
+        let line = line.chars().collect::<Vec<_>>();
+        let len = self.buffer.line().len();
+        self.buffer.replace(0, len, &line);
+        self.buffer
+            .handle_command(&ui::Command::Move(ui::Move::Exact(cursor)));
+        self.window.refresh();
+
+        if burst.bracketed || line.is_empty() {
+            return ui::ReadType::None;
+        }
+        // it was an enter after all
+        ui::ReadType::Line(line.into_iter().collect())
+    }
+
+    // lines are held until they're confirmed, or added on to what's being composed
+    fn confirm(&mut self, lines: Vec<String>) -> ui::ReadType {
+        if let Some(compose) = self.compose.as_mut() {
+            compose.extend(lines);
+            self.ctx.state.set_composing(Some(compose.len()));
+            return ui::ReadType::None;
+        }
+
+        self.paste = Some(lines);
+        self.draw_paste();
+        ui::ReadType::None
+    }
+
+    fn handle_paste_key(&mut self, input: pancurses::Input) -> ui::ReadType {
+        use pancurses::Input::Character;
+
+        let lines = match self.paste.take() {
+            Some(lines) => lines,
+            None => return ui::ReadType::None,
+        };
+
+        // enter could be the end of a paste that came in slowly, so it doesn't answer
+        let read = match input {
+            Character('y') => ui::ReadType::Lines(lines),
+            Character('n') | Character('\x1b') => ui::ReadType::None,
+            Character('j') => {
+                let line = lines.join(" ").chars().collect::<Vec<_>>();
+                let len = self.buffer.line().len();
+                self.buffer.replace(0, len, &line);
+                ui::ReadType::None
+            }
+            // anything else leaves the question up
+            _ => {
+                self.paste = Some(lines);
+                return ui::ReadType::None;
+            }
+        };
+
+        self.buffer.redraw();
+        self.window.refresh();
+        read
+    }
+
+    fn draw_paste(&self) {
+        let lines = self.paste.as_ref().map_or(0, |lines| lines.len());
+        let plural = if lines == 1 { "" } else { "s" };
+        self.window.erase();
+        let prompt = format!("send {} line{}? (y)es, (n)o or (j)oin", lines, plural);
+        self.window.mvaddstr(0, 0, prompt);
+        self.window.refresh();
+    }
+
+    // enter adds the line to the others, and ending compose mode asks to send them all
+    fn toggle_compose(&mut self) {
+        match self.compose.take() {
+            Some(lines) => {
+                self.ctx.state.set_composing(None);
+                if !lines.is_empty() {
+                    self.confirm(lines);
+                }
+            }
+            None => {
+                self.compose = Some(vec![]);
+                self.ctx.state.set_composing(Some(0));
+            }
+        }
+    }
+
+    // an empty line ends compose mode too
+    fn compose_line(&mut self, line: String) -> ui::ReadType {
+        if line.is_empty() {
+            self.toggle_compose();
+            return ui::ReadType::None;
+        }
+
+        self.add_history();
+        self.clear_input();
+        if let Some(compose) = self.compose.as_mut() {
+            compose.push(line);
+            self.ctx.state.set_composing(Some(compose.len()));
+        }
+        ui::ReadType::None
+    }
+
+    /// finishes the word before the cursor, or swaps in the next candidate if it was just finished
+    fn complete(&mut self, forward: bool) -> ui::ReadType {
+        let cycling = self
+            .completion
+            .as_ref()
+            .is_some_and(|completion| completion.is_current(self.buffer.line()));
+        if !cycling {
+            let line = self.buffer.line()[..self.buffer.position()]
+                .iter()
+                .collect::<String>();
+            let start = line.rfind(' ').map_or(0, |pos| pos + 1);
+            let candidates = self.candidates(&line[..start], &line[start..]);
+            let start = line[..start].chars().count();
+            self.completion = ui::Completion::new(start, candidates);
+        }
+
+        if let Some(completion) = self.completion.as_mut() {
+            let (start, end) = if cycling {
+                (completion.start(), completion.end())
+            } else {
+                (completion.start(), self.buffer.position())
+            };
+            let text = completion.advance(forward).chars().collect::<Vec<_>>();
+            self.buffer.replace(start, end, &text);
+            completion.set_line(self.buffer.line());
+            self.window.refresh();
+        }
+        ui::ReadType::None
+    }
+
+    /// what `word` could be, given what comes `before` it in the line
+    fn candidates(&self, before: &str, word: &str) -> Vec<String> {
+        let state = &self.ctx.state;
+        let client = state.client();
+        let isupport = client
+            .as_ref()
+            .map(|client| client.state().isupport())
+            .unwrap_or_default();
+
+        let (_, buf) = state.buffers().current();
+        let channel = client
+            .as_ref()
+            .and_then(|client| client.state().channels().get(buf.name()));
+
+        let config = state.config();
+        let candidates = ui::Candidates {
+            commands: self.commands.iter().map(|s| s.to_string()).collect(),
+            keys: config
+                .borrow()
+                .keybinds
+                .iter()
+                .map(|(req, _)| req.to_string())
+                .collect(),
+            channels: client
+                .as_ref()
+                .map(|client| client.state().channels().names())
+                .unwrap_or_default(),
+            nicks: channel
+                .map(|channel| channel.users())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|member| member.nick)
+                .collect(),
+        };
+        candidates.matching(before, word, &isupport)
+    }
+
+    fn keybind(&self, key: &ui::Key) -> Option<ui::KeyRequest> {
+        let keybind = ui::KeyType::from(*key);
+        self.ctx
+            .state
+            .config()
+            .borrow()
+            .keybinds
+            .get(&keybind)
+            .cloned()
+    }
+
     fn handle_input_key(&mut self, ch: char) -> ui::ReadType {
         if let Some(key) = ui::Key::parse(ch as u16) {
             match (&key.modifier, &key.kind) {
@@ -131,12 +662,23 @@ impl Input {
         // why not?
         let window = self.buffer.display();
         for (i, ch) in window.iter().enumerate() {
-            self.window.mvaddch(0, i as i32, *ch);
+            self.window.mvaddch(0, i as i32, displayed(*ch));
         }
 
         self.buffer.handle_command(&ui::Command::Append(ch));
         ui::ReadType::None
     }
+
+    fn window(&self) -> Rc<Window> {
+        Rc::clone(&self.window)
+    }
+
+    fn create(parent: &Window, rect: Rect) -> Window {
+        let window = rect.subwin(parent);
+        window.nodelay(true);
+        window.keypad(true);
+        window.into()
+    }
 }
 
 impl_recv!(Input);
@@ -148,20 +690,33 @@ pub struct InputBuffer<M>
 where
     M: MoveableCursor,
 {
-    history: ui::History,
+    history: Rc<RefCell<ui::History>>,
+    // the reverse search through the history, while there is one
+    search: Option<HistorySearch>,
     width: usize,
     buf: Vec<char>,
     position: usize,
     window: Rc<M>,
 }
 
+struct HistorySearch {
+    query: String,
+    // the line from before the search, for when it's cancelled
+    original: Vec<char>,
+    // how far back from the newest line the match is
+    found: Option<usize>,
+    // nothing (further back) has the query in it
+    failed: bool,
+}
+
 impl<M> InputBuffer<M>
 where
     M: MoveableCursor,
 {
     pub fn new(width: usize, window: Rc<M>) -> Self {
         InputBuffer {
-            history: ui::History::new(),
+            history: Rc::new(RefCell::new(ui::History::new())),
+            search: None,
             width,
             buf: vec![],
             position: 0,
@@ -169,14 +724,135 @@ where
         }
     }
 
-    // TODO move this out of this
+    pub fn set_history(&mut self, history: Rc<RefCell<ui::History>>) {
+        self.history = history;
+    }
+
     pub fn add_history(&mut self) {
+        if self.buf.is_empty() {
+            return;
+        }
         let line = self.line().iter().cloned().collect::<String>();
-        self.history.append(line);
+        self.history.borrow_mut().append(line);
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.search.is_some()
+    }
+
+    pub fn search_query(&self) -> Option<&str> {
+        self.search.as_ref().map(|search| search.query.as_str())
+    }
+
+    /// starts a reverse search through the history, or looks further back if there is one
+    pub fn search_history(&mut self) {
+        let skip = match &self.search {
+            Some(search) => search.found.map_or(0, |n| n + 1),
+            None => {
+                self.search = Some(HistorySearch {
+                    query: String::new(),
+                    original: self.buf.clone(),
+                    found: None,
+                    failed: false,
+                });
+                self.draw_search();
+                return;
+            }
+        };
+        self.find_history(skip);
+    }
+
+    /// searches for `query` instead, starting from the newest line again
+    pub fn set_search_query(&mut self, query: String) {
+        if let Some(search) = self.search.as_mut() {
+            search.query = query;
+        }
+        self.find_history(0);
+    }
+
+    /// `accept` keeps the line that was found, otherwise the line goes back to how it was
+    pub fn end_history_search(&mut self, accept: bool) {
+        if let Some(search) = self.search.take() {
+            if !accept {
+                self.buf = search.original;
+            }
+        }
+        self.position = self.buf.len();
+        self.redraw();
+    }
+
+    fn find_history(&mut self, skip: usize) {
+        let search = match self.search.as_mut() {
+            Some(search) => search,
+            None => return,
+        };
+
+        if search.query.is_empty() {
+            self.buf = search.original.clone();
+            search.found = None;
+            search.failed = false;
+        } else {
+            let history = self.history.borrow();
+            match history.find(&search.query, skip) {
+                Some((n, line)) => {
+                    self.buf = line.chars().collect();
+                    search.found = Some(n);
+                    search.failed = false;
+                }
+                None => search.failed = true,
+            }
+        }
+
+        self.position = self.buf.len();
+        self.draw_search();
+    }
+
+    // the query and the match, keeping the end of them in view
+    fn draw_search(&self) {
+        let search = match &self.search {
+            Some(search) => search,
+            None => return,
+        };
+
+        let prompt = if search.failed {
+            "failed history search"
+        } else {
+            "history search"
+        };
+        let text = format!("{} `{}`: ", prompt, search.query)
+            .chars()
+            .chain(self.buf.iter().cloned())
+            .collect::<Vec<_>>();
+
+        // leaving room for the cursor
+        let start = (text.len() + 1).saturating_sub(self.width);
+        self.window.clear();
+        for (i, ch) in text[start..].iter().enumerate() {
+            self.window.insert_at(i, *ch);
+        }
+        self.window.move_cursor(text.len() - start);
     }
 
-    pub fn clear_history(&mut self) {
-        self.history.clear()
+    /// moves the line to a new window, the cursor stays where it was in the line
+    pub fn resize(&mut self, width: usize, window: Rc<M>) {
+        self.width = width;
+        self.window = window;
+        self.redraw();
+    }
+
+    /// draws the line again, after something else used the window
+    pub fn redraw(&self) {
+        if self.search.is_some() {
+            return self.draw_search();
+        }
+
+        self.window.clear();
+
+        let offset = self.buf.len() - self.display().len();
+        for (i, ch) in self.display().iter().enumerate() {
+            self.window.insert_at(i, *ch);
+        }
+        self.window.move_cursor(self.position.saturating_sub(offset));
     }
 
     pub fn clear(&mut self) {
@@ -198,6 +874,18 @@ where
         &self.buf
     }
 
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// swaps `start..end` of the line for `text`, leaving the cursor after it
+    pub fn replace(&mut self, start: usize, end: usize, text: &[char]) {
+        let end = min(end, self.buf.len());
+        self.buf.splice(start..end, text.iter().cloned());
+        self.position = start + text.len();
+        self.redraw();
+    }
+
     pub fn handle_command(&mut self, cmd: &ui::Command) {
         use super::ui::{Command::*, Move::*};
 
@@ -287,11 +975,11 @@ where
 
             Recall(mv) => {
                 let history = match match mv {
-                    Forward => self.history.forward(),
-                    Backward => self.history.backward(),
+                    Forward => self.history.borrow_mut().forward().cloned(),
+                    Backward => self.history.borrow_mut().backward().cloned(),
                     _ => unreachable!(),
                 } {
-                    Some(history) => history.clone(),
+                    Some(history) => history,
                     None => return,
                 };
 
@@ -380,3 +1068,62 @@ where
         self.window.move_cursor(self.position);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn burst(typed: &str, rest: &str, suffix: &str, bracketed: bool) -> Burst {
+        Burst {
+            text: format!("{}{}", typed, rest),
+            typed: typed.len(),
+            suffix: suffix.to_string(),
+            bracketed,
+            last: Instant::now(),
+        }
+    }
+
+    fn lines(lines: &[&str]) -> Pasted {
+        Pasted::Lines(lines.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn pasted_lines() {
+        let pasted = burst("first\n", "second line\r\nthird\n\n", "", false);
+        assert_eq!(pasted.split(), lines(&["first", "second line", "third"]));
+
+        // short lines are still a paste once there's more than one enter
+        let pasted = burst("a\n", "b\nc\n", "", false);
+        assert_eq!(pasted.split(), lines(&["a", "b", "c"]));
+        let pasted = burst("hello\n", "a\n", "", false);
+        assert_eq!(pasted.split(), lines(&["hello", "a"]));
+
+        // whatever was after the cursor ends up on the last line
+        let pasted = burst("say ", "one\ntwo", " after", true);
+        assert_eq!(pasted.split(), lines(&["say one", "two after"]));
+    }
+
+    #[test]
+    fn pasted_line() {
+        // a single line goes in at the cursor, and the cursor goes after it
+        let pasted = burst("say ", "hello there", " after", true);
+        assert_eq!(
+            pasted.split(),
+            Pasted::Line("say hello there after".into(), 15)
+        );
+
+        let pasted = burst("", "", "", true);
+        assert_eq!(pasted.split(), Pasted::Line("".into(), 0));
+    }
+
+    #[test]
+    fn typed_quickly() {
+        // a couple of keys after enter are the start of the next line
+        let pasted = burst("hello\n", "ok", "", false);
+        assert_eq!(pasted.split(), Pasted::Typed("hello".into(), "ok".into()));
+
+        // but not in a bracketed paste
+        let pasted = burst("", "hello\nok", "", true);
+        assert_eq!(pasted.split(), lines(&["hello", "ok"]));
+    }
+}
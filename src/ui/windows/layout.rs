@@ -0,0 +1,102 @@
+use super::*;
+use std::cmp::max;
+
+/// Where a subwindow goes in the container
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub y: i32,
+    pub x: i32,
+    pub rows: i32,
+    pub cols: i32,
+}
+
+impl Rect {
+    pub fn subwin(self, parent: &Window) -> pancurses::Window {
+        parent
+            .subwin(self.rows, self.cols, self.y, self.x)
+            .expect("create subwindow")
+    }
+}
+
+/// The geometry of every subwindow, for the container's current size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+    pub output: Rect,
+    pub input: Rect,
+    pub status: Rect,
+    pub nicklist: Rect,
+}
+
+impl Layout {
+    /// the status bar sits above the input. the nicklist takes a fifth of the width,
+    /// the output only gives it up when it's shown
+    ///
+    /// `size` is the container's (rows, cols)
+    pub fn new(size: (i32, i32), nicklist: bool) -> Self {
+        // curses treats a size of 0 as "to the edge", so never ask for one
+        let (rows, cols) = size;
+        let (rows, cols) = (max(rows, 3), max(cols, 2));
+        let side = max(cols / 5, 1);
+        let width = if nicklist { cols - side } else { cols };
+
+        Layout {
+            output: Rect {
+                y: 0,
+                x: 0,
+                rows: rows - 2,
+                cols: width,
+            },
+            input: Rect {
+                y: rows - 1,
+                x: 0,
+                rows: 1,
+                cols,
+            },
+            status: Rect {
+                y: rows - 2,
+                x: 0,
+                rows: 1,
+                cols,
+            },
+            nicklist: Rect {
+                y: 0,
+                x: cols - side,
+                rows: rows - 2,
+                cols: side,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(y: i32, x: i32, rows: i32, cols: i32) -> Rect {
+        Rect { y, x, rows, cols }
+    }
+
+    #[test]
+    fn layout() {
+        let layout = Layout::new((24, 80), false);
+        assert_eq!(layout.output, rect(0, 0, 22, 80));
+        assert_eq!(layout.status, rect(22, 0, 1, 80));
+        assert_eq!(layout.input, rect(23, 0, 1, 80));
+
+        let layout = Layout::new((24, 80), true);
+        assert_eq!(layout.output, rect(0, 0, 22, 64));
+        assert_eq!(layout.nicklist, rect(0, 64, 22, 16));
+        assert_eq!(layout.input.cols, 80);
+    }
+
+    #[test]
+    fn tiny_layout() {
+        // nothing is ever zero sized
+        for &size in &[(0, 0), (1, 1), (3, 2)] {
+            let layout = Layout::new(size, true);
+            for rect in &[layout.output, layout.input, layout.status, layout.nicklist] {
+                assert!(rect.rows > 0 && rect.cols > 0, "{:?} {:?}", size, rect);
+            }
+        }
+    }
+}
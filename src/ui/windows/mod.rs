@@ -1,4 +1,5 @@
-use super::ui;
+use super::{irc, ui};
+use std::cell::RefCell;
 use std::ops::Deref;
 use std::rc::Rc;
 
@@ -37,11 +38,20 @@ impl MoveableCursor for Window {
     }
     fn insert_at(&self, pos: usize, ch: char) {
         self.mv(self.get_cur_y(), pos as i32);
-        self.insch(ch);
+        self.insch(displayed(ch));
         self.mv(self.get_cur_y(), pos as i32 + 1);
     }
 }
 
+// formatting codes are shown as their letter, reversed, so they only take up one column
+fn displayed(ch: char) -> pancurses::chtype {
+    if ch.is_ascii_control() {
+        pancurses::chtype::from(ch as u8 ^ 0x40) | pancurses::A_REVERSE
+    } else {
+        ch as pancurses::chtype
+    }
+}
+
 macro_rules! impl_recv {
     ($t:ty) => {
         impl ui::MessageReceiver<ui::Request> for $t {
@@ -54,6 +64,7 @@ macro_rules! impl_recv {
             fn output(&self, output: ui::Output, eol: bool) {
                 fn insert_into(
                     window: &Window,
+                    colors: &RefCell<ui::Palette>,
                     color: impl Into<ui::ColorPair>,
                     s: impl AsRef<str>,
                 ) {
@@ -64,33 +75,38 @@ macro_rules! impl_recv {
                     window.addstr(s);
         
                     let (ny, nx) = window.get_cur_yx();
-                    window.mvchgat(
-                        y,
-                        x,
-                        s.len() as i32,
-                        if color.bold {
-                            pancurses::A_BOLD
-                        } else {
-                            pancurses::A_NORMAL
-                        },
-                        color.fg.into(),
-                    );
+                    let mut attrs = pancurses::A_NORMAL;
+                    if color.bold {
+                        attrs |= pancurses::A_BOLD;
+                    }
+                    if color.italic {
+                        attrs |= pancurses::A_ITALIC;
+                    }
+                    if color.underline {
+                        attrs |= pancurses::A_UNDERLINE;
+                    }
+                    if color.reverse {
+                        attrs |= pancurses::A_REVERSE;
+                    }
+                    let pair = colors.borrow_mut().pair(&color);
+                    window.mvchgat(y, x, s.len() as i32, attrs, pair);
                     window.mv(ny, nx);
                 }        
 
-                let window = &self.window;
+                let window = self.window();
                 for (r, cp) in output.colors.iter() {
-                    insert_into(&window, *cp, &output.data[r.start..r.end])
+                    insert_into(&window, &self.ctx.colors, *cp, &output.data[r.start..r.end])
                 }
                 if eol {
-                    self.window.addch('\n');
+                    window.addch('\n');
                 }
                 window.refresh();
             }
         
             fn clear(&self) {
-                self.window.clear();
-                self.window.refresh();
+                let window = self.window();
+                window.clear();
+                window.refresh();
             }
         }
     };
@@ -99,8 +115,10 @@ macro_rules! impl_recv {
 import!(
     container,
     input,
+    layout,
     nicklist,
-    output
+    output,
+    status
 );
 
 pub use self::container::Container;
@@ -108,11 +126,14 @@ pub use self::container::Container;
 pub struct Context {
     pub(crate) state: Rc<ui::State>,
     pub(crate) queue: Rc<ui::MessageQueue<ui::Request>>,
+    // color pairs are shared by every window
+    pub(crate) colors: RefCell<ui::Palette>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum ReadType {
     Line(String),
+    Lines(Vec<String>),
     FKey(pancurses::Input),
     None,
 }
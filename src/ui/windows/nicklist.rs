@@ -1,30 +1,186 @@
 use super::*;
+use std::cell::{Cell, Ref, RefCell};
 
 pub struct Nicklist {
     parent: Rc<Window>,
-    window: Window,
+    window: RefCell<Window>,
     ctx: Rc<Context>,
+    visible: Cell<bool>,
+    members: RefCell<Vec<irc::Member>>,
+    scroll: Cell<usize>,
 }
 
 impl Nicklist {
-    pub fn new(parent: Rc<Window>, ctx: Rc<Context>) -> Self {
-        let bounds = parent.get_max_yx();
-        let window = parent
-            .subwin(0, 0, 0, bounds.1 - bounds.1 / 5)
-            .expect("create nicklist");
-
+    pub fn new(parent: Rc<Window>, ctx: Rc<Context>, rect: Rect) -> Self {
+        let window = rect.subwin(&parent);
         Self {
             parent,
-            window: window.into(),
+            window: RefCell::new(window.into()),
             ctx,
+            visible: Cell::new(false),
+            members: RefCell::new(vec![]),
+            scroll: Cell::new(0),
         }
     }
 
     pub fn is_visible(&self) -> bool {
-        false
+        self.visible.get()
+    }
+
+    pub fn toggle(&self) {
+        self.visible.set(!self.visible.get());
+        self.scroll.set(0);
+        if self.is_visible() {
+            self.redraw();
+        } else {
+            ui::Outputter::clear(self);
+        }
+    }
+
+    /// replaces the members, keeping the scroll position if it still fits
+    pub fn set_members(&self, members: Vec<irc::Member>) {
+        *self.members.borrow_mut() = members;
+        self.clamp_scroll();
+        if self.is_visible() {
+            self.redraw();
+        }
+    }
+
+    pub fn resize(&self, rect: Rect) {
+        *self.window.borrow_mut() = rect.subwin(&self.parent).into();
+        self.clamp_scroll();
+        self.redraw();
     }
 
-    pub fn toggle(&self) {}
+    pub fn scroll_up(&self) {
+        self.scroll_by(true);
+    }
+
+    pub fn scroll_down(&self) {
+        self.scroll_by(false);
+    }
+
+    fn scroll_by(&self, up: bool) {
+        let rows = self.size().0;
+        let len = self.members.borrow().len();
+        self.scroll.set(scroll(self.scroll.get(), up, len, rows));
+        self.redraw();
+    }
+
+    fn clamp_scroll(&self) {
+        let max = max_scroll(self.members.borrow().len(), self.size().0);
+        if self.scroll.get() > max {
+            self.scroll.set(max)
+        }
+    }
+
+    fn redraw(&self) {
+        if !self.is_visible() {
+            return;
+        }
+
+        {
+            let window = self.window();
+            window.erase();
+            window.mv(0, 0);
+        }
+
+        let (rows, width) = self.size();
+        let theme = self.ctx.state.theme();
+        let casemapping = self
+            .ctx
+            .state
+            .client()
+            .map(|client| client.state().isupport().casemapping)
+            .unwrap_or_default();
+
+        let members = self.members.borrow();
+        let shown = members.iter().skip(self.scroll.get()).take(rows);
+        for (i, member) in shown.enumerate() {
+            // the first column is a gap between it and the output
+            let mut output = ui::Output::new();
+            output.add(" ");
+            match member.highest() {
+                Some(symbol) => output.fg(prefix_color(symbol)).add(symbol.to_string()),
+                None => output.add(" "),
+            };
+            let nick = member
+                .nick
+                .chars()
+                .take(width.saturating_sub(2))
+                .collect::<String>();
+            let color = theme
+                .nick_color(&member.nick, casemapping)
+                .unwrap_or_else(|| ui::Color::White.into());
+            output.color(color).add(nick);
+
+            // the last line can't end with a newline or it'd scroll
+            ui::Outputter::output(self, output.build(), i + 1 < rows);
+        }
+        self.window().refresh();
+    }
+
+    fn window(&self) -> Ref<'_, Window> {
+        self.window.borrow()
+    }
+
+    fn size(&self) -> (usize, usize) {
+        let (rows, cols) = self.window().get_max_yx();
+        (rows as usize, cols as usize)
+    }
+}
+
+// half a screen at a time, stopping once the last member is on the bottom row
+fn scroll(pos: usize, up: bool, len: usize, rows: usize) -> usize {
+    let page = ::std::cmp::max(rows / 2, 1);
+    if up {
+        pos.saturating_sub(page)
+    } else {
+        ::std::cmp::min(pos + page, max_scroll(len, rows))
+    }
+}
+
+fn max_scroll(len: usize, rows: usize) -> usize {
+    len.saturating_sub(rows)
+}
+
+fn prefix_color(symbol: char) -> ui::Color {
+    match symbol {
+        '~' => ui::Color::Red,
+        '&' => ui::Color::Magenta,
+        '@' => ui::Color::Green,
+        '%' => ui::Color::Yellow,
+        '+' => ui::Color::Cyan,
+        _ => ui::Color::BrightWhite,
+    }
 }
 
 impl_recv!(Nicklist);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrolling() {
+        // 25 members in 10 rows, 5 at a time
+        assert_eq!(scroll(0, false, 25, 10), 5);
+        assert_eq!(scroll(5, false, 25, 10), 10);
+        assert_eq!(scroll(10, false, 25, 10), 15);
+        assert_eq!(scroll(15, false, 25, 10), 15);
+        assert_eq!(scroll(15, true, 25, 10), 10);
+        assert_eq!(scroll(3, true, 25, 10), 0);
+
+        // everyone fits
+        assert_eq!(scroll(0, false, 5, 10), 0);
+        // a single row still moves
+        assert_eq!(scroll(0, false, 5, 1), 1);
+    }
+
+    #[test]
+    fn prefixes() {
+        assert_eq!(prefix_color('@'), ui::Color::Green);
+        assert_eq!(prefix_color('+'), ui::Color::Cyan);
+        assert_eq!(prefix_color('!'), ui::Color::BrightWhite);
+    }
+}
@@ -1,33 +1,41 @@
 use super::*;
+use std::cell::{Ref, RefCell};
 
 pub struct Output {
     parent: Rc<Window>,
-    window: Window,
+    window: RefCell<Window>,
     ctx: Rc<Context>,
 }
 
 impl Output {
-    pub fn new(parent: Rc<Window>, ctx: Rc<Context>) -> Self {
-        let (h, w) = parent.get_max_yx();
-        let window = parent
-            .subwin(h - 1, w, 0, 0)
-            .expect("create output subwindow");
-
-        window.setscrreg(0, window.get_max_y());
-        window.scrollok(true);
-
+    pub fn new(parent: Rc<Window>, ctx: Rc<Context>, rect: Rect) -> Self {
+        let window = Self::create(&parent, rect);
         Self {
             parent,
-            window: window.into(),
+            window: RefCell::new(window),
             ctx,
         }
     }
-}
 
-//        self.window.resize(rows, cols);
-//   pub fn clear(&self) {
-//        self.window.erase();
-//        self.window.refresh();
-//    }
+    /// the old contents are left behind, the buffer has to be displayed again
+    pub fn resize(&self, rect: Rect) {
+        *self.window.borrow_mut() = Self::create(&self.parent, rect);
+    }
+
+    pub fn rows(&self) -> usize {
+        self.window().get_max_y() as usize
+    }
+
+    fn window(&self) -> Ref<'_, Window> {
+        self.window.borrow()
+    }
+
+    fn create(parent: &Window, rect: Rect) -> Window {
+        let window = rect.subwin(parent);
+        window.setscrreg(0, window.get_max_y());
+        window.scrollok(true);
+        window.into()
+    }
+}
 
 impl_recv!(Output);
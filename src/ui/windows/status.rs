@@ -0,0 +1,94 @@
+use super::*;
+use std::cell::{Ref, RefCell};
+
+pub struct Status {
+    parent: Rc<Window>,
+    window: RefCell<Window>,
+    ctx: Rc<Context>,
+}
+
+impl Status {
+    pub fn new(parent: Rc<Window>, ctx: Rc<Context>, rect: Rect) -> Self {
+        let window = rect.subwin(&parent);
+        Self {
+            parent,
+            window: RefCell::new(window.into()),
+            ctx,
+        }
+    }
+
+    pub fn resize(&self, rect: Rect) {
+        *self.window.borrow_mut() = rect.subwin(&self.parent).into();
+        self.refresh();
+    }
+
+    /// `[nick] network 0:*status 1:#channel  composing 2 lines lag 0.2s`
+    pub fn refresh(&self) {
+        use super::ui::{Activity, Color};
+
+        let state = &self.ctx.state;
+        let client = state.client();
+        let client = client.as_ref().map(|client| client.state());
+
+        let mut output = ui::Output::new();
+        output.fg(Color::White).add("[");
+        match client.as_ref().and_then(|client| client.nickname()) {
+            Some(nick) => output.fg(Color::Cyan).add(nick),
+            None => output.add("*"),
+        };
+        if client.as_ref().is_some_and(|client| client.is_away()) {
+            output.fg(Color::Yellow).add(" (away)");
+        }
+        output.fg(Color::White).add("] ");
+
+        if let Some(network) = state.network() {
+            output.fg(Color::Green).add(network).add(" ");
+        }
+
+        let buffers = state.buffers();
+        let (active, _) = buffers.current();
+        for (i, buf) in buffers.buffers().iter().enumerate() {
+            if i == active {
+                output.fg(Color::BrightWhite).bold();
+            } else {
+                output.fg(match buf.activity() {
+                    Activity::Highlight => Color::Magenta,
+                    Activity::Message => Color::Yellow,
+                    Activity::None => Color::White,
+                });
+            }
+            output.add(format!("{}:{}", i, buf.name()));
+            match (buf.unread(), buf.highlights()) {
+                (0, _) => {}
+                (unread, 0) => {
+                    output.add(format!("({})", unread));
+                }
+                (unread, highlights) => {
+                    output.add(format!("({}, {}!)", unread, highlights));
+                }
+            }
+            output.fg(Color::White).add(" ");
+        }
+
+        if let Some(lines) = state.composing() {
+            let plural = if lines == 1 { "" } else { "s" };
+            output
+                .fg(Color::Yellow)
+                .add(format!(" composing {} line{}", lines, plural));
+        }
+
+        if let Some(lag) = client.as_ref().and_then(|client| client.lag()) {
+            let lag = format!(" lag {}.{}s", lag.as_secs(), lag.subsec_millis() / 100);
+            output.fg(Color::White).add(lag);
+        }
+
+        self.window().erase();
+        ui::Outputter::output(self, output.build(), false);
+    }
+
+    fn window(&self) -> Ref<'_, Window> {
+        self.window.borrow()
+    }
+}
+
+impl_recv!(Status);